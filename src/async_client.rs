@@ -0,0 +1,826 @@
+//! This module contains an asynchronous variant of `MprisClient`. It is only available with the
+//! `async` feature.
+//!
+//! Method calls and property accesses return futures, and signals are delivered as a `Stream`.
+//! The futures do not depend on a particular executor: each `AsyncMprisClient` drives its D-Bus
+//! connection from a background thread, which waits on the socket and hands the replies over to
+//! the futures.
+//!
+//! ```no_run
+//! extern crate futures;
+//! extern crate mpris;
+//!
+//! use futures::StreamExt;
+//! use futures::executor::block_on;
+//! use mpris::async_client::AsyncMprisClient;
+//!
+//! # fn main() {
+//! let client = AsyncMprisClient::new("vlc", 1000).unwrap();
+//! block_on(client.player.play()).unwrap();
+//! let identity = block_on(client.root.identity()).unwrap();
+//!
+//! block_on(client.signals().for_each(|signal| {
+//!     println!("{}: {:?}", identity, signal);
+//!     futures::future::ready(())
+//! }));
+//! # }
+//! ```
+use dbus::{Message, MessageItem, MessageType, WatchEvent};
+use dbus::arg::{RefArg, Variant};
+use futures::{Future, Stream};
+use futures::channel::{mpsc, oneshot};
+use futures::task::{Context, Poll};
+use std::collections::HashMap;
+use std::mem;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use client::{ChangedProperty, MprisSignal, PropMap, SendConnection, MPRIS_MATCH_RULES,
+             get_name_owner, open_session_bus, playlist_id_to_path, playlists_from_reply,
+             props_from_reply, track_ids_to_paths, tracks_metadata_from_reply};
+use decode::DecodeMode;
+use errors::*;
+
+
+/// The longest time the connection thread waits on the socket before it checks for calls which
+/// timed out, and whether the client is gone.
+const IO_POLL_INTERVAL_MS: u64 = 100;
+
+/// What a pending call receives from the connection thread.
+#[derive(Debug)]
+enum Reply {
+    Message(Message),
+    TimedOut,
+}
+
+/// A call which waits for its reply.
+#[derive(Debug)]
+struct PendingReply {
+    sender: oneshot::Sender<Reply>,
+    deadline: Option<Instant>,
+}
+
+/// The part of `AsyncDBusConn` which is guarded by its `Mutex`.
+#[derive(Debug)]
+struct ConnState {
+    conn: SendConnection,
+    pending: HashMap<u32, PendingReply>,
+    subscribers: Vec<mpsc::UnboundedSender<Message>>,
+}
+
+/// Abstraction over the non-blocking DBUS connection of an `AsyncMprisClient`.
+///
+/// Messages are sent directly by the caller, while the replies and signals are received by the
+/// connection thread, see `run_connection_thread`.
+#[derive(Debug)]
+struct AsyncDBusConn {
+    state: Mutex<ConnState>,
+    player_name: String,
+    bus_name: String,
+    unique_bus_name: String,
+    timeout: i32,
+    decode_mode: Mutex<DecodeMode>,
+}
+
+impl AsyncDBusConn {
+    /// Constructs a new `AsyncDBusConn` for `org.mpris.MediaPlayer2.playerName` and starts its
+    /// connection thread.
+    fn new(player_name: &str, timeout_ms: i32) -> Result<Arc<Self>> {
+        let conn = open_session_bus()?;
+        for rule in &MPRIS_MATCH_RULES {
+            conn.add_match(rule)?;
+        }
+
+        let bus_name = format!("org.mpris.MediaPlayer2.{}", player_name);
+        let unique_bus_name = get_name_owner(&conn, player_name, &bus_name, timeout_ms)?;
+
+        let dbus_conn = Arc::new(AsyncDBusConn {
+            state: Mutex::new(ConnState {
+                conn: SendConnection(conn),
+                pending: HashMap::new(),
+                subscribers: Vec::new(),
+            }),
+            player_name: player_name.to_string(),
+            bus_name,
+            unique_bus_name,
+            timeout: timeout_ms,
+            decode_mode: Mutex::new(DecodeMode::Lenient),
+        });
+        let weak_conn = Arc::downgrade(&dbus_conn);
+        thread::Builder::new()
+            .name(format!("mpris {}", player_name))
+            .spawn(move || run_connection_thread(&weak_conn))
+            .chain_err(|| "Could not spawn the connection thread.")?;
+        Ok(dbus_conn)
+    }
+
+    /// Locks the connection.
+    fn state(&self) -> MutexGuard<'_, ConnState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the mode in which metadata is decoded.
+    fn decode_mode(&self) -> DecodeMode {
+        *self.decode_mode.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns a function which converts the D-Bus error of a call of `member` of `interface`.
+    fn error(&self, interface: &str, member: &str) -> impl FnOnce(::dbus::Error) -> Error + Send + 'static {
+        let (player, interface, member) = (self.player_name.clone(), interface.to_string(), member.to_string());
+        move |err| dbus_error(err, &player, &interface, &member)
+    }
+
+    /// Sends a call of `member` of `interface` and registers it for its reply.
+    fn send(&self, msg: Message, interface: &str, member: &str) -> Result<oneshot::Receiver<Reply>> {
+        let (sender, receiver) = oneshot::channel();
+        let deadline = if self.timeout < 0 {
+            None
+        } else {
+            Some(Instant::now() + Duration::from_millis(self.timeout as u64))
+        };
+
+        let mut state = self.state();
+        let serial = state.conn.send(msg).map_err(|_| {
+            ErrorKind::Disconnected(self.player_name.clone(), interface.to_string(), member.to_string())
+        })?;
+        state.pending.insert(serial, PendingReply { sender, deadline });
+        Ok(receiver)
+    }
+
+    /// Calls a DBUS method and converts the reply with `convert`.
+    ///
+    /// `args` are appended to the method call in the given order.
+    fn call_method<T, F>(&self, interface: &str, member: &str, args: &[MessageItem], convert: F) -> PendingCall<T>
+        where F: FnOnce(Message) -> Result<T> + Send + 'static
+    {
+        let msg = Message::new_method_call(&self.bus_name, "/org/mpris/MediaPlayer2", interface, member);
+        let reply = msg.map_err(Error::from)
+            .and_then(|mut msg| {
+                msg.append_items(args);
+                self.send(msg, interface, member)
+            });
+        let error = self.error(interface, member);
+        PendingCall::new(reply, move |reply| convert(reply.map_err(error)?))
+    }
+
+    /// Calls a DBUS method without returning a value.
+    fn call_method_without_reply(&self, interface: &str, member: &str, args: &[MessageItem]) -> PendingCall<()> {
+        self.call_method(interface, member, args, |_| Ok(()))
+    }
+
+    /// Sends a call to a method of `org.freedesktop.DBus.Properties`.
+    fn call_properties(&self, member: &str, args: &[MessageItem]) -> Result<oneshot::Receiver<Reply>> {
+        let mut msg = Message::new_method_call(&self.bus_name,
+                                               "/org/mpris/MediaPlayer2",
+                                               "org.freedesktop.DBus.Properties",
+                                               member)?;
+        msg.append_items(args);
+        self.send(msg, "org.freedesktop.DBus.Properties", member)
+    }
+
+    /// Reads a DBUS property, converts it into the matching `ChangedProperty` and extracts the
+    /// value with `extract`.
+    fn get_typed_prop<T: 'static>(&self,
+                                  interface: &str,
+                                  member: &'static str,
+                                  extract: fn(ChangedProperty) -> Result<T>)
+                                  -> PendingCall<T> {
+        let reply = self.call_properties("Get", &[interface.into(), member.into()]);
+        let (error, mode) = (self.error(interface, member), self.decode_mode());
+        PendingCall::new(reply, move |reply| extract(typed_prop_from_reply(member, &reply.map_err(error)?, mode)?))
+    }
+
+    /// Safely reads an optional DBUS property, converts it into the matching `ChangedProperty`
+    /// and extracts the value with `extract`.
+    fn get_optional_typed_prop<T: 'static>(&self,
+                                           interface: &str,
+                                           member: &'static str,
+                                           extract: fn(ChangedProperty) -> Result<T>)
+                                           -> PendingCall<Option<T>> {
+        let reply = self.call_properties("Get", &[interface.into(), member.into()]);
+        let (error, mode) = (self.error(interface, member), self.decode_mode());
+        PendingCall::new(reply, move |reply| match reply {
+            Ok(reply) => Ok(Some(extract(typed_prop_from_reply(member, &reply, mode)?)?)),
+            Err(ref err) if match_dbus_err(err, "org.freedesktop.DBus.Error.UnknownProperty") => Ok(None),
+            Err(err) => Err(error(err)),
+        })
+    }
+
+    /// Reads all properties of `interface` with a single `GetAll` call and converts them with
+    /// `convert`, which decodes metadata in the given mode.
+    fn get_all_props<T: 'static>(&self, interface: &str, convert: fn(PropMap, DecodeMode) -> T) -> PendingCall<T> {
+        let reply = self.call_properties("GetAll", &[interface.into()]);
+        let (error, mode) = (self.error(interface, "GetAll"), self.decode_mode());
+        PendingCall::new(reply, move |reply| Ok(convert(props_from_reply(&reply.map_err(error)?)?, mode)))
+    }
+
+    /// Writes a DBUS property.
+    fn set_prop(&self, interface: &str, member: &'static str, value: MessageItem) -> PendingCall<()> {
+        let reply = self.call_properties(
+            "Set",
+            &[interface.into(), member.into(), MessageItem::Variant(Box::new(value))],
+        );
+        let (player, interface) = (self.player_name.clone(), interface.to_string());
+        PendingCall::new(reply, move |reply| {
+            reply.map(|_| ())
+                .map_err(|err| set_prop_error(err, &player, "/org/mpris/MediaPlayer2", &interface, member))
+        })
+    }
+
+    /// Handles the socket events in `fds`, then passes the received replies and signals on and
+    /// fails the calls which timed out.
+    fn dispatch(&self, fds: &[::libc::pollfd]) {
+        let mut state = self.state();
+        for fd in fds.iter().filter(|fd| fd.revents != 0) {
+            state.conn.watch_handle(fd.fd, WatchEvent::from_revents(fd.revents));
+        }
+        let messages: Vec<Message> = state.conn.incoming(0).collect();
+
+        for msg in messages {
+            match msg.msg_type() {
+                MessageType::MethodReturn | MessageType::Error => {
+                    let pending = msg.get_reply_serial().and_then(|serial| state.pending.remove(&serial));
+                    if let Some(pending) = pending {
+                        let _ = pending.sender.send(Reply::Message(msg));
+                    }
+                }
+                MessageType::Signal if self.is_from_player(&msg) => {
+                    state.subscribers.retain(|subscriber| {
+                        copy_signal(&msg)
+                            .map(|copy| subscriber.unbounded_send(copy).is_ok())
+                            .unwrap_or(true)
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let now = Instant::now();
+        let finished: Vec<u32> = state.pending.iter()
+            .filter(|&(_, pending)| {
+                pending.sender.is_canceled() || pending.deadline.map(|deadline| deadline <= now).unwrap_or(false)
+            })
+            .map(|(serial, _)| *serial)
+            .collect();
+        for serial in finished {
+            if let Some(pending) = state.pending.remove(&serial) {
+                let _ = pending.sender.send(Reply::TimedOut);
+            }
+        }
+    }
+
+    /// Returns how long the connection thread may wait for the next event.
+    fn poll_timeout(&self) -> Duration {
+        let now = Instant::now();
+        let max_timeout = Duration::from_millis(IO_POLL_INTERVAL_MS);
+        self.state().pending.values()
+            .filter_map(|pending| pending.deadline)
+            .map(|deadline| deadline.saturating_duration_since(now))
+            .fold(max_timeout, |timeout, remaining| timeout.min(remaining))
+    }
+
+    /// Checks whether `msg` was sent by the media player.
+    fn is_from_player(&self, msg: &Message) -> bool {
+        msg.sender()
+            .map(|sender| {
+                &sender as &str == self.unique_bus_name || &sender as &str == self.bus_name
+            })
+            .unwrap_or(false)
+    }
+
+    /// Returns a new stream of the media player's signals.
+    fn subscribe(&self) -> mpsc::UnboundedReceiver<Message> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.state().subscribers.push(sender);
+        receiver
+    }
+}
+
+/// Drives the connection of `dbus_conn` until the client is dropped or the connection is closed.
+///
+/// The connection is only locked while messages are read, but not while waiting for them, so
+/// method calls are sent without delay.
+fn run_connection_thread(dbus_conn: &Weak<AsyncDBusConn>) {
+    loop {
+        let (mut fds, timeout) = match dbus_conn.upgrade() {
+            Some(dbus_conn) => {
+                let fds: Vec<::libc::pollfd> = dbus_conn.state().conn.watch_fds()
+                    .iter()
+                    .map(|watch| watch.to_pollfd())
+                    .collect();
+                (fds, dbus_conn.poll_timeout())
+            }
+            None => return,
+        };
+
+        let timeout_ms = timeout.as_secs() as i32 * 1000 + timeout.subsec_millis() as i32;
+        unsafe {
+            ::libc::poll(fds.as_mut_ptr(), fds.len() as ::libc::nfds_t, timeout_ms);
+        }
+
+        match dbus_conn.upgrade() {
+            Some(ref dbus_conn) if dbus_conn.state().conn.is_connected() => dbus_conn.dispatch(&fds),
+            _ => return,
+        }
+    }
+}
+
+/// Converts the reply of `org.freedesktop.DBus.Properties.Get` into the matching
+/// `ChangedProperty`, and decodes metadata in the `mode`.
+fn typed_prop_from_reply(member: &str, reply: &Message, mode: DecodeMode) -> Result<ChangedProperty> {
+    let mut value: Variant<Box<dyn RefArg>> = reply.read1()
+        .chain_err(|| "Could not convert to Variant")?;
+    ChangedProperty::from_variant(member, &mut value, mode)
+}
+
+/// Copies a signal, so that it can be passed on to several streams.
+fn copy_signal(msg: &Message) -> Option<Message> {
+    let (_, path, interface, member) = msg.headers();
+    let mut copy = Message::new_signal(path?, interface?, member?).ok()?;
+    copy.append_items(&msg.get_items());
+    Some(copy)
+}
+
+
+/// The state of a `PendingCall`.
+enum CallState<T> {
+    Failed(Error),
+    Waiting(oneshot::Receiver<Reply>, Box<dyn FnOnce(::std::result::Result<Message, ::dbus::Error>) -> Result<T> + Send>),
+    Done,
+}
+
+/// A future which resolves to the result of a D-Bus call.
+#[must_use = "futures do nothing unless polled"]
+pub struct PendingCall<T> {
+    state: CallState<T>,
+}
+
+impl<T> PendingCall<T> {
+    /// Creates a new `PendingCall`, which passes the reply (or the error reply) to `convert`.
+    fn new<F>(reply: Result<oneshot::Receiver<Reply>>, convert: F) -> Self
+        where F: FnOnce(::std::result::Result<Message, ::dbus::Error>) -> Result<T> + Send + 'static
+    {
+        let state = match reply {
+            Ok(reply) => CallState::Waiting(reply, Box::new(convert)),
+            Err(err) => CallState::Failed(err),
+        };
+        PendingCall { state }
+    }
+}
+
+impl<T> Future for PendingCall<T> {
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<T>> {
+        match mem::replace(&mut self.state, CallState::Done) {
+            CallState::Failed(err) => Poll::Ready(Err(err)),
+            CallState::Waiting(mut reply, convert) => {
+                match Pin::new(&mut reply).poll(cx) {
+                    Poll::Pending => {
+                        self.state = CallState::Waiting(reply, convert);
+                        Poll::Pending
+                    }
+                    Poll::Ready(Ok(Reply::Message(mut msg))) => {
+                        let result = match msg.as_result() {
+                            Ok(..) => Ok(()),
+                            Err(err) => Err(err),
+                        };
+                        Poll::Ready(convert(result.map(|_| msg)))
+                    }
+                    Poll::Ready(Ok(Reply::TimedOut)) => {
+                        Poll::Ready(convert(Err(::dbus::Error::new_custom(
+                            "org.freedesktop.DBus.Error.NoReply",
+                            "Did not receive a reply in time.",
+                        ))))
+                    }
+                    Poll::Ready(Err(oneshot::Canceled)) => {
+                        Poll::Ready(convert(Err(::dbus::Error::new_custom(
+                            "org.freedesktop.DBus.Error.Disconnected",
+                            "The D-Bus connection was closed.",
+                        ))))
+                    }
+                }
+            }
+            CallState::Done => panic!("PendingCall polled after completion"),
+        }
+    }
+}
+
+
+/// Stream of `MprisSignal`s.
+pub struct AsyncMprisSignals {
+    messages: mpsc::UnboundedReceiver<Message>,
+    mode: DecodeMode,
+}
+
+impl Stream for AsyncMprisSignals {
+    type Item = MprisSignal;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<MprisSignal>> {
+        loop {
+            match Pin::new(&mut self.messages).poll_next(cx) {
+                Poll::Ready(Some(msg)) => {
+                    if let Some(signal) = MprisSignal::from_message(&msg, self.mode) {
+                        return Poll::Ready(Some(signal));
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct AsyncMprisClient {
+    dbus_conn: Arc<AsyncDBusConn>,
+
+    pub root: AsyncMprisRoot,
+    pub player: AsyncMprisPlayer,
+    pub tracklist: AsyncMprisTrackList,
+    pub playlists: AsyncMprisPlaylists,
+}
+
+impl AsyncMprisClient {
+    /// Creates a new `AsyncMprisClient` instance.
+    ///
+    /// Connecting to the bus blocks, but all later calls are asynchronous. `timeout_ms` specifies
+    /// the maximum time until a D-Bus method call fails. The value -1 disables the timeout.
+    pub fn new(player_name: &str, timeout_ms: i32) -> Result<Self> {
+        let dbus_conn = AsyncDBusConn::new(player_name, timeout_ms)?;
+
+        Ok(AsyncMprisClient {
+            root: AsyncMprisRoot { dbus_conn: dbus_conn.clone() },
+            player: AsyncMprisPlayer { dbus_conn: dbus_conn.clone() },
+            tracklist: AsyncMprisTrackList { dbus_conn: dbus_conn.clone() },
+            playlists: AsyncMprisPlaylists { dbus_conn: dbus_conn.clone() },
+
+            dbus_conn,
+        })
+    }
+
+    /// Sets how the metadata read from the media player is decoded, which is
+    /// `DecodeMode::Lenient` by default. See `MprisClient::set_decode_mode`.
+    ///
+    /// The mode applies to the calls made afterwards, and to the streams returned by `signals`
+    /// afterwards.
+    pub fn set_decode_mode(&self, mode: DecodeMode) {
+        *self.dbus_conn.decode_mode.lock().unwrap_or_else(|err| err.into_inner()) = mode;
+    }
+
+    /// Returns how the metadata read from the media player is decoded, see `set_decode_mode`.
+    pub fn decode_mode(&self) -> DecodeMode {
+        self.dbus_conn.decode_mode()
+    }
+
+    /// Returns a stream of the `MprisSignal`s emitted from now on. Their metadata is decoded in
+    /// the current decode mode, see `set_decode_mode`.
+    ///
+    /// The stream ends when the client is dropped.
+    pub fn signals(&self) -> AsyncMprisSignals {
+        AsyncMprisSignals { messages: self.dbus_conn.subscribe(), mode: self.dbus_conn.decode_mode() }
+    }
+}
+
+/// Implements an asynchronous method without arguments.
+macro_rules! async_method {
+    ($(#[$attr:meta])* $name:ident, $interface:expr, $member:expr) => {
+        $(#[$attr])*
+        pub fn $name(&self) -> PendingCall<()> {
+            self.dbus_conn.call_method_without_reply($interface, $member, &[])
+        }
+    };
+}
+
+/// Implements an asynchronous getter for a mandatory property.
+macro_rules! async_prop_getter {
+    ($(#[$attr:meta])* $name:ident, $interface:expr, $member:ident, $return_type:ty) => {
+        $(#[$attr])*
+        pub fn $name(&self) -> PendingCall<$return_type> {
+            self.dbus_conn.get_typed_prop($interface, stringify!($member), |prop| match prop {
+                ChangedProperty::$member(value) => Ok(value),
+                prop => bail!(ErrorKind::TypeCastError(prop.to_debug_str(), stringify!($return_type))),
+            })
+        }
+    };
+}
+
+/// Implements an asynchronous getter for an optional property.
+macro_rules! async_optional_prop_getter {
+    ($(#[$attr:meta])* $name:ident, $interface:expr, $member:ident, $return_type:ty) => {
+        $(#[$attr])*
+        pub fn $name(&self) -> PendingCall<Option<$return_type>> {
+            self.dbus_conn.get_optional_typed_prop($interface, stringify!($member), |prop| match prop {
+                ChangedProperty::$member(value) => Ok(value),
+                prop => bail!(ErrorKind::TypeCastError(prop.to_debug_str(), stringify!($return_type))),
+            })
+        }
+    };
+}
+
+/// Implements `get_all`, which reads all properties of an interface via
+/// `AsyncDBusConn::get_all_props`.
+macro_rules! async_get_all {
+    ($(#[$attr:meta])* $name:ident, $interface:expr, $properties_type:ident) => {
+        $(#[$attr])*
+        pub fn $name(&self) -> PendingCall<::properties::$properties_type> {
+            self.dbus_conn.get_all_props($interface, ::properties::$properties_type::from_props)
+        }
+    };
+}
+
+/// Asynchronous variant of `MprisRoot`.
+#[derive(Debug)]
+pub struct AsyncMprisRoot {
+    dbus_conn: Arc<AsyncDBusConn>,
+}
+
+impl AsyncMprisRoot {
+    async_get_all!(
+        /// See `MprisRoot::get_all`.
+        get_all, "org.mpris.MediaPlayer2", RootProperties);
+
+    async_method!(
+        /// See `MprisRoot::raise`.
+        raise, "org.mpris.MediaPlayer2", "Raise");
+    async_method!(
+        /// See `MprisRoot::quit`.
+        quit, "org.mpris.MediaPlayer2", "Quit");
+
+    async_prop_getter!(
+        /// See `MprisRoot::can_quit`.
+        can_quit, "org.mpris.MediaPlayer2", CanQuit, bool);
+    async_optional_prop_getter!(
+        /// See `MprisRoot::fullscreen`.
+        fullscreen, "org.mpris.MediaPlayer2", Fullscreen, bool);
+
+    /// See `MprisRoot::set_fullscreen`.
+    pub fn set_fullscreen(&self, value: bool) -> PendingCall<()> {
+        self.dbus_conn.set_prop("org.mpris.MediaPlayer2", "Fullscreen", MessageItem::Bool(value))
+    }
+
+    async_optional_prop_getter!(
+        /// See `MprisRoot::can_set_fullscreen`.
+        can_set_fullscreen, "org.mpris.MediaPlayer2", CanSetFullscreen, bool);
+    async_prop_getter!(
+        /// See `MprisRoot::can_raise`.
+        can_raise, "org.mpris.MediaPlayer2", CanRaise, bool);
+    async_prop_getter!(
+        /// See `MprisRoot::has_track_list`.
+        has_track_list, "org.mpris.MediaPlayer2", HasTrackList, bool);
+    async_prop_getter!(
+        /// See `MprisRoot::identity`.
+        identity, "org.mpris.MediaPlayer2", Identity, String);
+    async_optional_prop_getter!(
+        /// See `MprisRoot::desktop_entry`.
+        desktop_entry, "org.mpris.MediaPlayer2", DesktopEntry, String);
+    async_prop_getter!(
+        /// See `MprisRoot::supported_uri_schemes`.
+        supported_uri_schemes, "org.mpris.MediaPlayer2", SupportedUriSchemes, Vec<String>);
+    async_prop_getter!(
+        /// See `MprisRoot::supported_mime_types`.
+        supported_mime_types, "org.mpris.MediaPlayer2", SupportedMimeTypes, Vec<String>);
+}
+
+/// Asynchronous variant of `MprisPlayer`.
+#[derive(Debug)]
+pub struct AsyncMprisPlayer {
+    dbus_conn: Arc<AsyncDBusConn>,
+}
+
+impl AsyncMprisPlayer {
+    async_get_all!(
+        /// See `MprisPlayer::get_all`.
+        get_all, "org.mpris.MediaPlayer2.Player", PlayerProperties);
+
+    async_method!(
+        /// See `MprisPlayer::next`.
+        next, "org.mpris.MediaPlayer2.Player", "Next");
+    async_method!(
+        /// See `MprisPlayer::previous`.
+        previous, "org.mpris.MediaPlayer2.Player", "Previous");
+    async_method!(
+        /// See `MprisPlayer::pause`.
+        pause, "org.mpris.MediaPlayer2.Player", "Pause");
+    async_method!(
+        /// See `MprisPlayer::play_pause`.
+        play_pause, "org.mpris.MediaPlayer2.Player", "PlayPause");
+    async_method!(
+        /// See `MprisPlayer::stop`.
+        stop, "org.mpris.MediaPlayer2.Player", "Stop");
+    async_method!(
+        /// See `MprisPlayer::play`.
+        play, "org.mpris.MediaPlayer2.Player", "Play");
+
+    /// See `MprisPlayer::seek`.
+    pub fn seek(&self, offset: i64) -> PendingCall<()> {
+        self.dbus_conn.call_method_without_reply(
+            "org.mpris.MediaPlayer2.Player",
+            "Seek",
+            &[MessageItem::Int64(offset)],
+        )
+    }
+
+    /// See `MprisPlayer::set_position`.
+    pub fn set_position(&self, track_id: &::TrackId, position: i64) -> PendingCall<()> {
+        self.dbus_conn.call_method_without_reply(
+            "org.mpris.MediaPlayer2.Player",
+            "SetPosition",
+            &[track_id.clone().into(), MessageItem::Int64(position)],
+        )
+    }
+
+    /// See `MprisPlayer::open_uri`.
+    pub fn open_uri(&self, uri: &str) -> PendingCall<()> {
+        self.dbus_conn.call_method_without_reply(
+            "org.mpris.MediaPlayer2.Player",
+            "OpenUri",
+            &[MessageItem::Str(uri.to_string())],
+        )
+    }
+
+    async_prop_getter!(
+        /// See `MprisPlayer::playback_status`.
+        playback_status, "org.mpris.MediaPlayer2.Player", PlaybackStatus, ::PlaybackStatus);
+    async_optional_prop_getter!(
+        /// See `MprisPlayer::loop_status`.
+        loop_status, "org.mpris.MediaPlayer2.Player", LoopStatus, ::LoopStatus);
+
+    /// See `MprisPlayer::set_loop_status`.
+    pub fn set_loop_status(&self, value: ::LoopStatus) -> PendingCall<()> {
+        self.dbus_conn.set_prop("org.mpris.MediaPlayer2.Player", "LoopStatus", value.into())
+    }
+
+    async_prop_getter!(
+        /// See `MprisPlayer::rate`.
+        rate, "org.mpris.MediaPlayer2.Player", Rate, ::PlaybackRate);
+
+    /// See `MprisPlayer::set_rate`.
+    pub fn set_rate(&self, value: ::PlaybackRate) -> PendingCall<()> {
+        self.dbus_conn.set_prop("org.mpris.MediaPlayer2.Player", "Rate", MessageItem::Double(value))
+    }
+
+    async_optional_prop_getter!(
+        /// See `MprisPlayer::shuffle`.
+        shuffle, "org.mpris.MediaPlayer2.Player", Shuffle, bool);
+
+    /// See `MprisPlayer::set_shuffle`.
+    pub fn set_shuffle(&self, value: bool) -> PendingCall<()> {
+        self.dbus_conn.set_prop("org.mpris.MediaPlayer2.Player", "Shuffle", MessageItem::Bool(value))
+    }
+
+    async_prop_getter!(
+        /// See `MprisPlayer::metadata`.
+        metadata, "org.mpris.MediaPlayer2.Player", Metadata, ::MetadataMap);
+    async_prop_getter!(
+        /// See `MprisPlayer::volume`.
+        volume, "org.mpris.MediaPlayer2.Player", Volume, ::Volume);
+
+    /// See `MprisPlayer::set_volume`.
+    pub fn set_volume(&self, value: ::Volume) -> PendingCall<()> {
+        self.dbus_conn.set_prop("org.mpris.MediaPlayer2.Player", "Volume", MessageItem::Double(value))
+    }
+
+    async_prop_getter!(
+        /// See `MprisPlayer::position`.
+        position, "org.mpris.MediaPlayer2.Player", Position, i64);
+    async_prop_getter!(
+        /// See `MprisPlayer::minimum_rate`.
+        minimum_rate, "org.mpris.MediaPlayer2.Player", MinimumRate, ::PlaybackRate);
+    async_prop_getter!(
+        /// See `MprisPlayer::maximum_rate`.
+        maximum_rate, "org.mpris.MediaPlayer2.Player", MaximumRate, ::PlaybackRate);
+    async_prop_getter!(
+        /// See `MprisPlayer::can_go_next`.
+        can_go_next, "org.mpris.MediaPlayer2.Player", CanGoNext, bool);
+    async_prop_getter!(
+        /// See `MprisPlayer::can_go_previous`.
+        can_go_previous, "org.mpris.MediaPlayer2.Player", CanGoPrevious, bool);
+    async_prop_getter!(
+        /// See `MprisPlayer::can_play`.
+        can_play, "org.mpris.MediaPlayer2.Player", CanPlay, bool);
+    async_prop_getter!(
+        /// See `MprisPlayer::can_pause`.
+        can_pause, "org.mpris.MediaPlayer2.Player", CanPause, bool);
+    async_prop_getter!(
+        /// See `MprisPlayer::can_seek`.
+        can_seek, "org.mpris.MediaPlayer2.Player", CanSeek, bool);
+    async_prop_getter!(
+        /// See `MprisPlayer::can_control`.
+        can_control, "org.mpris.MediaPlayer2.Player", CanControl, bool);
+}
+
+/// Asynchronous variant of `MprisTrackList`.
+#[derive(Debug)]
+pub struct AsyncMprisTrackList {
+    dbus_conn: Arc<AsyncDBusConn>,
+}
+
+impl AsyncMprisTrackList {
+    async_get_all!(
+        /// See `MprisTrackList::get_all`.
+        get_all, "org.mpris.MediaPlayer2.TrackList", TrackListProperties);
+
+    /// See `MprisTrackList::get_tracks_metadata`.
+    pub fn get_tracks_metadata(&self, track_ids: &[::TrackId]) -> PendingCall<Vec<::MetadataMap>> {
+        let paths = track_ids_to_paths(track_ids);
+        let mode = self.dbus_conn.decode_mode();
+        self.dbus_conn.call_method(
+            "org.mpris.MediaPlayer2.TrackList",
+            "GetTracksMetadata",
+            &[MessageItem::from(&paths[..])],
+            move |reply| tracks_metadata_from_reply(&reply, mode),
+        )
+    }
+
+    /// See `MprisTrackList::add_track`.
+    pub fn add_track(&self, uri: &str, after_track: &::TrackId, set_as_current: bool) -> PendingCall<()> {
+        self.dbus_conn.call_method_without_reply(
+            "org.mpris.MediaPlayer2.TrackList",
+            "AddTrack",
+            &[MessageItem::Str(uri.to_string()), after_track.clone().into(), MessageItem::Bool(set_as_current)],
+        )
+    }
+
+    /// See `MprisTrackList::remove_track`.
+    pub fn remove_track(&self, track_id: &::TrackId) -> PendingCall<()> {
+        self.dbus_conn.call_method_without_reply(
+            "org.mpris.MediaPlayer2.TrackList",
+            "RemoveTrack",
+            &[track_id.clone().into()],
+        )
+    }
+
+    /// See `MprisTrackList::go_to`.
+    pub fn go_to(&self, track_id: &::TrackId) -> PendingCall<()> {
+        self.dbus_conn.call_method_without_reply(
+            "org.mpris.MediaPlayer2.TrackList",
+            "GoTo",
+            &[track_id.clone().into()],
+        )
+    }
+
+    async_prop_getter!(
+        /// See `MprisTrackList::tracks`.
+        tracks, "org.mpris.MediaPlayer2.TrackList", Tracks, Vec<::TrackId>);
+    async_prop_getter!(
+        /// See `MprisTrackList::can_edit_tracks`.
+        can_edit_tracks, "org.mpris.MediaPlayer2.TrackList", CanEditTracks, bool);
+}
+
+/// Asynchronous variant of `MprisPlaylists`.
+#[derive(Debug)]
+pub struct AsyncMprisPlaylists {
+    dbus_conn: Arc<AsyncDBusConn>,
+}
+
+impl AsyncMprisPlaylists {
+    async_get_all!(
+        /// See `MprisPlaylists::get_all`.
+        get_all, "org.mpris.MediaPlayer2.Playlists", PlaylistsProperties);
+
+    /// See `MprisPlaylists::activate_playlist`.
+    pub fn activate_playlist(&self, playlist_id: &str) -> PendingCall<()> {
+        match playlist_id_to_path(playlist_id) {
+            Ok(playlist_id) => {
+                self.dbus_conn.call_method_without_reply(
+                    "org.mpris.MediaPlayer2.Playlists",
+                    "ActivatePlaylist",
+                    &[MessageItem::ObjectPath(playlist_id)],
+                )
+            }
+            Err(err) => PendingCall::new(Err(err), |_| Ok(())),
+        }
+    }
+
+    /// See `MprisPlaylists::get_playlists`.
+    pub fn get_playlists(&self,
+                         index: u32,
+                         max_count: u32,
+                         order: ::PlaylistOrdering,
+                         reverse_order: bool)
+                         -> PendingCall<Vec<::Playlist>> {
+        self.dbus_conn.call_method(
+            "org.mpris.MediaPlayer2.Playlists",
+            "GetPlaylists",
+            &[MessageItem::UInt32(index),
+              MessageItem::UInt32(max_count),
+              order.into(),
+              MessageItem::Bool(reverse_order)],
+            |reply| playlists_from_reply(&reply),
+        )
+    }
+
+    async_prop_getter!(
+        /// See `MprisPlaylists::playlist_count`.
+        playlist_count, "org.mpris.MediaPlayer2.Playlists", PlaylistCount, u32);
+    async_prop_getter!(
+        /// See `MprisPlaylists::orderings`.
+        orderings, "org.mpris.MediaPlayer2.Playlists", Orderings, Vec<::PlaylistOrdering>);
+    async_prop_getter!(
+        /// See `MprisPlaylists::active_playlist`.
+        active_playlist, "org.mpris.MediaPlayer2.Playlists", ActivePlaylist, ::MaybePlaylist);
+}
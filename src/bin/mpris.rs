@@ -0,0 +1,376 @@
+//! A command-line utility to control media players which implement MPRIS.
+//!
+//! Run `mpris --help` for the list of commands.
+extern crate mpris;
+#[macro_use]
+extern crate serde_json;
+
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use std::str::FromStr;
+
+use mpris::LoopStatus;
+use mpris::client::MprisClient;
+use mpris::errors::*;
+use mpris::format::{Template, TemplateContext, Value};
+use mpris::state::PlayerState;
+use mpris::watcher::{PlayerEvent, SignalWatcher, WatcherEvent};
+
+
+const USAGE: &str = "\
+Usage: mpris [OPTIONS] COMMAND [ARGS]
+
+Controls media players which implement MPRIS.
+
+Options:
+  -p, --player NAME[,NAME...]  Controls the first available player of the given names. Can be
+                               given several times. A name also matches all instances of the
+                               player, e.g. `vlc` matches `vlc.instance42`.
+  -a, --all-players            Controls all (selected) players instead of the first one.
+  -t, --timeout MS             The maximum time a D-Bus method call blocks (default: 1000).
+  -f, --format TEMPLATE        Prints `status` and `metadata` with TEMPLATE, e.g.
+                               `{{artist}} - {{title}} [{{duration(mpris:length)}}]`. Besides
+                               the metadata, `player`, `status`, `loop`, `shuffle`, `volume`,
+                               `rate`, `position` and `identity` can be shown.
+  -h, --help                   Prints this help.
+
+Commands:
+  list                         Lists the names of the available players.
+  follow [--json]              Prints the signals of the (selected) players, and when players
+                               appear or vanish, until it is interrupted. With --json, each
+                               event is printed as one line of JSON.
+  status                       Prints the playback status.
+  play                         Starts or resumes playback.
+  pause                        Pauses playback.
+  play-pause                   Toggles between playing and paused.
+  next                         Skips to the next track.
+  previous                     Skips to the previous track.
+  stop                         Stops playback.
+  seek [+|-]SECONDS            Seeks forward or backward by SECONDS.
+  position [SECONDS]           Prints the position, or jumps to SECONDS.
+  volume [LEVEL]               Prints the volume, or sets it to LEVEL (0.0 to 1.0).
+  loop [None|Track|Playlist]   Prints or sets the loop status.
+  shuffle [on|off|toggle]      Prints or sets whether shuffle is enabled.
+  open URI                     Opens URI.
+  metadata [KEY]               Prints all metadata entries, or the value of KEY.
+  raise                        Brings the player's user interface to the front.
+  quit                         Asks the player to quit.
+";
+
+/// The parsed command-line arguments.
+#[derive(Debug, Default)]
+struct Options {
+    players: Vec<String>,
+    all_players: bool,
+    timeout_ms: i32,
+    format: Option<Template>,
+    command: String,
+    args: Vec<String>,
+}
+
+/// Parses the command-line arguments. Options are only recognized before the command, so that
+/// e.g. `seek -5` works.
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options> {
+    let mut options = Options { timeout_ms: 1000, ..Options::default() };
+    while let Some(arg) = args.next() {
+        let (name, inline_value) = match arg.find('=') {
+            Some(index) if arg.starts_with("--") => (arg[..index].to_string(), Some(arg[index + 1..].to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            inline_value.clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| Error::from(format!("Missing value for {}", name)))
+        };
+        match &name as &str {
+            "-p" | "--player" => {
+                let names = value()?;
+                options.players.extend(names.split(',').filter(|name| !name.is_empty()).map(String::from));
+            }
+            "-a" | "--all-players" => options.all_players = true,
+            "-t" | "--timeout" => {
+                let timeout = value()?;
+                options.timeout_ms = timeout.parse()
+                    .chain_err(|| format!("Invalid timeout: {}", timeout))?;
+            }
+            "-f" | "--format" => options.format = Some(Template::parse(&value()?)?),
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if name.starts_with('-') => return Err(format!("Unknown option: {}", name).into()),
+            _ => {
+                options.command = arg;
+                options.args = args.collect();
+                return Ok(options);
+            }
+        }
+    }
+    Err("No command given. Run `mpris --help` for the list of commands.".into())
+}
+
+/// Returns whether the player `player_name` is selected by `selector`, either by its full name or
+/// as an instance of the player.
+fn matches_player(selector: &str, player_name: &str) -> bool {
+    player_name == selector
+        || (player_name.starts_with(selector) && player_name[selector.len()..].starts_with('.'))
+}
+
+/// Returns the names of the players which are controlled, in the order of the selectors.
+fn select_players(options: &Options) -> Result<Vec<String>> {
+    let available = MprisClient::list_players(options.timeout_ms)?;
+    let mut selected: Vec<String> = Vec::new();
+    if options.players.is_empty() {
+        selected = available;
+    } else {
+        for selector in &options.players {
+            for player_name in available.iter().filter(|player_name| matches_player(selector, player_name)) {
+                if !selected.contains(player_name) {
+                    selected.push(player_name.clone());
+                }
+            }
+        }
+    }
+
+    if !options.all_players {
+        selected.truncate(1);
+    }
+    if selected.is_empty() {
+        return Err("No players found".into());
+    }
+    Ok(selected)
+}
+
+/// Parses a number of seconds into microseconds.
+fn parse_seconds(seconds: &str) -> Result<i64> {
+    let value = f64::from_str(seconds.trim_start_matches('+'))
+        .chain_err(|| format!("Invalid number of seconds: {}", seconds))?;
+    Ok((value * 1_000_000.0).round() as i64)
+}
+
+/// Formats microseconds as seconds.
+fn format_seconds(us: i64) -> String {
+    format!("{:.6}", us as f64 / 1_000_000.0)
+}
+
+/// The values shown by `--format`: the state of a player, and its name as `player`.
+struct PlayerContext<'a> {
+    player_name: &'a str,
+    state: PlayerState,
+}
+
+impl<'a> TemplateContext for PlayerContext<'a> {
+    fn value(&self, key: &str) -> Option<Value> {
+        match key {
+            "player" => Some(Value::Text(self.player_name.to_string())),
+            _ => self.state.value(key),
+        }
+    }
+}
+
+/// Returns the single argument of the command, if there is one.
+fn optional_arg(options: &Options) -> Result<Option<&str>> {
+    match options.args.len() {
+        0 => Ok(None),
+        1 => Ok(Some(&options.args[0])),
+        _ => Err(format!("Too many arguments for {}", options.command).into()),
+    }
+}
+
+/// Returns the mandatory single argument of the command.
+fn required_arg(options: &Options) -> Result<&str> {
+    optional_arg(options)?.ok_or_else(|| format!("Missing argument for {}", options.command).into())
+}
+
+/// Runs the command of `options` on the player `player_name`.
+fn run_command(options: &Options, player_name: &str) -> Result<()> {
+    let client = MprisClient::new(player_name, options.timeout_ms)?;
+    if let Some(ref template) = options.format {
+        if options.command == "status" || (options.command == "metadata" && options.args.is_empty()) {
+            let context = PlayerContext { player_name, state: PlayerState::new(&client)? };
+            println!("{}", template.render(&context));
+            return Ok(());
+        }
+    }
+    match &options.command as &str {
+        "status" => println!("{}", client.player.playback_status()?.as_ref()),
+        "play" => client.player.play()?,
+        "pause" => client.player.pause()?,
+        "play-pause" => client.player.play_pause()?,
+        "next" => client.player.next()?,
+        "previous" => client.player.previous()?,
+        "stop" => client.player.stop()?,
+        "seek" => client.player.seek(parse_seconds(required_arg(options)?)?)?,
+        "position" => {
+            match optional_arg(options)? {
+                Some(seconds) => {
+                    let metadata = client.player.metadata()?;
+                    client.player.set_position(metadata.trackid(), parse_seconds(seconds)?)?
+                }
+                None => println!("{}", format_seconds(client.player.position()?)),
+            }
+        }
+        "volume" => {
+            match optional_arg(options)? {
+                Some(level) => {
+                    let level = f64::from_str(level).chain_err(|| format!("Invalid volume: {}", level))?;
+                    client.player.set_volume(level)?
+                }
+                None => println!("{:.6}", client.player.volume()?),
+            }
+        }
+        "loop" => {
+            match optional_arg(options)? {
+                Some(status) => client.player.set_loop_status(LoopStatus::from_str(status)?)?,
+                None => {
+                    let status = client.player.loop_status()?
+                        .ok_or_else(|| Error::from("The player does not support looping"))?;
+                    println!("{}", status.as_ref())
+                }
+            }
+        }
+        "shuffle" => {
+            let shuffle = || {
+                client.player.shuffle()?.ok_or_else(|| Error::from("The player does not support shuffle"))
+            };
+            match optional_arg(options)? {
+                Some("on") | Some("true") => client.player.set_shuffle(true)?,
+                Some("off") | Some("false") => client.player.set_shuffle(false)?,
+                Some("toggle") => client.player.set_shuffle(!shuffle()?)?,
+                Some(value) => return Err(format!("Invalid shuffle value: {}", value).into()),
+                None => println!("{}", if shuffle()? { "On" } else { "Off" }),
+            }
+        }
+        "open" => client.player.open_uri(required_arg(options)?)?,
+        "metadata" => {
+            let metadata = client.player.metadata()?;
+            match optional_arg(options)? {
+                Some(key) => {
+                    let value = metadata.get(key)
+                        .ok_or_else(|| Error::from(format!("No metadata entry {}", key)))?;
+                    println!("{}", value)
+                }
+                None => {
+                    let mut keys: Vec<&str> = metadata.keys().collect();
+                    keys.sort();
+                    for key in keys {
+                        if let Some(value) = metadata.get(key) {
+                            println!("{} {} {}", player_name, key, value);
+                        }
+                    }
+                }
+            }
+        }
+        "raise" => client.root.raise()?,
+        "quit" => client.root.quit()?,
+        command => return Err(format!("Unknown command: {}", command).into()),
+    }
+    Ok(())
+}
+
+/// Returns the JSON line of a player event. Appearing players are described with their playback
+/// status and metadata, if they can be read.
+fn player_event_json(event: &PlayerEvent, timeout_ms: i32) -> serde_json::Value {
+    let player_name = event.player_name();
+    match *event {
+        PlayerEvent::PlayerAppeared { .. } | PlayerEvent::PlayerOwnerChanged { .. } => {
+            let properties = MprisClient::new(player_name, timeout_ms)
+                .and_then(|client| client.player.get_all())
+                .unwrap_or_default();
+            let event = match *event {
+                PlayerEvent::PlayerAppeared { .. } => "appeared",
+                _ => "replaced",
+            };
+            json!({
+                "player": player_name,
+                "event": event,
+                "status": properties.playback_status,
+                "metadata": properties.metadata,
+            })
+        }
+        PlayerEvent::PlayerVanished { .. } => json!({ "player": player_name, "event": "vanished" }),
+    }
+}
+
+/// Prints the events of the selected players as they happen. Only returns on errors, or when
+/// stdout is closed, e.g. by the end of a pipe.
+fn follow(options: &Options) -> Result<()> {
+    let as_json = match optional_arg(options)? {
+        None => false,
+        Some("--json") => true,
+        Some(arg) => return Err(format!("Invalid argument for follow: {}", arg).into()),
+    };
+    let is_selected = |player_name: &str| {
+        options.players.is_empty() || options.players.iter().any(|selector| matches_player(selector, player_name))
+    };
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut watcher = SignalWatcher::new(options.timeout_ms)?;
+    loop {
+        for event in watcher.events(1000) {
+            let written = match event {
+                WatcherEvent::Player(ref event) if is_selected(event.player_name()) => {
+                    if as_json {
+                        writeln!(stdout, "{}", player_event_json(event, options.timeout_ms))
+                    } else {
+                        writeln!(stdout, "{} {:?}", event.player_name(), event)
+                    }
+                }
+                WatcherEvent::Signal(ref signal) if is_selected(signal.player_name()) => {
+                    if as_json {
+                        let line = json!({ "player": signal.player_name(), "event": "signal", "signal": signal.signal });
+                        writeln!(stdout, "{}", line)
+                    } else {
+                        writeln!(stdout, "{} {:?}", signal.player_name(), signal.signal)
+                    }
+                }
+                _ => Ok(()),
+            };
+            match written {
+                Ok(()) => {}
+                Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+                Err(err) => return Err(err).chain_err(|| "Could not write to stdout"),
+            }
+        }
+    }
+}
+
+/// Runs the command on the selected players. Returns whether it succeeded for all of them.
+fn run(options: &Options) -> Result<bool> {
+    match &options.command as &str {
+        "list" => {
+            for player_name in MprisClient::list_players(options.timeout_ms)? {
+                println!("{}", player_name);
+            }
+            return Ok(true);
+        }
+        "follow" => {
+            follow(options)?;
+            return Ok(true);
+        }
+        _ => {}
+    }
+
+    let mut success = true;
+    for player_name in select_players(options)? {
+        if let Err(e) = run_command(options, &player_name) {
+            eprintln!("mpris: {}: {}", player_name, e);
+            success = false;
+        }
+    }
+    Ok(success)
+}
+
+fn main() {
+    let result = parse_args(env::args().skip(1)).and_then(|options| run(&options));
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("mpris: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -1,84 +1,224 @@
-use dbus::{BusType, Connection, Message, Props, MessageItem, MessageType};
-use dbus::arg::{RefArg, Variant};
-use std::rc::Rc;
+use dbus::{BusType, Connection, Message, Props, MessageItem, MessageType, Path};
+use dbus::arg::{ArgType, RefArg, Variant};
+use std::cmp;
+use std::ops::Deref;
 use std::str::FromStr;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
+use decode::{decode_metadata, DecodeMode, RawEntry};
 use errors::*;
 
+/// The match rules for all signals of a media player.
+pub(crate) const MPRIS_MATCH_RULES: [&str; 5] = [
+    "path='/org/mpris/MediaPlayer2',interface='org.freedesktop.DBus.Properties',member='PropertiesChanged'",
+    "path='/org/mpris/MediaPlayer2',interface='org.mpris.MediaPlayer2'",
+    "path='/org/mpris/MediaPlayer2',interface='org.mpris.MediaPlayer2.Player'",
+    "path='/org/mpris/MediaPlayer2',interface='org.mpris.MediaPlayer2.TrackList'",
+    "path='/org/mpris/MediaPlayer2',interface='org.mpris.MediaPlayer2.Playlists'",
+];
+
+/// The longest time `MprisSignals` keeps the connection locked while waiting for a message, so
+/// that method calls from other threads are not held up for the whole timeout.
+const SIGNAL_POLL_INTERVAL_MS: u32 = 20;
+
+/// A `Connection` which may be moved between threads.
+///
+/// The `dbus` crate initializes libdbus for multi-threaded use, and the connection is only ever
+/// used while the `Mutex` in `DBusConn` is locked. No message handlers or callbacks, which might
+/// be tied to a thread, are registered on it.
+#[derive(Debug)]
+pub(crate) struct SendConnection(pub(crate) Connection);
+
+// SAFETY: The connection is private, i.e. it is opened by `open_session_bus` and not shared with
+// other users of libdbus in this process. A `SendConnection` is only stored in the
+// `Mutex` of `DBusConn`, or in the `ConnState` behind the `Mutex` of `AsyncDBusConn`, so it is
+// accessed by one thread at a time. No borrow of the connection, like the iterator returned by
+// `incoming`, outlives the `MutexGuard` it was taken from: the messages are collected or taken
+// before the guard is dropped.
+unsafe impl Send for SendConnection {}
+
+impl Deref for SendConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.0
+    }
+}
+
 /// Abstraction over the DBUS connection. All interactions with DBUS should go through one of the
 /// methods of this struct.
+///
+/// The connection is guarded by a `Mutex`, so a `DBusConn` can be shared between threads.
 #[derive(Debug)]
 struct DBusConn {
-    conn: Connection,
+    conn: Mutex<SendConnection>,
+    player_name: String,
     bus_name: String,
     unique_bus_name: String,
     timeout: i32,
+    /// Whether the control methods check the matching capability first.
+    checked: AtomicBool,
+    /// The value of `CanControl`, which does not change, once it was read.
+    can_control: Mutex<Option<bool>>,
+    /// How the metadata read from the media player is decoded.
+    decode_mode: Mutex<DecodeMode>,
 }
 
 impl DBusConn {
+    /// Locks the connection.
+    fn conn(&self) -> MutexGuard<'_, SendConnection> {
+        self.conn.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns how the metadata read from the media player is decoded.
+    fn decode_mode(&self) -> DecodeMode {
+        *self.decode_mode.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Converts the D-Bus error of a call of `member` of `interface`.
+    fn error(&self, err: ::dbus::Error, interface: &str, member: &str) -> Error {
+        dbus_error(err, &self.player_name, interface, member)
+    }
+
+    /// Calls a DBUS method and returns the reply. This method blocks until the call either
+    /// succeeds or fails.
+    ///
+    /// `args` are appended to the method call in the given order.
+    fn call_method(&self,
+                   obj_path: &str,
+                   interface: &str,
+                   member: &str,
+                   args: &[MessageItem])
+                   -> Result<Message> {
+        let mut msg = Message::new_method_call(&self.bus_name, obj_path, interface, member)?;
+        msg.append_items(args);
+        self.conn().send_with_reply_and_block(msg, self.timeout)
+            .map_err(|err| self.error(err, interface, member))
+    }
+
     /// Calls a DBUS method without returning a value. This method blocks until the call either
     /// succeeds or fails.
-    fn call_method_without_reply(&self, obj_path: &str, member: &str) -> Result<()> {
-        let msg =
-            Message::new_method_call(&self.bus_name, obj_path, "org.mpris.MediaPlayer2", member)?;
-        if let Err(err) = self.conn.send_with_reply_and_block(msg, self.timeout) {
-            if err.message().unwrap_or("").contains("org.freedesktop.DBus.Error.ServiceUnknown") {
-                Err(err).chain_err(|| ErrorKind::ServiceUnknown(self.bus_name.clone()))
-            } else {
-                Err(err).chain_err(|| ErrorKind::GeneralError("Could not call D-Bus method.".to_string()))
-            }
-        } else { Ok(())}
+    ///
+    /// `args` are appended to the method call in the given order.
+    fn call_method_without_reply(&self,
+                                 obj_path: &str,
+                                 interface: &str,
+                                 member: &str,
+                                 args: &[MessageItem])
+                                 -> Result<()> {
+        self.call_method(obj_path, interface, member, args).map(|_| ())
     }
 
-    /// Reads a DBUS property.
-    fn get_prop(&self, obj_path: &str, member: &str) -> Result<MessageItem> {
-        let prop = Props::new(
-            &self.conn,
-            &self.bus_name,
-            obj_path,
-            "org.mpris.MediaPlayer2",
-            self.timeout,
-        );
-        let msg_item = prop.get(member)?;
-        Ok(msg_item)
+    /// Reads a DBUS property without converting its value.
+    fn get_variant_prop(&self, obj_path: &str, interface: &str, member: &str) -> Result<Variant<Box<dyn RefArg>>> {
+        let msg = Message::new_method_call(&self.bus_name,
+                                           obj_path,
+                                           "org.freedesktop.DBus.Properties",
+                                           "Get")?
+            .append2(interface, member);
+        let reply = self.conn().send_with_reply_and_block(msg, self.timeout)
+            .map_err(|err| self.error(err, interface, member))?;
+        reply.read1().chain_err(|| "Could not convert to Variant")
     }
 
-    /// Safely reads an optional DBUS property.
-    fn get_optional_prop(&self, obj_path: &str, member: &str) -> Result<Option<MessageItem>> {
-        let prop = Props::new(
-            &self.conn,
-            &self.bus_name,
-            obj_path,
-            "org.mpris.MediaPlayer2",
-            self.timeout,
-        );
-        match prop.get(member) {
-            Ok(msg_item) => Ok(Some(msg_item)),
-            Err(ref e) if match_dbus_err(e, "DBus.Error.UnknownProperty") => Ok(None),
-            Err(e) => Err(e.into()),
+    /// Safely reads an optional DBUS property without converting its value.
+    fn get_optional_variant_prop(&self,
+                                 obj_path: &str,
+                                 interface: &str,
+                                 member: &str)
+                                 -> Result<Option<Variant<Box<dyn RefArg>>>> {
+        match self.get_variant_prop(obj_path, interface, member) {
+            Ok(value) => Ok(Some(value)),
+            Err(Error(ErrorKind::UnknownProperty(..), _)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads a DBUS property and converts it into the matching `ChangedProperty`.
+    fn get_typed_prop(&self, obj_path: &str, interface: &str, member: &str) -> Result<ChangedProperty> {
+        let mut value = self.get_variant_prop(obj_path, interface, member)?;
+        ChangedProperty::from_variant(member, &mut value, self.decode_mode())
+    }
+
+    /// Safely reads an optional DBUS property and converts it into the matching
+    /// `ChangedProperty`.
+    fn get_optional_typed_prop(&self,
+                               obj_path: &str,
+                               interface: &str,
+                               member: &str)
+                               -> Result<Option<ChangedProperty>> {
+        match self.get_optional_variant_prop(obj_path, interface, member)? {
+            Some(mut value) => Ok(Some(ChangedProperty::from_variant(member, &mut value, self.decode_mode())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads all properties of an interface with a single `GetAll` call.
+    fn get_all_props(&self, obj_path: &str, interface: &str) -> Result<PropMap> {
+        let reply = self.call_method(obj_path,
+                                     "org.freedesktop.DBus.Properties",
+                                     "GetAll",
+                                     &[MessageItem::Str(interface.to_string())])?;
+        props_from_reply(&reply)
+    }
+
+    /// Fails with `ErrorKind::NotCapable` before `member` of `interface` is called, if the checked
+    /// mode is enabled and the property `capability` of `interface` is not `true`. An absent
+    /// capability counts as `false`.
+    ///
+    /// `CanControl` is only read once, as it is not expected to change. The other capabilities
+    /// are read on every check, as the media player only announces their changes by signals.
+    fn check_capability(&self, interface: &str, capability: &str, member: &str) -> Result<()> {
+        if !self.checked.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let cached = if capability == "CanControl" {
+            *self.can_control.lock().unwrap_or_else(|err| err.into_inner())
+        } else {
+            None
+        };
+        let capable = match cached {
+            Some(capable) => capable,
+            None => {
+                let capable = match self.get_optional_variant_prop("/org/mpris/MediaPlayer2", interface, capability)? {
+                    Some(value) => cast_var::<bool>(&value)?,
+                    None => false,
+                };
+                if capability == "CanControl" {
+                    *self.can_control.lock().unwrap_or_else(|err| err.into_inner()) = Some(capable);
+                }
+                capable
+            }
+        };
+        if capable {
+            Ok(())
+        } else {
+            bail!(ErrorKind::NotCapable(self.player_name.clone(),
+                                        interface.to_string(),
+                                        member.to_string(),
+                                        capability.to_string()))
         }
     }
 
     /// Writes a DBUS property.
-    fn set_prop(&self, obj_path: &str, member: &str, value: MessageItem) -> Result<()> {
+    fn set_prop(&self,
+                obj_path: &str,
+                interface: &str,
+                member: &str,
+                value: MessageItem)
+                -> Result<()> {
+        let conn = self.conn();
         let prop = Props::new(
-            &self.conn,
+            &conn,
             &self.bus_name,
             obj_path,
-            "org.mpris.MediaPlayer2",
+            interface,
             self.timeout,
         );
-        match prop.set(member, value) {
-            Ok(..) => Ok(()),
-            Err(ref e) if match_dbus_err(e, "DBus.Error.UnknownProperty") => {
-                bail!(ErrorKind::AccessedAbsentOptionalProperty(
-                    obj_path.to_string(),
-                    member.to_string(),
-                ))
-            }
-            Err(e) => bail!(e),
-        }
+        prop.set(member, value).map_err(|err| set_prop_error(err, &self.player_name, obj_path, interface, member))
     }
 
 
@@ -87,50 +227,77 @@ impl DBusConn {
     /// `timeout_ms` specifies the maximum time a D-Bus method call blocks. The value -1 disables
     /// the timeout.
     fn new(player_name: &str, timeout_ms: i32) -> Result<Self> {
-        let conn = Connection::get_private(BusType::Session)?;
-
-        conn.add_match(
-            "path='/org/mpris/MediaPlayer2',interface='org.freedesktop.DBus.Properties',member='PropertiesChanged'",
-        )?;
-        conn.add_match(
-            "path='/org/mpris/MediaPlayer2',interface='org.mpris.MediaPlayer2'",
-        )?;
-        conn.add_match(
-            "path='/org/mpris/MediaPlayer2',interface='org.mpris.MediaPlayer2.Player'",
-        )?;
-        conn.add_match(
-            "path='/org/mpris/MediaPlayer2',interface='org.mpris.MediaPlayer2.TrackList'",
-        )?;
-        conn.add_match(
-            "path='/org/mpris/MediaPlayer2',interface='org.mpris.MediaPlayer2.Playlists'",
-        )?;
+        let conn = open_session_bus()?;
+        for rule in &MPRIS_MATCH_RULES {
+            conn.add_match(rule)?;
+        }
 
         let bus_name = format!("org.mpris.MediaPlayer2.{}", player_name);
-
-        // get unique bus name
-        let msg = Message::new_method_call("org.freedesktop.DBus",
-                                           "/org/freedesktop/DBus",
-                                           "org.freedesktop.DBus", "GetNameOwner")
-            .expect("Could not construct method call.")
-            .append1(&bus_name);
-        let res = conn.send_with_reply_and_block(msg, timeout_ms)
-            .chain_err(|| ErrorKind::GeneralError("Could not get unique bus name. Does the player exist?".to_string()))?;
-        let unique_name = res.read1().chain_err(|| "Could not convert to String")?;
+        let unique_name = get_name_owner(&conn, player_name, &bus_name, timeout_ms)?;
 
         Ok(DBusConn {
-            conn,
+            conn: Mutex::new(SendConnection(conn)),
+            player_name: player_name.to_string(),
             bus_name,
             unique_bus_name: unique_name,
             timeout: timeout_ms,
+            checked: AtomicBool::new(false),
+            can_control: Mutex::new(None),
+            decode_mode: Mutex::new(DecodeMode::Lenient),
         })
     }
 }
 
+/// Opens a private connection to the session bus.
+///
+/// With the `test-support` feature, the connection goes to the private bus of `test_support`
+/// instead, once it has been started by `session_bus_address`.
+pub(crate) fn open_session_bus() -> Result<Connection> {
+    #[cfg(feature = "test-support")]
+    {
+        if let Some(address) = ::test_support::started_bus_address() {
+            let conn = Connection::open_private(&address)?;
+            conn.register()?;
+            return Ok(conn);
+        }
+    }
+    Ok(Connection::get_private(BusType::Session)?)
+}
+
+/// Returns the unique bus name of the owner of `bus_name`, the bus name of the media player
+/// `player_name`.
+///
+/// Fails with `ErrorKind::ServiceUnknown` if the media player is not running.
+pub(crate) fn get_name_owner(conn: &Connection, player_name: &str, bus_name: &str, timeout_ms: i32) -> Result<String> {
+    let msg = Message::new_method_call("org.freedesktop.DBus",
+                                       "/org/freedesktop/DBus",
+                                       "org.freedesktop.DBus", "GetNameOwner")
+        .expect("Could not construct method call.")
+        .append1(bus_name);
+    let res = conn.send_with_reply_and_block(msg, timeout_ms)
+        .map_err(|err| dbus_error(err, player_name, "org.freedesktop.DBus", "GetNameOwner"))?;
+    res.read1().chain_err(|| "Could not convert to String")
+}
+
+
+/// The names and values of the properties of an interface, as returned by
+/// `org.freedesktop.DBus.Properties.GetAll`.
+pub(crate) type PropMap = HashMap<String, Variant<Box<dyn RefArg>>>;
+
+/// Reads the map of property names and values from the reply of
+/// `org.freedesktop.DBus.Properties.GetAll`.
+pub(crate) fn props_from_reply(reply: &Message) -> Result<PropMap> {
+    reply.read1().chain_err(|| "Could not convert to a map of properties")
+}
+
 #[derive(Debug)]
 pub struct MprisClient {
-    dbus_conn: Rc<DBusConn>,
+    dbus_conn: Arc<DBusConn>,
 
     pub root: MprisRoot,
+    pub player: MprisPlayer,
+    pub tracklist: MprisTrackList,
+    pub playlists: MprisPlaylists,
 }
 
 impl MprisClient {
@@ -139,14 +306,15 @@ impl MprisClient {
     /// `timeout_ms` specifies the maximum time a D-Bus method call blocks. The value -1 disables
     /// the timeout.
     pub fn new(player_name: &str, timeout_ms: i32) -> Result<Self> {
-        let dbus_conn = Rc::new(DBusConn::new(player_name, timeout_ms)?);
-
-        let dbus_conn_clone = dbus_conn.clone();
+        let dbus_conn = Arc::new(DBusConn::new(player_name, timeout_ms)?);
 
         Ok(MprisClient {
-            dbus_conn,
+            root: MprisRoot::new(dbus_conn.clone()),
+            player: MprisPlayer::new(dbus_conn.clone()),
+            tracklist: MprisTrackList::new(dbus_conn.clone()),
+            playlists: MprisPlaylists::new(dbus_conn.clone()),
 
-            root: MprisRoot::new(dbus_conn_clone),
+            dbus_conn,
         })
     }
 
@@ -155,7 +323,7 @@ impl MprisClient {
     /// `timeout_ms` specifies the maximum time a D-Bus method call blocks. The value -1 disables
     /// the timeout.
     pub fn list_players(timeout_ms: i32) -> Result<Vec<String>> {
-        let conn = Connection::get_private(BusType::Session)?;
+        let conn = open_session_bus()?;
         let msg = Message::new_method_call("org.freedesktop.DBus",
                                            "/org/freedesktop/DBus",
                                            "org.freedesktop.DBus",
@@ -169,30 +337,133 @@ impl MprisClient {
             .collect())
     }
 
+    /// Enables or disables the checked mode, which is disabled by default.
+    ///
+    /// In the checked mode, each control method first reads the matching capability, e.g.
+    /// `can_go_next` for `MprisPlayer::next`, and fails with `ErrorKind::NotCapable` instead of
+    /// calling the media player if it is `false`. Methods which require `can_control`, like
+    /// `MprisPlayer::stop` or `MprisPlayer::set_volume`, only read it once.
+    pub fn set_checked(&self, checked: bool) {
+        self.dbus_conn.checked.store(checked, Ordering::SeqCst);
+    }
+
+    /// Returns whether the checked mode is enabled, see `set_checked`.
+    pub fn is_checked(&self) -> bool {
+        self.dbus_conn.checked.load(Ordering::SeqCst)
+    }
+
+    /// Sets how the metadata read from the media player is decoded, which is
+    /// `DecodeMode::Lenient` by default.
+    ///
+    /// In the `DecodeMode::Strict` mode, reading metadata whose entries do not have the specified
+    /// types fails with `ErrorKind::NonConformingMetadata`. This applies to `MprisPlayer::metadata`,
+    /// `MprisTrackList::get_tracks_metadata`, the `get_all` methods, `PlayerState` and the
+    /// signals. `get_all` and `PlayerState` keep such metadata in their `other` field instead,
+    /// and the signals leave it out.
+    pub fn set_decode_mode(&self, mode: DecodeMode) {
+        *self.dbus_conn.decode_mode.lock().unwrap_or_else(|err| err.into_inner()) = mode;
+    }
+
+    /// Returns how the metadata read from the media player is decoded, see `set_decode_mode`.
+    pub fn decode_mode(&self) -> DecodeMode {
+        self.dbus_conn.decode_mode()
+    }
+
     /// Returns an iterator of `MprisSignal`s.`timeout_ms` specifies the maximum amount of time the
     /// iterator blocks (and waits for new messages).
     pub fn signals(&self, timeout_ms: u32) -> MprisSignals {
         MprisSignals::new(self.dbus_conn.clone(), timeout_ms)
     }
+
+    /// Reads all properties of `interface` with a single D-Bus call.
+    pub(crate) fn get_all_props(&self, interface: &str) -> Result<PropMap> {
+        self.dbus_conn.get_all_props("/org/mpris/MediaPlayer2", interface)
+    }
+
+    /// Safely reads the optional property `member` of `interface`.
+    pub(crate) fn get_optional_variant_prop(&self,
+                                            interface: &str,
+                                            member: &str)
+                                            -> Result<Option<Variant<Box<dyn RefArg>>>> {
+        self.dbus_conn.get_optional_variant_prop("/org/mpris/MediaPlayer2", interface, member)
+    }
+}
+
+/// Implements a getter for a mandatory property, which is read via `DBusConn::get_typed_prop`.
+macro_rules! prop_getter {
+    ($(#[$attr:meta])* $name:ident, $interface:expr, $member:ident, $return_type:ty) => {
+        $(#[$attr])*
+        pub fn $name(&self) -> Result<$return_type> {
+            match self.dbus_conn.get_typed_prop(
+                "/org/mpris/MediaPlayer2",
+                $interface,
+                stringify!($member),
+            )? {
+                ChangedProperty::$member(value) => Ok(value),
+                prop => bail!(ErrorKind::TypeCastError(prop.to_debug_str(), stringify!($return_type))),
+            }
+        }
+    };
+}
+
+/// Implements a getter for an optional property, which is read via
+/// `DBusConn::get_optional_typed_prop`.
+macro_rules! optional_prop_getter {
+    ($(#[$attr:meta])* $name:ident, $interface:expr, $member:ident, $return_type:ty) => {
+        $(#[$attr])*
+        pub fn $name(&self) -> Result<Option<$return_type>> {
+            match self.dbus_conn.get_optional_typed_prop(
+                "/org/mpris/MediaPlayer2",
+                $interface,
+                stringify!($member),
+            )? {
+                Some(ChangedProperty::$member(value)) => Ok(Some(value)),
+                None => Ok(None),
+                Some(prop) => {
+                    bail!(ErrorKind::TypeCastError(prop.to_debug_str(), stringify!($return_type)))
+                }
+            }
+        }
+    };
+}
+
+/// Implements `get_all`, which reads all properties of an interface via
+/// `DBusConn::get_all_props`.
+macro_rules! get_all {
+    ($interface:expr, $properties_type:ident) => {
+        /// Reads all properties of the interface with a single `GetAll` call.
+        ///
+        /// Properties which are unknown, or whose value could not be converted, are kept in the
+        /// `other` field.
+        pub fn get_all(&self) -> Result<::properties::$properties_type> {
+            let props = self.dbus_conn.get_all_props("/org/mpris/MediaPlayer2", $interface)?;
+            Ok(::properties::$properties_type::from_props(props, self.dbus_conn.decode_mode()))
+        }
+    };
 }
 
 #[derive(Debug)]
 pub struct MprisRoot {
-    dbus_conn: Rc<DBusConn>,
+    dbus_conn: Arc<DBusConn>,
 }
 
 
 impl MprisRoot {
-    fn new(dbus_conn: Rc<DBusConn>) -> Self {
+    fn new(dbus_conn: Arc<DBusConn>) -> Self {
         MprisRoot { dbus_conn }
     }
 
+    get_all!("org.mpris.MediaPlayer2", RootProperties);
+
     /// Brings the media player's user interface to the front using any appropriate mechanism
     /// available.
     pub fn raise(&self) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2", "CanRaise", "Raise")?;
         self.dbus_conn.call_method_without_reply(
             "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2",
             "Raise",
+            &[],
         )
     }
 
@@ -201,61 +472,37 @@ impl MprisRoot {
     /// The media player may refuse to allow clients to shut it down. In this case, the `can_quit`
     /// property is `false` and this method does nothing.
     pub fn quit(&self) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2", "CanQuit", "Quit")?;
         self.dbus_conn.call_method_without_reply(
             "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2",
             "Quit",
+            &[],
         )
     }
 
-    /// If `false`, calling `quit` will have no effect, and may raise an error. If `true`,
-    /// calling `quit` will cause the media application to attempt to quit (although it may still be
-    /// prevented from quitting by the user, for example).
-    ///
-    /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged` signal
-    /// is emitted with the new value.
-    pub fn can_quit(&self) -> Result<bool> {
-        match self.dbus_conn.get_prop(
-            "/org/mpris/MediaPlayer2",
-            "CanQuit",
-        ) {
-            Ok(MessageItem::Bool(cq)) => Ok(cq),
-            Ok(_) => {
-                Err(
-                    ErrorKind::GeneralError("Could not get property: unexpected type".to_string())
-                        .into(),
-                )
-            }
-            Err(err) => Err(err),
-        }
-    }
+    prop_getter!(
+        /// If `false`, calling `quit` will have no effect, and may raise an error. If `true`,
+        /// calling `quit` will cause the media application to attempt to quit (although it may
+        /// still be prevented from quitting by the user, for example).
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        can_quit, "org.mpris.MediaPlayer2", CanQuit, bool);
 
-    /// Whether the media player is occupying the fullscreen.
-    ///
-    /// This is typically used for videos. A value of `true` indicates that the media player is
-    /// taking up the full screen.
-    ///
-    /// Media centre software may well have this value fixed to `true`
-    ///
-    /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged` signal
-    /// is emitted with the new value.
-    ///
-    /// This property is optional.
-    pub fn fullscreen(&self) -> Result<Option<bool>> {
-        match self.dbus_conn.get_optional_prop(
-            "/org/mpris/MediaPlayer2",
-            "Fullscreen",
-        ) {
-            Ok(Some(MessageItem::Bool(cq))) => Ok(Some(cq)),
-            Ok(None) => Ok(None),
-            Err(err) => Err(err),
-            Ok(_) => {
-                Err(
-                    ErrorKind::GeneralError("Could not get property: unexpected type".to_string())
-                        .into(),
-                )
-            }
-        }
-    }
+    optional_prop_getter!(
+        /// Whether the media player is occupying the fullscreen.
+        ///
+        /// This is typically used for videos. A value of `true` indicates that the media player is
+        /// taking up the full screen.
+        ///
+        /// Media centre software may well have this value fixed to `true`
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        ///
+        /// This property is optional.
+        fullscreen, "org.mpris.MediaPlayer2", Fullscreen, bool);
 
     /// Whether the media player is occupying the fullscreen.
     ///
@@ -275,23 +522,672 @@ impl MprisRoot {
     ///
     /// This property is optional.
     pub fn set_fullscreen(&self, value: bool) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2", "CanSetFullscreen", "Fullscreen")?;
         self.dbus_conn.set_prop(
             "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2",
             "Fullscreen",
             MessageItem::Bool(value),
         )
     }
+
+    optional_prop_getter!(
+        /// If `false`, attempting to set `fullscreen` will have no effect, and may raise an error.
+        /// If `true`, attempting to set `fullscreen` will not raise an error, and (if it is
+        /// different from the current value) will cause the media player to attempt to enter or
+        /// exit fullscreen mode.
+        ///
+        /// Note that the media player may be unable to fulfil the request. In this case, the value
+        /// will not change. If the media player knows in advance that it will not be able to
+        /// fulfil the request, however, this property should be `false`.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        ///
+        /// This property is optional.
+        can_set_fullscreen, "org.mpris.MediaPlayer2", CanSetFullscreen, bool);
+
+    prop_getter!(
+        /// If `false`, calling `raise` will have no effect, and may raise an error. If `true`,
+        /// calling `raise` will cause the media application to attempt to bring its user
+        /// interface to the front, although it may be prevented from doing so (by the window
+        /// manager, for example).
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        can_raise, "org.mpris.MediaPlayer2", CanRaise, bool);
+
+    prop_getter!(
+        /// Indicates whether the `/org/mpris/MediaPlayer2` object implements the
+        /// `org.mpris.MediaPlayer2.TrackList` interface.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        has_track_list, "org.mpris.MediaPlayer2", HasTrackList, bool);
+
+    prop_getter!(
+        /// A friendly name to identify the media player to users. This should usually match the
+        /// name found in .desktop files (eg: "VLC media player").
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        identity, "org.mpris.MediaPlayer2", Identity, String);
+
+    optional_prop_getter!(
+        /// The basename of an installed .desktop file which complies with the Desktop entry
+        /// specification, with the ".desktop" extension stripped.
+        ///
+        /// Example: The desktop entry file is "/usr/share/applications/vlc.desktop", and this
+        /// property contains "vlc".
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        ///
+        /// This property is optional.
+        desktop_entry, "org.mpris.MediaPlayer2", DesktopEntry, String);
+
+    prop_getter!(
+        /// The URI schemes supported by the media player.
+        ///
+        /// This can be viewed as protocols supported by the player in almost all cases. Almost
+        /// every media player will include support for the "file" scheme. Other common schemes are
+        /// "http" and "rtsp".
+        ///
+        /// Note that URI schemes should be lower-case.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        supported_uri_schemes, "org.mpris.MediaPlayer2", SupportedUriSchemes, Vec<String>);
+
+    prop_getter!(
+        /// The mime-types supported by the media player.
+        ///
+        /// Mime-types should be in the standard format (eg: "audio/mpeg" or
+        /// "application/ogg").
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        supported_mime_types, "org.mpris.MediaPlayer2", SupportedMimeTypes, Vec<String>);
+}
+
+#[derive(Debug)]
+pub struct MprisPlayer {
+    dbus_conn: Arc<DBusConn>,
+}
+
+
+impl MprisPlayer {
+    fn new(dbus_conn: Arc<DBusConn>) -> Self {
+        MprisPlayer { dbus_conn }
+    }
+
+    get_all!("org.mpris.MediaPlayer2.Player", PlayerProperties);
+
+    /// Skips to the next track in the tracklist.
+    ///
+    /// If there is no next track (and endless playback and track repeat are both off), stop
+    /// playback.
+    ///
+    /// If playback is paused or stopped, it remains that way.
+    ///
+    /// If `can_go_next` is `false`, attempting to call this method should have no effect.
+    pub fn next(&self) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanGoNext", "Next")?;
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "Next",
+            &[],
+        )
+    }
+
+    /// Skips to the previous track in the tracklist.
+    ///
+    /// If there is no previous track (and endless playback and track repeat are both off), stop
+    /// playback.
+    ///
+    /// If playback is paused or stopped, it remains that way.
+    ///
+    /// If `can_go_previous` is `false`, attempting to call this method should have no effect.
+    pub fn previous(&self) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanGoPrevious", "Previous")?;
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "Previous",
+            &[],
+        )
+    }
+
+    /// Pauses playback.
+    ///
+    /// If playback is already paused, this has no effect.
+    ///
+    /// Calling `play` after this should cause playback to start again from the same position.
+    ///
+    /// If `can_pause` is `false`, attempting to call this method should have no effect.
+    pub fn pause(&self) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanPause", "Pause")?;
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "Pause",
+            &[],
+        )
+    }
+
+    /// Pauses playback.
+    ///
+    /// If playback is already paused, resumes playback.
+    ///
+    /// If playback is stopped, starts playback.
+    ///
+    /// If `can_pause` is `false`, attempting to call this method should have no effect and raise
+    /// an error.
+    pub fn play_pause(&self) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanPause", "PlayPause")?;
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "PlayPause",
+            &[],
+        )
+    }
+
+    /// Stops playback.
+    ///
+    /// If playback is already stopped, this has no effect.
+    ///
+    /// Calling `play` after this should cause playback to start again from the beginning of the
+    /// track.
+    ///
+    /// If `can_control` is `false`, attempting to call this method should have no effect and raise
+    /// an error.
+    pub fn stop(&self) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanControl", "Stop")?;
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "Stop",
+            &[],
+        )
+    }
+
+    /// Starts or resumes playback.
+    ///
+    /// If already playing, this has no effect.
+    ///
+    /// If paused, playback resumes from the current position.
+    ///
+    /// If there is no track to play, this has no effect.
+    ///
+    /// If `can_play` is `false`, attempting to call this method should have no effect.
+    pub fn play(&self) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanPlay", "Play")?;
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "Play",
+            &[],
+        )
+    }
+
+    /// Seeks forward in the current track by the specified number of microseconds.
+    ///
+    /// A negative value seeks back. If this would mean seeking back further than the start of the
+    /// track, the position is set to 0.
+    ///
+    /// If the value passed in would mean seeking beyond the end of the track, acts like a call to
+    /// `next`.
+    ///
+    /// If the `can_seek` property is `false`, this has no effect.
+    pub fn seek(&self, offset: i64) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanSeek", "Seek")?;
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "Seek",
+            &[MessageItem::Int64(offset)],
+        )
+    }
+
+    /// Sets the current track position in microseconds.
+    ///
+    /// If the `position` argument is less than 0, do nothing.
+    ///
+    /// If the `position` argument is greater than the track length, do nothing.
+    ///
+    /// If the `can_seek` property is `false`, this has no effect.
+    ///
+    /// The `track_id` is used to avoid race conditions: if it is not the id of the current track,
+    /// the call is ignored as "stale".
+    pub fn set_position(&self, track_id: &::TrackId, position: i64) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanSeek", "SetPosition")?;
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "SetPosition",
+            &[track_id.clone().into(), MessageItem::Int64(position)],
+        )
+    }
+
+    /// Opens the `uri` given as an argument.
+    ///
+    /// If the playback is stopped, starts playing.
+    ///
+    /// If the uri scheme or the mime-type of the uri to open is not supported, this method does
+    /// nothing and may raise an error. In particular, if the list of available uri schemes is
+    /// empty, this method may not be implemented.
+    ///
+    /// If the media player implements the `TrackList` interface, then the opened track should be
+    /// made part of the tracklist.
+    pub fn open_uri(&self, uri: &str) -> Result<()> {
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "OpenUri",
+            &[MessageItem::Str(uri.to_string())],
+        )
+    }
+
+    prop_getter!(
+        /// The current playback status.
+        ///
+        /// May be `Playing`, `Paused` or `Stopped`.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        playback_status, "org.mpris.MediaPlayer2.Player", PlaybackStatus, ::PlaybackStatus);
+
+    optional_prop_getter!(
+        /// The current loop / repeat status.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        ///
+        /// This property is optional.
+        loop_status, "org.mpris.MediaPlayer2.Player", LoopStatus, ::LoopStatus);
+
+    /// Sets the current loop / repeat status.
+    ///
+    /// If `can_control` is `false`, attempting to set this property should have no effect and
+    /// raise an error.
+    ///
+    /// This property is optional.
+    pub fn set_loop_status(&self, value: ::LoopStatus) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanControl", "LoopStatus")?;
+        self.dbus_conn.set_prop(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "LoopStatus",
+            value.into(),
+        )
+    }
+
+    prop_getter!(
+        /// The current playback rate.
+        ///
+        /// The value must fall in the range described by `minimum_rate` and `maximum_rate`, and
+        /// must not be 0.0.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        rate, "org.mpris.MediaPlayer2.Player", Rate, ::PlaybackRate);
+
+    /// Sets the current playback rate.
+    ///
+    /// If playback is paused, the `playback_status` property should be used to indicate this. A
+    /// value of 0.0 should not be set by the client. If it is, the media player should act as
+    /// though `pause` was called.
+    ///
+    /// The value must fall in the range described by `minimum_rate` and `maximum_rate`.
+    pub fn set_rate(&self, value: ::PlaybackRate) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanControl", "Rate")?;
+        self.dbus_conn.set_prop(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "Rate",
+            MessageItem::Double(value),
+        )
+    }
+
+    optional_prop_getter!(
+        /// A value of `false` indicates that playback is progressing linearly through a playlist,
+        /// while `true` means playback is progressing through a playlist in some other order.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        ///
+        /// This property is optional.
+        shuffle, "org.mpris.MediaPlayer2.Player", Shuffle, bool);
+
+    /// Enables or disables shuffling.
+    ///
+    /// If `can_control` is `false`, attempting to set this property should have no effect and
+    /// raise an error.
+    ///
+    /// This property is optional.
+    pub fn set_shuffle(&self, value: bool) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanControl", "Shuffle")?;
+        self.dbus_conn.set_prop(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "Shuffle",
+            MessageItem::Bool(value),
+        )
+    }
+
+    prop_getter!(
+        /// The metadata of the current element.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        metadata, "org.mpris.MediaPlayer2.Player", Metadata, ::MetadataMap);
+
+    prop_getter!(
+        /// The volume level.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        volume, "org.mpris.MediaPlayer2.Player", Volume, ::Volume);
+
+    /// Sets the volume level.
+    ///
+    /// When setting, if a negative value is passed, the volume should be set to 0.0.
+    ///
+    /// If `can_control` is `false`, attempting to set this property should have no effect and
+    /// raise an error.
+    pub fn set_volume(&self, value: ::Volume) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.Player", "CanControl", "Volume")?;
+        self.dbus_conn.set_prop(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "Volume",
+            MessageItem::Double(value),
+        )
+    }
+
+    prop_getter!(
+        /// The current track position in microseconds, between 0 and the `mpris:length` metadata
+        /// entry.
+        ///
+        /// Note: If the media player allows it, the current playback position can be changed
+        /// either the `set_position` method or the `seek` method on this interface. If this is not
+        /// the case, the `can_seek` property is `false`, and setting this property has no effect
+        /// and can raise an error.
+        ///
+        /// The `org.freedesktop.DBus.Properties.PropertiesChanged` signal is **not** emitted when
+        /// this property changes.
+        position, "org.mpris.MediaPlayer2.Player", Position, i64);
+
+    prop_getter!(
+        /// The minimum value which the `rate` property can take. Clients should not attempt to
+        /// set the `rate` property below this value.
+        ///
+        /// Note that even if this value is 0.0 or negative, clients should not attempt to set the
+        /// `rate` property to 0.0.
+        ///
+        /// This value should always be 1.0 or less.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        minimum_rate, "org.mpris.MediaPlayer2.Player", MinimumRate, ::PlaybackRate);
+
+    prop_getter!(
+        /// The maximum value which the `rate` property can take. Clients should not attempt to
+        /// set the `rate` property above this value.
+        ///
+        /// This value should always be 1.0 or greater.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        maximum_rate, "org.mpris.MediaPlayer2.Player", MaximumRate, ::PlaybackRate);
+
+    prop_getter!(
+        /// Whether the client can call the `next` method on this interface and expect the current
+        /// track to change.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        can_go_next, "org.mpris.MediaPlayer2.Player", CanGoNext, bool);
+
+    prop_getter!(
+        /// Whether the client can call the `previous` method on this interface and expect the
+        /// current track to change.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        can_go_previous, "org.mpris.MediaPlayer2.Player", CanGoPrevious, bool);
+
+    prop_getter!(
+        /// Whether playback can be started using `play` or `play_pause`.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        can_play, "org.mpris.MediaPlayer2.Player", CanPlay, bool);
+
+    prop_getter!(
+        /// Whether playback can be paused using `pause` or `play_pause`.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        can_pause, "org.mpris.MediaPlayer2.Player", CanPause, bool);
+
+    prop_getter!(
+        /// Whether the client can control the playback position using `seek` and
+        /// `set_position`. This may be different for different tracks.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        can_seek, "org.mpris.MediaPlayer2.Player", CanSeek, bool);
+
+    prop_getter!(
+        /// Whether the media player may be controlled over this interface.
+        ///
+        /// This property is not expected to change, as it describes an intrinsic capability of the
+        /// implementation. If this is `false`, clients should assume that all properties on this
+        /// interface are read-only (and will raise errors if writing to them is attempted), no
+        /// methods are implemented and all other properties starting with "can_" are also
+        /// `false`.
+        ///
+        /// The `org.freedesktop.DBus.Properties.PropertiesChanged` signal is **not** emitted when
+        /// this property changes.
+        can_control, "org.mpris.MediaPlayer2.Player", CanControl, bool);
+}
+
+#[derive(Debug)]
+pub struct MprisTrackList {
+    dbus_conn: Arc<DBusConn>,
+}
+
+
+impl MprisTrackList {
+    fn new(dbus_conn: Arc<DBusConn>) -> Self {
+        MprisTrackList { dbus_conn }
+    }
+
+    get_all!("org.mpris.MediaPlayer2.TrackList", TrackListProperties);
+
+    /// Gets all the metadata available for a set of tracks.
+    ///
+    /// Each set of metadata must have a `mpris:trackid` entry at the very least, which contains a
+    /// string that uniquely identifies this track within the scope of the tracklist.
+    pub fn get_tracks_metadata(&self, track_ids: &[::TrackId]) -> Result<Vec<::MetadataMap>> {
+        let paths = track_ids_to_paths(track_ids);
+        let reply = self.dbus_conn.call_method(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.TrackList",
+            "GetTracksMetadata",
+            &[MessageItem::from(&paths[..])],
+        )?;
+        tracks_metadata_from_reply(&reply, self.dbus_conn.decode_mode())
+    }
+
+    /// Adds a URI in the tracklist.
+    ///
+    /// `after_track` is the identifier of the track after which the new item should be inserted.
+    /// The path `/org/mpris/MediaPlayer2/TrackList/NoTrack` indicates that the track should be
+    /// inserted at the start of the track list.
+    ///
+    /// If `set_as_current` is `true`, the newly inserted track should be considered as the current
+    /// track. Setting this to `true` has the same effect as calling `go_to` afterwards.
+    ///
+    /// If `can_edit_tracks` is `false`, this has no effect.
+    pub fn add_track(&self, uri: &str, after_track: &::TrackId, set_as_current: bool) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.TrackList", "CanEditTracks", "AddTrack")?;
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.TrackList",
+            "AddTrack",
+            &[MessageItem::Str(uri.to_string()), after_track.clone().into(), MessageItem::Bool(set_as_current)],
+        )
+    }
+
+    /// Removes an item from the tracklist.
+    ///
+    /// If the track is not part of this tracklist, this has no effect.
+    ///
+    /// If `can_edit_tracks` is `false`, this has no effect.
+    pub fn remove_track(&self, track_id: &::TrackId) -> Result<()> {
+        self.dbus_conn.check_capability("org.mpris.MediaPlayer2.TrackList", "CanEditTracks", "RemoveTrack")?;
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.TrackList",
+            "RemoveTrack",
+            &[track_id.clone().into()],
+        )
+    }
+
+    /// Skip to the specified `track_id`.
+    ///
+    /// If the track is not part of this tracklist, this has no effect.
+    ///
+    /// If this object is not `/org/mpris/MediaPlayer2`, the current `TrackList`'s tracks should be
+    /// replaced with the contents of this `TrackList`, and the `TrackListReplaced` signal should be
+    /// fired from `/org/mpris/MediaPlayer2`.
+    pub fn go_to(&self, track_id: &::TrackId) -> Result<()> {
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.TrackList",
+            "GoTo",
+            &[track_id.clone().into()],
+        )
+    }
+
+    prop_getter!(
+        /// An array which contains the identifier of each track in the tracklist, in order.
+        ///
+        /// The `org.freedesktop.DBus.Properties.PropertiesChanged` signal is emitted every time
+        /// this property changes, but the signal message does not contain the new value. Client
+        /// implementations should rather rely on the `TrackAdded`, `TrackRemoved` and
+        /// `TrackListReplaced` signals to keep their representation of the tracklist up to date.
+        tracks, "org.mpris.MediaPlayer2.TrackList", Tracks, Vec<::TrackId>);
+
+    prop_getter!(
+        /// If `false`, calling `add_track` or `remove_track` will have no effect, and may raise an
+        /// error.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        can_edit_tracks, "org.mpris.MediaPlayer2.TrackList", CanEditTracks, bool);
+}
+
+#[derive(Debug)]
+pub struct MprisPlaylists {
+    dbus_conn: Arc<DBusConn>,
+}
+
+
+impl MprisPlaylists {
+    fn new(dbus_conn: Arc<DBusConn>) -> Self {
+        MprisPlaylists { dbus_conn }
+    }
+
+    get_all!("org.mpris.MediaPlayer2.Playlists", PlaylistsProperties);
+
+    /// Starts playing the given playlist.
+    ///
+    /// Note that this must be implemented. If the media player does not allow clients to change
+    /// the playlist, it should not implement this interface at all.
+    ///
+    /// It is up to the media player whether this completely replaces the current tracklist, or
+    /// whether it is merely inserted into the tracklist and the first track starts. For example,
+    /// if the media player is operating in a "jukebox" mode, it may just append the playlist to
+    /// the list of upcoming tracks, and skip to the first track in the playlist.
+    pub fn activate_playlist(&self, playlist_id: &str) -> Result<()> {
+        let playlist_id = playlist_id_to_path(playlist_id)?;
+        self.dbus_conn.call_method_without_reply(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Playlists",
+            "ActivatePlaylist",
+            &[MessageItem::ObjectPath(playlist_id)],
+        )
+    }
+
+    /// Gets a set of playlists.
+    ///
+    /// `index` is the index of the first playlist to be fetched (according to the ordering),
+    /// `max_count` the maximum number of playlists to fetch, `order` the ordering that should be
+    /// used and `reverse_order` whether the order should be reversed.
+    pub fn get_playlists(&self,
+                         index: u32,
+                         max_count: u32,
+                         order: ::PlaylistOrdering,
+                         reverse_order: bool)
+                         -> Result<Vec<::Playlist>> {
+        let reply = self.dbus_conn.call_method(
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Playlists",
+            "GetPlaylists",
+            &[MessageItem::UInt32(index),
+              MessageItem::UInt32(max_count),
+              order.into(),
+              MessageItem::Bool(reverse_order)],
+        )?;
+        playlists_from_reply(&reply)
+    }
+
+    prop_getter!(
+        /// The number of playlists available.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        playlist_count, "org.mpris.MediaPlayer2.Playlists", PlaylistCount, u32);
+
+    prop_getter!(
+        /// The available orderings. At least one must be offered.
+        ///
+        /// Media players may not return playlists in the order given by `user_defined` if they
+        /// do not support it.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        orderings, "org.mpris.MediaPlayer2.Playlists", Orderings, Vec<::PlaylistOrdering>);
+
+    prop_getter!(
+        /// The currently-active playlist.
+        ///
+        /// If there is no currently-active playlist, the `valid` field of the returned
+        /// `MaybePlaylist` is `false`.
+        ///
+        /// Note that this may not have a value even after `activate_playlist` is called with a
+        /// valid playlist id as `activate_playlist` implementations have the option of simply
+        /// inserting the contents of the playlist into the current tracklist.
+        ///
+        /// When this property changes, the `org.freedesktop.DBus.Properties.PropertiesChanged`
+        /// signal is emitted with the new value.
+        active_playlist, "org.mpris.MediaPlayer2.Playlists", ActivePlaylist, ::MaybePlaylist);
 }
 
 /// Iterator over `MprisSignal`s.
 pub struct MprisSignals {
-    dbus_conn: Rc<DBusConn>,
+    dbus_conn: Arc<DBusConn>,
     timeout_ms: u32,
 }
 
 impl MprisSignals {
     /// Creates new `MprisSignals` instance.
-    fn new(dbus_conn: Rc<DBusConn>, timeout_ms: u32) -> Self {
+    fn new(dbus_conn: Arc<DBusConn>, timeout_ms: u32) -> Self {
         MprisSignals { dbus_conn, timeout_ms }
     }
 }
@@ -300,23 +1196,36 @@ impl Iterator for MprisSignals {
     type Item = MprisSignal;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self
-            .dbus_conn
-            .conn
-            .incoming(self.timeout_ms)
-            .filter(|msg| {
-                if let Some(msg_str) = msg.sender() {
-                    &msg_str as &str == self.dbus_conn.unique_bus_name
-                        || &msg_str as &str == self.dbus_conn.bus_name
-                } else { false }
-            })
-            .filter_map(|msg| MprisSignal::from_message(&msg))
-            .next()
+        let deadline = Instant::now() + Duration::from_millis(u64::from(self.timeout_ms));
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let remaining_ms = remaining.as_secs() as u32 * 1000 + remaining.subsec_millis();
+            // the lock is released between the polls, so other threads can call methods meanwhile
+            let msg = self.dbus_conn.conn().incoming(cmp::min(remaining_ms, SIGNAL_POLL_INTERVAL_MS)).next();
+            match msg {
+                Some(msg) => {
+                    let from_player = msg.sender()
+                        .map(|sender| {
+                            &sender as &str == self.dbus_conn.unique_bus_name
+                                || &sender as &str == self.dbus_conn.bus_name
+                        })
+                        .unwrap_or(false);
+                    if from_player {
+                        if let Some(signal) = MprisSignal::from_message(&msg, self.dbus_conn.decode_mode()) {
+                            return Some(signal);
+                        }
+                    }
+                }
+                None if remaining_ms == 0 => return None,
+                None => {}
+            }
+        }
     }
 }
 
 /// Enum for the signals emitted by an MPRIS interface.
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum MprisSignal {
     /// Indicates that the track position has changed in a way that is inconsistant with the current
     /// playing state.
@@ -330,24 +1239,25 @@ pub enum MprisSignal {
     /// last known one when going from `Paused` to `Playing`, and 0 when going from `Stopped` to
     /// `Playing`.
     Seeked { position: i64 },
-    // todo MPRIS TrackList
-//    /// Indicates that the entire tracklist has been replaced.
-//    /// It is left up to the implementation to decide when a change to the track list is invasive
-//    /// enough that this signal should be emitted instead of a series of `TrackAdded` and
-//    /// `TrackRemoved` signals.
-//    TrackListReplaced { tracks: Vec<::TrackId>, current_track: ::TrackId },
-//    /// Indicates that a track has been added to the track list.
-//    TrackAdded { metadata: ::MetadataMap, after_track: ::TrackId },
-//    /// Indicates that a track has been removed from the track list.
-//    TrackRemoved { track_id: ::TrackId },
-//    /// Indicates that the metadata of a track in the tracklist has changed.
-//    /// This may indicate that a track has been replaced, in which case the `track_id` metadata
-//    /// entry is different from the `track_id` argument.
-//    TrackMetadataChanged { track_id: ::TrackId, metadata: ::MetadataMap },
-    // todo MPRIS Playlists
-//    /// Indicates that either the Name or Icon attribute of a playlist has changed.
-//    /// Client implementations should be aware that this signal may not be implemented.
-//    PlaylistChanged { playlist: ::Playlist },
+    /// Indicates that the entire tracklist has been replaced.
+    ///
+    /// It is left up to the implementation to decide when a change to the track list is invasive
+    /// enough that this signal should be emitted instead of a series of `TrackAdded` and
+    /// `TrackRemoved` signals.
+    TrackListReplaced { tracks: Vec<::TrackId>, current_track: ::TrackId },
+    /// Indicates that a track has been added to the track list.
+    TrackAdded { metadata: ::MetadataMap, after_track: ::TrackId },
+    /// Indicates that a track has been removed from the track list.
+    TrackRemoved { track_id: ::TrackId },
+    /// Indicates that the metadata of a track in the tracklist has changed.
+    ///
+    /// This may indicate that a track has been replaced, in which case the `track_id` metadata
+    /// entry is different from the `track_id` argument.
+    TrackMetadataChanged { track_id: ::TrackId, metadata: ::MetadataMap },
+    /// Indicates that either the Name or Icon attribute of a playlist has changed.
+    ///
+    /// Client implementations should be aware that this signal may not be implemented.
+    PlaylistChanged { playlist: ::Playlist },
     /// Indicates that a properties have changed or have been invalidated.
     PropertiesChanged {
         interface: String,
@@ -357,20 +1267,20 @@ pub enum MprisSignal {
 }
 
 impl MprisSignal {
-    /// Builds a new `MprisSignal` from a DBUS `Message`.
+    /// Builds a new `MprisSignal` from a DBUS `Message`, decoding the metadata in the `mode`.
     ///
     /// Only signals with the sender bus name "org.freedesktop.DBus" and `bus_name` are considered.
-    fn from_message(msg: &Message) -> Option<Self> {
+    pub(crate) fn from_message(msg: &Message, mode: DecodeMode) -> Option<Self> {
         if let (MessageType::Signal, Some(_path), Some(_interface), Some(_member)) = msg.headers() {
             match (&_path as &str, &_interface as &str, &_member as &str) {
                 ("/org/mpris/MediaPlayer2", "org.freedesktop.DBus.Properties", "PropertiesChanged") => {
                     if let (Some(interface),
                         Some(ch_props),
                         Some(invalidated_properties)) =
-                    msg.get3::<_, HashMap<String, Variant<Box<RefArg>>>, _>() {
+                    msg.get3::<_, HashMap<String, Variant<Box<dyn RefArg>>>, _>() {
                         let changed_properties = ch_props
                             .into_iter()
-                            .filter_map(|(n, mut v)| ChangedProperty::from_variant(&n, &mut v).ok())
+                            .filter_map(|(n, mut v)| ChangedProperty::from_variant(&n, &mut v, mode).ok())
                             .collect();
                         Some(MprisSignal::PropertiesChanged {
                             interface,
@@ -384,15 +1294,83 @@ impl MprisSignal {
                         Some(MprisSignal::Seeked { position: pos })
                     } else { None }
                 }
-                // todo MPRIS TrackList
-                // todo MPRIS Playlists
+                ("/org/mpris/MediaPlayer2", "org.mpris.MediaPlayer2.TrackList", "TrackListReplaced") => {
+                    if let (Some(tracks), Some(current_track)) = msg.get2::<Vec<Path>, Path>() {
+                        Some(MprisSignal::TrackListReplaced {
+                            tracks: tracks.iter().filter_map(|t| ::TrackId::from_str(t).ok()).collect(),
+                            current_track: ::TrackId::from_str(&current_track).ok()?,
+                        })
+                    } else { None }
+                }
+                ("/org/mpris/MediaPlayer2", "org.mpris.MediaPlayer2.TrackList", "TrackAdded") => {
+                    let mut iter = msg.iter_init();
+                    let metadata = metadata_from_refarg(&iter.get_refarg()?, mode).ok()?;
+                    iter.next();
+                    let after_track = ::TrackId::from_str(&iter.get::<Path>()?).ok()?;
+                    Some(MprisSignal::TrackAdded { metadata, after_track })
+                }
+                ("/org/mpris/MediaPlayer2", "org.mpris.MediaPlayer2.TrackList", "TrackRemoved") => {
+                    let track_id = ::TrackId::from_str(&msg.get1::<Path>()?).ok()?;
+                    Some(MprisSignal::TrackRemoved { track_id })
+                }
+                ("/org/mpris/MediaPlayer2", "org.mpris.MediaPlayer2.TrackList", "TrackMetadataChanged") => {
+                    let mut iter = msg.iter_init();
+                    let track_id = ::TrackId::from_str(&iter.get::<Path>()?).ok()?;
+                    iter.next();
+                    let metadata = metadata_from_refarg(&iter.get_refarg()?, mode).ok()?;
+                    Some(MprisSignal::TrackMetadataChanged { track_id, metadata })
+                }
+                ("/org/mpris/MediaPlayer2", "org.mpris.MediaPlayer2.Playlists", "PlaylistChanged") => {
+                    let playlist = playlist_from_refarg(&msg.iter_init().get_refarg()?).ok()?;
+                    Some(MprisSignal::PlaylistChanged { playlist })
+                }
                 _ => None
             }
         } else { None }
     }
+
+    /// Builds the DBUS `Message` of this signal, as it is emitted by a media player.
+    pub(crate) fn to_message(&self) -> Message {
+        let (interface, member) = match *self {
+            MprisSignal::Seeked { .. } => ("org.mpris.MediaPlayer2.Player", "Seeked"),
+            MprisSignal::TrackListReplaced { .. } => ("org.mpris.MediaPlayer2.TrackList", "TrackListReplaced"),
+            MprisSignal::TrackAdded { .. } => ("org.mpris.MediaPlayer2.TrackList", "TrackAdded"),
+            MprisSignal::TrackRemoved { .. } => ("org.mpris.MediaPlayer2.TrackList", "TrackRemoved"),
+            MprisSignal::TrackMetadataChanged { .. } => {
+                ("org.mpris.MediaPlayer2.TrackList", "TrackMetadataChanged")
+            }
+            MprisSignal::PlaylistChanged { .. } => ("org.mpris.MediaPlayer2.Playlists", "PlaylistChanged"),
+            MprisSignal::PropertiesChanged { .. } => ("org.freedesktop.DBus.Properties", "PropertiesChanged"),
+        };
+        let msg = Message::new_signal("/org/mpris/MediaPlayer2", interface, member)
+            .expect("Could not construct signal.");
+
+        match *self {
+            MprisSignal::Seeked { position } => msg.append1(position),
+            MprisSignal::TrackListReplaced { ref tracks, ref current_track } => {
+                let tracks: Vec<Path> = tracks.iter().map(track_id_to_path).collect();
+                msg.append2(tracks, track_id_to_path(current_track))
+            }
+            MprisSignal::TrackAdded { ref metadata, ref after_track } => {
+                msg.append2(metadata_to_dict(metadata), track_id_to_path(after_track))
+            }
+            MprisSignal::TrackRemoved { ref track_id } => msg.append1(track_id_to_path(track_id)),
+            MprisSignal::TrackMetadataChanged { ref track_id, ref metadata } => {
+                msg.append2(track_id_to_path(track_id), metadata_to_dict(metadata))
+            }
+            MprisSignal::PlaylistChanged { ref playlist } => msg.append1(playlist_to_tuple(playlist)),
+            MprisSignal::PropertiesChanged { ref interface, ref changed_properties, ref invalidated_properties } => {
+                let changed_properties: HashMap<String, Variant<Box<dyn RefArg>>> = changed_properties.iter()
+                    .filter_map(|prop| Some((prop.name()?.to_string(), Variant(prop.to_refarg()?))))
+                    .collect();
+                msg.append3(interface, changed_properties, invalidated_properties)
+            }
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ChangedProperty {
     // Mpris root properties
     CanQuit(bool),
@@ -412,6 +1390,7 @@ pub enum ChangedProperty {
     Shuffle(bool),
     Metadata(::MetadataMap),
     Volume(::Volume),
+    Position(i64),
     MinimumRate(::PlaybackRate),
     MaximumRate(::PlaybackRate),
     CanGoNext(bool),
@@ -419,22 +1398,22 @@ pub enum ChangedProperty {
     CanPlay(bool),
     CanPause(bool),
     CanSeek(bool),
+    CanControl(bool),
 
     // Mpris TrackList properties
-    Tracks,
+    Tracks(Vec<::TrackId>),
     CanEditTracks(bool),
 
-    // todo MPRIS Playlists
-// Mpris Playlists properties
-//    PlaylistCount(u32),
-//    Orderings(::PlaylistOrdering),
-//    ActivePlaylist(::Playlist),
+    // Mpris Playlists properties
+    PlaylistCount(u32),
+    Orderings(Vec<::PlaylistOrdering>),
+    ActivePlaylist(::MaybePlaylist),
 
     Other(String),
 }
 
 impl ChangedProperty {
-    fn from_variant(name: &str, data: &mut Variant<Box<RefArg>>) -> Result<Self> {
+    pub(crate) fn from_variant(name: &str, data: &mut Variant<Box<dyn RefArg>>, mode: DecodeMode) -> Result<Self> {
         use client::ChangedProperty::*;
 
         let res = match name {
@@ -454,20 +1433,9 @@ impl ChangedProperty {
             "LoopStatus" => LoopStatus(::LoopStatus::from_str(cast_var_to_str(data)?)?),
             "Rate" => Rate(cast_var(data)?),
             "Shuffle" => Shuffle(cast_var(data)?),
-            "Metadata" => {
-                if let Some(raw_map_variant) = ::dbus::arg::cast_mut::<HashMap<String, Variant<Box<RefArg>>>>(&mut data.0) {
-                    let raw_map: HashMap<String, Rc<RefArg>> = raw_map_variant.into_iter()
-                        .map(|(k, v)| {
-                            let value_data: Box<RefArg> = ::std::mem::replace(&mut v.0, Box::new(false));
-                            (k.to_string(), value_data.into())
-                        })
-                        .collect();
-
-                    return Ok(Metadata(::MetadataMap::from_map(raw_map)?));
-                }
-                bail!(ErrorKind::TypeCastError(data.to_debug_str(), "HashMap"));
-            }
+            "Metadata" => Metadata(metadata_from_refarg(&data.0, mode)?),
             "Volume" => Volume(cast_var(data)?),
+            "Position" => Position(cast_var(data)?),
             "MinimumRate" => MinimumRate(cast_var(data)?),
             "MaximumRate" => MaximumRate(cast_var(data)?),
             "CanGoNext" => CanGoNext(cast_var(data)?),
@@ -475,28 +1443,224 @@ impl ChangedProperty {
             "CanPlay" => CanPlay(cast_var(data)?),
             "CanPause" => CanPause(cast_var(data)?),
             "CanSeek" => CanSeek(cast_var(data)?),
+            "CanControl" => CanControl(cast_var(data)?),
 
 // Mpris TrackList properties
-            "Tracks" => Tracks,
+            "Tracks" => Tracks(track_ids_from_refarg(&data.0)?),
             "CanEditTracks" => CanEditTracks(cast_var(data)?),
 
 // Mpris Playlists properties
-// "PlaylistCount" => PlaylistCount(*data.as_any().downcast_ref::<u32>()?),
-// "Orderings" => Orderings(*data.as_any().downcast_ref::<Vec<String>>()?),
-// "ActivePlaylist" => ActivePlaylist( ... ),
+            "PlaylistCount" => PlaylistCount(cast_var(data)?),
+            "Orderings" => {
+                Orderings(cast_var::<Vec<String>>(data)?
+                    .iter()
+                    .map(|ordering| ::PlaylistOrdering::from_str(ordering))
+                    .collect::<Result<_>>()?)
+            }
+            "ActivePlaylist" => ActivePlaylist(maybe_playlist_from_refarg(&data.0)?),
             _ => Other(format!("{:?}", data.0)),
         };
 
         Ok(res)
     }
+
+    /// Returns the D-Bus name of the property, or `None` for `Other`.
+    pub(crate) fn name(&self) -> Option<&'static str> {
+        use client::ChangedProperty::*;
+
+        let name = match *self {
+            CanQuit(..) => "CanQuit",
+            Fullscreen(..) => "Fullscreen",
+            CanSetFullscreen(..) => "CanSetFullscreen",
+            CanRaise(..) => "CanRaise",
+            HasTrackList(..) => "HasTrackList",
+            Identity(..) => "Identity",
+            DesktopEntry(..) => "DesktopEntry",
+            SupportedUriSchemes(..) => "SupportedUriSchemes",
+            SupportedMimeTypes(..) => "SupportedMimeTypes",
+            PlaybackStatus(..) => "PlaybackStatus",
+            LoopStatus(..) => "LoopStatus",
+            Rate(..) => "Rate",
+            Shuffle(..) => "Shuffle",
+            Metadata(..) => "Metadata",
+            Volume(..) => "Volume",
+            Position(..) => "Position",
+            MinimumRate(..) => "MinimumRate",
+            MaximumRate(..) => "MaximumRate",
+            CanGoNext(..) => "CanGoNext",
+            CanGoPrevious(..) => "CanGoPrevious",
+            CanPlay(..) => "CanPlay",
+            CanPause(..) => "CanPause",
+            CanSeek(..) => "CanSeek",
+            CanControl(..) => "CanControl",
+            Tracks(..) => "Tracks",
+            CanEditTracks(..) => "CanEditTracks",
+            PlaylistCount(..) => "PlaylistCount",
+            Orderings(..) => "Orderings",
+            ActivePlaylist(..) => "ActivePlaylist",
+            Other(..) => return None,
+        };
+        Some(name)
+    }
+
+    /// Converts the value of the property into an argument which can be sent over D-Bus, or
+    /// returns `None` for `Other`.
+    pub(crate) fn to_refarg(&self) -> Option<Box<dyn RefArg>> {
+        use client::ChangedProperty::*;
+
+        let arg: Box<dyn RefArg> = match *self {
+            CanQuit(value) | Fullscreen(value) | CanSetFullscreen(value) | CanRaise(value)
+            | HasTrackList(value) | Shuffle(value) | CanGoNext(value) | CanGoPrevious(value)
+            | CanPlay(value) | CanPause(value) | CanSeek(value) | CanControl(value)
+            | CanEditTracks(value) => Box::new(value),
+            Identity(ref value) | DesktopEntry(ref value) => Box::new(value.clone()),
+            SupportedUriSchemes(ref value) | SupportedMimeTypes(ref value) => Box::new(value.clone()),
+            PlaybackStatus(ref value) => Box::new(value.as_ref().to_string()),
+            LoopStatus(ref value) => Box::new(value.as_ref().to_string()),
+            Rate(value) | Volume(value) | MinimumRate(value) | MaximumRate(value) => Box::new(value),
+            Metadata(ref value) => metadata_to_refarg(value),
+            Position(value) => Box::new(value),
+            Tracks(ref value) => {
+                Box::new(value.iter().map(track_id_to_path).collect::<Vec<Path<'static>>>())
+            }
+            PlaylistCount(value) => Box::new(value),
+            Orderings(ref value) => {
+                Box::new(value.iter()
+                    .map(|ordering| ordering.as_ref().to_string())
+                    .collect::<Vec<String>>())
+            }
+            ActivePlaylist(ref value) => Box::new((value.valid, playlist_to_tuple(&value.playlist))),
+            Other(..) => return None,
+        };
+        Some(arg)
+    }
+}
+
+
+/// Builds a `MetadataMap` from an `a{sv}` argument, which is decoded in the `mode`.
+///
+/// Depending on how the argument was read, the dictionary is either a `HashMap` or an internal
+/// type of the `dbus` crate, so it is only accessed via `RefArg::as_iter`.
+fn metadata_from_refarg(arg: &dyn RefArg, mode: DecodeMode) -> Result<::MetadataMap> {
+    let mut iter = arg.as_iter()
+        .ok_or_else(|| Error::from(ErrorKind::TypeCastError(arg.to_debug_str(), "HashMap")))?;
+    let mut entries = Vec::new();
+    while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+        let key = key.as_str()
+            .ok_or_else(|| Error::from(ErrorKind::TypeCastError(key.to_debug_str(), "&str")))?;
+        entries.extend(RawEntry::from_refarg(key, value));
+    }
+    decode_metadata(entries, mode)
+}
+
+/// The keys of the metadata entries which the specification types as `int32`. All other integers
+/// are sent as `int64` or `uint64`.
+const INT32_METADATA_KEYS: [&str; 4] = ["xesam:audioBPM", "xesam:discNumber", "xesam:trackNumber", "xesam:userCount"];
+
+/// Converts a `MetadataMap` into an `a{sv}` argument.
+fn metadata_to_refarg(metadata: &::MetadataMap) -> Box<dyn RefArg> {
+    Box::new(metadata_to_dict(metadata))
 }
 
+/// Converts a `MetadataMap` into the map of an `a{sv}` argument.
+pub(crate) fn metadata_to_dict(metadata: &::MetadataMap) -> HashMap<String, Variant<Box<dyn RefArg>>> {
+    metadata.iter()
+        .map(|(key, value)| {
+            let arg: Box<dyn RefArg> = match value.as_i64() {
+                Some(number) if INT32_METADATA_KEYS.contains(&key) && number as i32 as i64 == number => {
+                    Box::new(number as i32)
+                }
+                _ => value.to_refarg(),
+            };
+            (key.to_string(), Variant(arg))
+        })
+        .collect()
+}
+
+/// Converts a `TrackId` into an `o` argument.
+pub(crate) fn track_id_to_path(track_id: &::TrackId) -> Path<'static> {
+    Path::from(track_id.as_ref().to_string())
+}
+
+/// Converts a list of `TrackId`s into the elements of an `ao` argument.
+pub(crate) fn track_ids_to_paths(track_ids: &[::TrackId]) -> Vec<Path<'static>> {
+    track_ids.iter().map(track_id_to_path).collect()
+}
+
+/// Builds the list of `MetadataMap`s in the reply of `GetTracksMetadata`, which are decoded in
+/// the `mode`.
+pub(crate) fn tracks_metadata_from_reply(reply: &Message, mode: DecodeMode) -> Result<Vec<::MetadataMap>> {
+    let metadata = reply.iter_init().get_refarg()
+        .chain_err(|| "Could not read the reply of GetTracksMetadata")?;
+    let maps = metadata.as_iter()
+        .ok_or_else(|| Error::from(ErrorKind::TypeCastError(metadata.to_debug_str(), "Vec")))?;
+    maps.map(|map| metadata_from_refarg(map, mode)).collect()
+}
+
+/// Builds a list of `TrackId`s from an `ao` argument.
+fn track_ids_from_refarg(arg: &dyn RefArg) -> Result<Vec<::TrackId>> {
+    let iter = arg.as_iter()
+        .ok_or_else(|| Error::from(ErrorKind::TypeCastError(arg.to_debug_str(), "Vec")))?;
+    iter.map(|track_id| {
+            let track_id_str = track_id.as_str()
+                .ok_or_else(|| Error::from(ErrorKind::TypeCastError(track_id.to_debug_str(), "&str")))?;
+            ::TrackId::from_str(track_id_str)
+        })
+        .collect()
+}
+
+/// Builds a `Playlist` from an `(oss)` argument.
+fn playlist_from_refarg(arg: &dyn RefArg) -> Result<::Playlist> {
+    let fields: Vec<&str> = arg.as_iter()
+        .map(|iter| iter.filter_map(|field| field.as_str()).collect())
+        .unwrap_or_default();
+    if let [id, name, icon] = fields[..] {
+        Ok(::Playlist { id: id.to_string(), name: name.to_string(), icon: icon.to_string() })
+    } else {
+        bail!(ErrorKind::TypeCastError(arg.to_debug_str(), "Playlist"))
+    }
+}
+
+/// Builds the list of `Playlist`s in the reply of `GetPlaylists`.
+pub(crate) fn playlists_from_reply(reply: &Message) -> Result<Vec<::Playlist>> {
+    let playlists = reply.iter_init().get_refarg()
+        .chain_err(|| "Could not read the reply of GetPlaylists")?;
+    let iter = playlists.as_iter()
+        .ok_or_else(|| Error::from(ErrorKind::TypeCastError(playlists.to_debug_str(), "Vec")))?;
+    iter.map(playlist_from_refarg).collect()
+}
+
+/// Converts a playlist id into an `o` argument.
+pub(crate) fn playlist_id_to_path(playlist_id: &str) -> Result<Path<'static>> {
+    Path::new(playlist_id.to_string())
+        .map_err(|_| ErrorKind::TypeBuildError("Path", playlist_id.to_string()).into())
+}
+
+/// Converts a `Playlist` into a tuple which can be sent as an `(oss)` argument.
+pub(crate) fn playlist_to_tuple(playlist: &::Playlist) -> (Path<'static>, String, String) {
+    (Path::new(playlist.id.clone()).unwrap_or_default(), playlist.name.clone(), playlist.icon.clone())
+}
+
+/// Builds a `MaybePlaylist` from a `(b(oss))` argument.
+fn maybe_playlist_from_refarg(arg: &dyn RefArg) -> Result<::MaybePlaylist> {
+    let mut iter = arg.as_iter()
+        .ok_or_else(|| Error::from(ErrorKind::TypeCastError(arg.to_debug_str(), "MaybePlaylist")))?;
+    if let (Some(valid), Some(playlist)) = (iter.next(), iter.next()) {
+        if valid.arg_type() == ArgType::Boolean {
+            return Ok(::MaybePlaylist {
+                valid: valid.as_u64() == Some(1),
+                playlist: playlist_from_refarg(playlist)?,
+            });
+        }
+    }
+    bail!(ErrorKind::TypeCastError(arg.to_debug_str(), "MaybePlaylist"))
+}
 
-fn cast_var_to_str(var: &Variant<Box<RefArg>>) -> Result<&str> {
+fn cast_var_to_str(var: &Variant<Box<dyn RefArg>>) -> Result<&str> {
     var.0.as_str().ok_or_else(|| ErrorKind::TypeCastError(var.to_debug_str(), "&str").into())
 }
 
-fn cast_var<T: Clone + 'static>(var: &Variant<Box<RefArg>>) -> Result<T> {
+fn cast_var<T: Clone + 'static>(var: &Variant<Box<dyn RefArg>>) -> Result<T> {
     ::dbus::arg::cast::<T>(&var.0)
         .cloned()
         .ok_or_else(|| ErrorKind::TypeCastError(var.to_debug_str(), stringify!(T)).into())
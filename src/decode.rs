@@ -0,0 +1,250 @@
+//! This module contains the decoding of metadata, which accepts the common deviations of media
+//! players from the specification.
+//!
+//! Many media players send the metadata entries with other types than the specification
+//! requires, e.g. `mpris:length` as `uint64` instead of `int64`, `xesam:artist` as a single
+//! string instead of a list, or `mpris:trackid` as a string instead of an object path. By default
+//! such entries are converted to the specified type, and each conversion is recorded as a
+//! `Coercion`, which `MetadataMap::coercions` returns. A missing or invalid `mpris:trackid` is
+//! replaced by `/org/mpris/MediaPlayer2/TrackList/NoTrack`.
+//!
+//! In the `DecodeMode::Strict` mode, every entry which does not have the specified type is
+//! rejected with `ErrorKind::NonConformingMetadata`, e.g. to check media players for conformance.
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+use dbus::arg::{ArgType, RefArg};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use errors::*;
+use {MetadataMap, MetadataValue, TrackId};
+
+
+/// The track id which is used if a media player does not send a valid one.
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// The D-Bus signatures of the metadata entries of the specification.
+///
+/// Dates are sent as strings in the ISO 8601 format.
+pub const SPECIFIED_SIGNATURES: [(&str, &str); 22] = [
+    ("mpris:trackid", "o"),
+    ("mpris:length", "x"),
+    ("mpris:artUrl", "s"),
+    ("xesam:album", "s"),
+    ("xesam:albumArtist", "as"),
+    ("xesam:artist", "as"),
+    ("xesam:asText", "s"),
+    ("xesam:audioBPM", "i"),
+    ("xesam:autoRating", "d"),
+    ("xesam:comment", "as"),
+    ("xesam:composer", "as"),
+    ("xesam:contentCreated", "s"),
+    ("xesam:discNumber", "i"),
+    ("xesam:firstUsed", "s"),
+    ("xesam:genre", "as"),
+    ("xesam:lastUsed", "s"),
+    ("xesam:lyricist", "as"),
+    ("xesam:title", "s"),
+    ("xesam:trackNumber", "i"),
+    ("xesam:url", "s"),
+    ("xesam:userCount", "i"),
+    ("xesam:userRating", "d"),
+];
+
+/// The keys of the metadata entries which are dates in the ISO 8601 format.
+const DATE_KEYS: [&str; 3] = ["xesam:contentCreated", "xesam:firstUsed", "xesam:lastUsed"];
+
+/// How metadata entries which do not have the specified type are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DecodeMode {
+    /// The entries are converted to the specified type where possible, and kept as they are
+    /// otherwise.
+    #[default]
+    Lenient,
+    /// The metadata is rejected.
+    Strict,
+}
+
+/// A metadata entry which did not have the specified type and was converted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coercion {
+    /// The key of the entry, e.g. `mpris:length`.
+    pub key: String,
+    /// The D-Bus signature of the entry as it was received, or an empty string if the entry was
+    /// missing.
+    pub found: String,
+    /// The D-Bus signature required by the specification, e.g. `x`.
+    pub expected: &'static str,
+}
+
+/// A metadata entry before it is decoded.
+pub(crate) struct RawEntry {
+    pub key: String,
+    pub value: MetadataValue,
+    /// The D-Bus signature of the value, if it was read from D-Bus.
+    pub signature: Option<String>,
+}
+
+impl RawEntry {
+    /// Reads an entry from D-Bus. Variants are unwrapped. Returns `None` if the value cannot be
+    /// represented.
+    pub fn from_refarg(key: &str, arg: &dyn RefArg) -> Option<Self> {
+        let arg = match arg.arg_type() {
+            ArgType::Variant => arg.as_iter()?.next()?,
+            _ => arg,
+        };
+        Some(RawEntry {
+            key: key.to_string(),
+            value: MetadataValue::from_refarg(arg)?,
+            signature: Some(arg.signature().to_string()),
+        })
+    }
+}
+
+/// Returns the signature required by the specification for the entry `key`.
+pub fn specified_signature(key: &str) -> Option<&'static str> {
+    SPECIFIED_SIGNATURES.iter().find(|&&(name, _)| name == key).map(|&(_, signature)| signature)
+}
+
+/// Returns the D-Bus signature, which is sent for a value.
+fn signature_of(value: &MetadataValue) -> &'static str {
+    match *value {
+        MetadataValue::String(..) | MetadataValue::DateTime(..) => "s",
+        MetadataValue::StringList(..) => "as",
+        MetadataValue::Int(..) => "x",
+        MetadataValue::UInt(..) => "t",
+        MetadataValue::Double(..) => "d",
+        MetadataValue::Bool(..) => "b",
+        MetadataValue::ObjectPath(..) => "o",
+        MetadataValue::List(..) => "av",
+        MetadataValue::Nested(..) => "a{sv}",
+    }
+}
+
+/// Parses a date in the ISO 8601 format, which may lack the time or the time zone. Dates without a
+/// time zone are taken as UTC, and a date without a day as the first day of the year.
+pub(crate) fn parse_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date);
+    }
+    let midnight = |date: NaiveDate| date.and_hms_opt(0, 0, 0);
+    let naive = NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S").ok()
+        .or_else(|| midnight(NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?))
+        .or_else(|| midnight(NaiveDate::parse_from_str(&format!("{}-01-01", date), "%Y-%m-%d").ok()?))?;
+    Some(FixedOffset::east_opt(0)?.from_utc_datetime(&naive))
+}
+
+/// Converts a value to the type of the specified `signature`. Returns `None` if the value cannot
+/// be converted.
+fn coerce(key: &str, value: &MetadataValue, signature: &str) -> Option<MetadataValue> {
+    let coerced = match (signature, value) {
+        ("o", MetadataValue::ObjectPath(..)) | ("d", MetadataValue::Double(..)) | ("as", MetadataValue::StringList(..)) => {
+            value.clone()
+        }
+        ("s", MetadataValue::String(..)) if !DATE_KEYS.contains(&key) => value.clone(),
+        ("s", MetadataValue::DateTime(..)) if DATE_KEYS.contains(&key) => value.clone(),
+        ("o", MetadataValue::String(path)) => {
+            TrackId::from_str(path).ok()?;
+            MetadataValue::ObjectPath(path.clone())
+        }
+        ("x", _) | ("i", _) => {
+            let number = match value {
+                MetadataValue::Int(..) | MetadataValue::UInt(..) => value.as_i64()?,
+                MetadataValue::Double(number) if number.fract() == 0.0 => *number as i64,
+                MetadataValue::String(number) => number.trim().parse().ok()?,
+                _ => return None,
+            };
+            if signature == "i" && i64::from(number as i32) != number {
+                return None;
+            }
+            MetadataValue::Int(number)
+        }
+        ("d", MetadataValue::Int(number)) => MetadataValue::Double(*number as f64),
+        ("d", MetadataValue::UInt(number)) => MetadataValue::Double(*number as f64),
+        ("d", MetadataValue::String(number)) => MetadataValue::Double(number.trim().parse().ok()?),
+        ("s", MetadataValue::ObjectPath(text)) => MetadataValue::String(text.clone()),
+        ("s", MetadataValue::StringList(values)) if values.len() == 1 => MetadataValue::String(values[0].clone()),
+        ("as", MetadataValue::String(text)) => MetadataValue::StringList(vec![text.clone()]),
+        _ => return None,
+    };
+    Some(coerced)
+}
+
+/// Returns whether a value already has the type of the specified `signature`.
+fn has_specified_type(key: &str, value: &MetadataValue, signature: &str) -> bool {
+    match (signature, value) {
+        ("o", MetadataValue::ObjectPath(..)) => true,
+        ("x", MetadataValue::Int(..)) | ("x", MetadataValue::UInt(..)) => value.as_i64().is_some(),
+        ("i", MetadataValue::Int(..)) | ("i", MetadataValue::UInt(..)) => {
+            match value.as_i64() {
+                Some(number) => i64::from(number as i32) == number,
+                None => false,
+            }
+        }
+        ("d", MetadataValue::Double(..)) => true,
+        ("as", MetadataValue::StringList(..)) => true,
+        ("s", MetadataValue::DateTime(..)) => DATE_KEYS.contains(&key),
+        ("s", MetadataValue::String(..)) => !DATE_KEYS.contains(&key),
+        _ => false,
+    }
+}
+
+/// Decodes the entries of a metadata map.
+pub(crate) fn decode_metadata<I: IntoIterator<Item = RawEntry>>(entries: I, mode: DecodeMode) -> Result<MetadataMap> {
+    let mut values: HashMap<String, MetadataValue> = HashMap::new();
+    let mut coercions = Vec::new();
+    for RawEntry { key, value, signature } in entries {
+        let expected = match specified_signature(&key) {
+            Some(expected) => expected,
+            None => {
+                values.insert(key, value);
+                continue;
+            }
+        };
+        // dates are sent as strings in any ISO 8601 form, but stored as `DateTime`
+        let value = match value {
+            MetadataValue::String(ref date) if DATE_KEYS.contains(&(&key as &str)) => {
+                parse_date(date).map(MetadataValue::DateTime).unwrap_or_else(|| value.clone())
+            }
+            value => value,
+        };
+
+        // Values which were not read from D-Bus only need to have the right variant.
+        let conforms = has_specified_type(&key, &value, expected) && match signature {
+            Some(ref signature) => signature == expected,
+            None => true,
+        };
+        let found = signature.unwrap_or_else(|| signature_of(&value).to_string());
+        if conforms {
+            values.insert(key, value);
+            continue;
+        }
+        if mode == DecodeMode::Strict {
+            bail!(ErrorKind::NonConformingMetadata(key, found, expected.to_string()));
+        }
+        match coerce(&key, &value, expected) {
+            Some(coerced) => {
+                // only conversions which change the signature or the value are recorded
+                if found != expected || coerced != value {
+                    coercions.push(Coercion { key: key.clone(), found, expected });
+                }
+                values.insert(key, coerced);
+            }
+            None if key == "mpris:trackid" => {
+                coercions.push(Coercion { key: key.clone(), found, expected });
+                values.insert(key, MetadataValue::ObjectPath(NO_TRACK.to_string()));
+            }
+            None => {
+                values.insert(key, value);
+            }
+        }
+    }
+
+    if !values.contains_key("mpris:trackid") {
+        if mode == DecodeMode::Strict {
+            bail!(ErrorKind::NonConformingMetadata("mpris:trackid".to_string(), String::new(), "o".to_string()));
+        }
+        coercions.push(Coercion { key: "mpris:trackid".to_string(), found: String::new(), expected: "o" });
+        values.insert("mpris:trackid".to_string(), MetadataValue::ObjectPath(NO_TRACK.to_string()));
+    }
+    Ok(MetadataMap::from_decoded(values, coercions))
+}
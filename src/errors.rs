@@ -23,9 +23,57 @@ error_chain! {
             description("type cast error")
             display("could not cast type '{:?}' to {}", from, to)
         }
-        ServiceUnknown(bus_name: String) {
+        InvalidTemplate(position: usize, msg: String) {
+            description("invalid template")
+            display("invalid template at position {}: {}", position, msg)
+        }
+        NonConformingMetadata(key: String, found: String, expected: String) {
+            description("non-conforming metadata")
+            display("metadata entry '{}' has the type '{}' instead of '{}'", key, found, expected)
+        }
+        NotSupported(player: String, interface: String, member: String) {
+            description("not supported")
+            display("{} does not support {}.{}", player, interface, member)
+        }
+        InvalidArgs(player: String, interface: String, member: String) {
+            description("invalid arguments")
+            display("{} rejected the arguments of {}.{}", player, interface, member)
+        }
+        UnknownMethod(player: String, interface: String, member: String) {
+            description("unknown method")
+            display("{} does not provide {}.{}", player, interface, member)
+        }
+        UnknownInterface(player: String, interface: String, member: String) {
+            description("unknown interface")
+            display("{} does not implement {}, which provides {}", player, interface, member)
+        }
+        UnknownProperty(player: String, interface: String, member: String) {
+            description("unknown property")
+            display("{} has no property {}.{}", player, interface, member)
+        }
+        PropertyReadOnly(player: String, interface: String, member: String) {
+            description("property is read-only")
+            display("the property {}.{} of {} is read-only", interface, member, player)
+        }
+        AccessDenied(player: String, interface: String, member: String) {
+            description("access denied")
+            display("{} denied the access to {}.{}", player, interface, member)
+        }
+        NoReply(player: String, interface: String, member: String) {
+            description("no reply")
+            display("{} did not reply to {}.{} in time", player, interface, member)
+        }
+        ServiceUnknown(player: String, interface: String, member: String) {
             description("service unknown")
-            display("The service {} is unknown. Is the player still running?", bus_name)
+            display("The player {} is unknown. Is the player still running? (calling {}.{})", player, interface, member)
+        }
+        NotCapable(player: String, interface: String, member: String, capability: String) {
+            description("not capable")
+            display("{} cannot handle {}.{}, because {} is false", player, interface, member, capability)
+        }
+        Disconnected(player: String, interface: String, member: String) {
+            description("disconnected")
+            display("the D-Bus connection was closed while calling {}.{} of {}", interface, member, player)
         }
     }
 }
@@ -41,10 +89,86 @@ impl<D> DebugStr for D where D: Debug {
     }
 }
 
-/// Returns `true` if `err`'s name contains `match_err_name`. If there is no `name`, `false` is
-/// returned.
+/// Returns `true` if `err`'s name is `match_err_name`, e.g.
+/// `org.freedesktop.DBus.Error.UnknownProperty`. If there is no `name`, `false` is returned.
 pub(crate) fn match_dbus_err(err: &::dbus::Error, match_err_name: &str) -> bool {
-    err.name()
-        .map(|name| name.contains(match_err_name))
-        .unwrap_or(false)
+    err.name() == Some(match_err_name)
+}
+
+/// Converts the D-Bus error `err`, which was returned for the `member` of `interface` of the media
+/// player `player`, into the matching `ErrorKind`. The D-Bus error is kept as the cause. Errors
+/// which have no matching `ErrorKind` are returned as `ErrorKind::DBus`.
+pub(crate) fn dbus_error(err: ::dbus::Error, player: &str, interface: &str, member: &str) -> Error {
+    let (player, interface, member) = (player.to_string(), interface.to_string(), member.to_string());
+    let kind = match err.name().unwrap_or("") {
+        "org.freedesktop.DBus.Error.NotSupported" => ErrorKind::NotSupported(player, interface, member),
+        "org.freedesktop.DBus.Error.InvalidArgs" => ErrorKind::InvalidArgs(player, interface, member),
+        "org.freedesktop.DBus.Error.UnknownMethod" => ErrorKind::UnknownMethod(player, interface, member),
+        // a media player without the object `/org/mpris/MediaPlayer2` implements no interface
+        "org.freedesktop.DBus.Error.UnknownInterface" |
+        "org.freedesktop.DBus.Error.UnknownObject" => ErrorKind::UnknownInterface(player, interface, member),
+        "org.freedesktop.DBus.Error.UnknownProperty" => ErrorKind::UnknownProperty(player, interface, member),
+        "org.freedesktop.DBus.Error.PropertyReadOnly" => ErrorKind::PropertyReadOnly(player, interface, member),
+        "org.freedesktop.DBus.Error.AccessDenied" => ErrorKind::AccessDenied(player, interface, member),
+        "org.freedesktop.DBus.Error.NoReply" |
+        "org.freedesktop.DBus.Error.Timeout" |
+        "org.freedesktop.DBus.Error.TimedOut" => ErrorKind::NoReply(player, interface, member),
+        "org.freedesktop.DBus.Error.ServiceUnknown" |
+        "org.freedesktop.DBus.Error.NameHasNoOwner" => ErrorKind::ServiceUnknown(player, interface, member),
+        "org.freedesktop.DBus.Error.Disconnected" => ErrorKind::Disconnected(player, interface, member),
+        // libdbus reports some errors of the bus only in the message
+        _ if err.message().unwrap_or("").contains("org.freedesktop.DBus.Error.ServiceUnknown") => {
+            ErrorKind::ServiceUnknown(player, interface, member)
+        }
+        _ => return err.into(),
+    };
+    Error::with_chain(err, kind)
+}
+
+/// Converts the D-Bus error `err`, which was returned for writing the property `member` of
+/// `interface` of the object `obj_path`.
+///
+/// Writing an absent property fails with `ErrorKind::AccessedAbsentOptionalProperty`, whose cause
+/// is the `ErrorKind::UnknownProperty` error of the media player. Other errors are converted by
+/// `dbus_error`.
+pub(crate) fn set_prop_error(err: ::dbus::Error, player: &str, obj_path: &str, interface: &str, member: &str) -> Error {
+    let absent = match_dbus_err(&err, "org.freedesktop.DBus.Error.UnknownProperty");
+    let err = dbus_error(err, player, interface, member);
+    if absent {
+        Error::with_chain(err, ErrorKind::AccessedAbsentOptionalProperty(obj_path.to_string(), member.to_string()))
+    } else {
+        err
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_ErrorKind() {
+        let kind = |name: &str| {
+            let err = ::dbus::Error::new_custom(name, "message");
+            dbus_error(err, "vlc", "org.mpris.MediaPlayer2.Player", "Next").0
+        };
+        let context = || ("vlc".to_string(), "org.mpris.MediaPlayer2.Player".to_string(), "Next".to_string());
+        assert!(matches!(kind("org.freedesktop.DBus.Error.NotSupported"), ErrorKind::NotSupported(..)));
+        assert!(matches!(kind("org.freedesktop.DBus.Error.InvalidArgs"), ErrorKind::InvalidArgs(..)));
+        assert!(matches!(kind("org.freedesktop.DBus.Error.UnknownMethod"), ErrorKind::UnknownMethod(..)));
+        assert!(matches!(kind("org.freedesktop.DBus.Error.UnknownInterface"), ErrorKind::UnknownInterface(..)));
+        assert!(matches!(kind("org.freedesktop.DBus.Error.UnknownObject"), ErrorKind::UnknownInterface(..)));
+        assert!(matches!(kind("org.freedesktop.DBus.Error.UnknownProperty"), ErrorKind::UnknownProperty(..)));
+        assert!(matches!(kind("org.freedesktop.DBus.Error.PropertyReadOnly"), ErrorKind::PropertyReadOnly(..)));
+        assert!(matches!(kind("org.freedesktop.DBus.Error.AccessDenied"), ErrorKind::AccessDenied(..)));
+        assert!(matches!(kind("org.freedesktop.DBus.Error.Timeout"), ErrorKind::NoReply(..)));
+        assert!(matches!(kind("org.freedesktop.DBus.Error.Disconnected"), ErrorKind::Disconnected(..)));
+        match kind("org.freedesktop.DBus.Error.ServiceUnknown") {
+            ErrorKind::ServiceUnknown(player, interface, member) => assert_eq!((player, interface, member), context()),
+            other => panic!("wrong kind of error: {:?}", other),
+        }
+        // only whole names match, and other errors are kept
+        assert!(matches!(kind("org.example.Error.NotSupportedYet"), ErrorKind::DBus(..)));
+        assert!(matches!(kind("org.freedesktop.DBus.Error.Failed"), ErrorKind::DBus(..)));
+    }
 }
@@ -0,0 +1,510 @@
+//! This module contains templates, which format the metadata and the state of a media player,
+//! e.g. for status bars.
+//!
+//! A template is text with expressions in double braces:
+//!
+//! ```text
+//! {{#if artist}}{{artist}} - {{/if}}{{trunc(title, 30)}} [{{duration(mpris:length)}}]
+//! ```
+//!
+//! An expression is one of:
+//!
+//! - A key, e.g. `xesam:title` or `mpris:length`. Keys without a namespace are looked up with the
+//!   `xesam:` and `mpris:` prefixes, so `title` is `xesam:title` and `length` is `mpris:length`.
+//!   A `PlayerState` additionally provides `status`, `loop`, `shuffle`, `volume`, `rate`,
+//!   `position` and `identity`.
+//! - A string literal in double quotes, e.g. `"Unknown"`, or an integer, e.g. `30`.
+//! - A comparison of two expressions with `==` or `!=`, e.g. `status == "Playing"`.
+//! - A function call:
+//!   - `duration(x)` formats the time `x` in microseconds as `m:ss`, or `h:mm:ss`.
+//!   - `default(x, y)` is `y` if `x` is missing or empty, and `x` otherwise.
+//!   - `trunc(x, n)` truncates `x` to `n` characters, and ends it with `…` if it was longer.
+//!   - `join(x, separator)` joins the elements of the list `x`. Lists which are shown directly
+//!     are joined with `, `.
+//!
+//! `{{#if x}}...{{else}}...{{/if}}` shows its first part if `x` is present, not empty and not
+//! `false`, and the (optional) `else` part otherwise. Missing values are shown as empty text.
+use std::str::FromStr;
+
+use errors::*;
+use state::PlayerState;
+
+
+/// A value, which is shown by a template.
+///
+/// Values are produced by keys, literals and functions. Missing values are represented by
+/// `None` instead, and are shown as empty text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A text, e.g. a title or a string literal. It is shown as it is, and counts as true in a
+    /// condition unless it is empty.
+    Text(String),
+    /// A list of texts, e.g. the artists of a track. It is shown with its elements joined by `, `,
+    /// and counts as true unless it is empty.
+    List(Vec<String>),
+    /// An integer, e.g. `mpris:length` in microseconds or an integer literal. It is shown in
+    /// decimal notation, and always counts as true.
+    Int(i64),
+    /// A floating point number, e.g. the volume or a rating. It is shown in its shortest decimal
+    /// notation, and always counts as true.
+    Float(f64),
+    /// A boolean, e.g. the shuffle status or the result of a comparison. It is shown as `true` or
+    /// `false`, and counts as its own value in a condition.
+    Bool(bool),
+}
+
+impl<'a> From<&'a ::MetadataValue> for Value {
+    fn from(value: &'a ::MetadataValue) -> Value {
+        match *value {
+            ::MetadataValue::StringList(ref values) => Value::List(values.clone()),
+            ::MetadataValue::List(ref values) => Value::List(values.iter().map(|value| value.to_string()).collect()),
+            ::MetadataValue::Int(number) => Value::Int(number),
+            ::MetadataValue::UInt(number) => Value::Int(number as i64),
+            ::MetadataValue::Double(number) => Value::Float(number),
+            ::MetadataValue::Bool(value) => Value::Bool(value),
+            _ => Value::Text(value.to_string()),
+        }
+    }
+}
+
+impl Value {
+    /// Returns the value as text, as it is shown by a template.
+    pub fn to_text(&self) -> String {
+        match *self {
+            Value::Text(ref text) => text.clone(),
+            Value::List(ref values) => values.join(", "),
+            Value::Int(number) => number.to_string(),
+            Value::Float(number) => number.to_string(),
+            Value::Bool(value) => value.to_string(),
+        }
+    }
+
+    /// Returns whether the value counts as true in a condition.
+    fn is_true(&self) -> bool {
+        match *self {
+            Value::Text(ref text) => !text.is_empty(),
+            Value::List(ref values) => !values.is_empty(),
+            Value::Bool(value) => value,
+            Value::Int(..) | Value::Float(..) => true,
+        }
+    }
+}
+
+/// Formats a time in microseconds as `m:ss`, or `h:mm:ss` if it is at least an hour long.
+pub fn format_duration(us: i64) -> String {
+    let seconds = us.max(0) / 1_000_000;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Provides the values of the keys in a template.
+pub trait TemplateContext {
+    /// Returns the value of `key`, or `None` if there is none.
+    fn value(&self, key: &str) -> Option<Value>;
+}
+
+impl TemplateContext for ::MetadataMap {
+    fn value(&self, key: &str) -> Option<Value> {
+        if key.contains(':') {
+            return self.get(key).map(Value::from);
+        }
+        ["xesam:", "mpris:"].iter()
+            .filter_map(|prefix| self.get(&format!("{}{}", prefix, key)))
+            .map(Value::from)
+            .next()
+    }
+}
+
+impl TemplateContext for PlayerState {
+    fn value(&self, key: &str) -> Option<Value> {
+        match key {
+            "status" => self.player.playback_status.map(|status| Value::Text(status.as_ref().to_string())),
+            "loop" => self.player.loop_status.as_ref().map(|status| Value::Text(status.as_ref().to_string())),
+            "shuffle" => self.player.shuffle.map(Value::Bool),
+            "volume" => self.player.volume.map(Value::Float),
+            "rate" => self.player.rate.map(Value::Float),
+            "position" => self.player.position.map(Value::Int),
+            "identity" => self.root.identity.clone().map(Value::Text),
+            _ => self.player.metadata.as_ref()?.value(key),
+        }
+    }
+}
+
+/// An expression in a template.
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Key(String),
+    Literal(Value),
+    Compare { left: Box<Expr>, right: Box<Expr>, equal: bool },
+    Call { function: Function, args: Vec<Expr> },
+}
+
+/// The functions which can be called in a template.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Duration,
+    Default,
+    Trunc,
+    Join,
+}
+
+impl Function {
+    /// Returns the function and its number of arguments.
+    fn from_name(name: &str) -> Option<(Function, usize)> {
+        match name {
+            "duration" => Some((Function::Duration, 1)),
+            "default" => Some((Function::Default, 2)),
+            "trunc" => Some((Function::Trunc, 2)),
+            "join" => Some((Function::Join, 2)),
+            _ => None,
+        }
+    }
+}
+
+impl Expr {
+    /// Evaluates the expression. Returns `None` if the value is missing.
+    fn eval<C: TemplateContext>(&self, context: &C) -> Option<Value> {
+        match *self {
+            Expr::Key(ref key) => context.value(key),
+            Expr::Literal(ref value) => Some(value.clone()),
+            Expr::Compare { ref left, ref right, equal } => {
+                let text = |expr: &Expr| expr.eval(context).map(|value| value.to_text()).unwrap_or_default();
+                Some(Value::Bool((text(left) == text(right)) == equal))
+            }
+            Expr::Call { function, ref args } => {
+                let value = args[0].eval(context);
+                match function {
+                    Function::Duration => match value? {
+                        Value::Int(us) => Some(Value::Text(format_duration(us))),
+                        Value::Float(us) => Some(Value::Text(format_duration(us as i64))),
+                        value => Some(value),
+                    },
+                    Function::Default => match value {
+                        Some(ref value) if value.is_true() => Some(value.clone()),
+                        _ => args[1].eval(context),
+                    },
+                    Function::Trunc => {
+                        let text = value?.to_text();
+                        let max_chars = match args[1].eval(context) {
+                            Some(Value::Int(max_chars)) => max_chars.max(0) as usize,
+                            _ => return Some(Value::Text(text)),
+                        };
+                        if text.chars().count() <= max_chars {
+                            Some(Value::Text(text))
+                        } else {
+                            Some(Value::Text(text.chars().take(max_chars).chain(Some('…')).collect()))
+                        }
+                    }
+                    Function::Join => {
+                        let separator = args[1].eval(context).map(|value| value.to_text()).unwrap_or_default();
+                        match value? {
+                            Value::List(values) => Some(Value::Text(values.join(&separator))),
+                            value => Some(value),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A part of a template.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Expr(Expr),
+    If { condition: Expr, then: Vec<Node>, otherwise: Vec<Node> },
+}
+
+/// A parsed template, which can be rendered for a `MetadataMap` or a `PlayerState`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    /// Parses a template. See the module documentation for the syntax.
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parser = Parser { template, position: 0 };
+        let (nodes, end) = parser.parse_nodes()?;
+        match end {
+            Tag::End => Ok(Template { nodes }),
+            _ => parser.error("unexpected {{else}} or {{/if}}"),
+        }
+    }
+
+    /// Renders the template with the values provided by `context`.
+    pub fn render<C: TemplateContext>(&self, context: &C) -> String {
+        let mut output = String::new();
+        render_nodes(&self.nodes, context, &mut output);
+        output
+    }
+}
+
+impl FromStr for Template {
+    type Err = Error;
+
+    fn from_str(template: &str) -> Result<Self> {
+        Template::parse(template)
+    }
+}
+
+fn render_nodes<C: TemplateContext>(nodes: &[Node], context: &C, output: &mut String) {
+    for node in nodes {
+        match *node {
+            Node::Text(ref text) => output.push_str(text),
+            Node::Expr(ref expr) => {
+                if let Some(value) = expr.eval(context) {
+                    output.push_str(&value.to_text());
+                }
+            }
+            Node::If { ref condition, ref then, ref otherwise } => {
+                let is_true = condition.eval(context).map(|value| value.is_true()).unwrap_or(false);
+                render_nodes(if is_true { then } else { otherwise }, context, output);
+            }
+        }
+    }
+}
+
+/// The tag which ended a sequence of nodes.
+#[derive(Debug, PartialEq)]
+enum Tag {
+    Else,
+    EndIf,
+    End,
+}
+
+/// The tokens of an expression.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Open,
+    Close,
+    Comma,
+    Equal,
+    NotEqual,
+}
+
+struct Parser<'a> {
+    template: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, msg: &str) -> Result<T> {
+        bail!(ErrorKind::InvalidTemplate(self.position, msg.to_string()))
+    }
+
+    /// Parses nodes until the end of the template, or until an `{{else}}` or `{{/if}}` tag.
+    fn parse_nodes(&mut self) -> Result<(Vec<Node>, Tag)> {
+        let mut nodes = Vec::new();
+        loop {
+            let rest = &self.template[self.position..];
+            let start = match rest.find("{{") {
+                Some(start) => start,
+                None => {
+                    if !rest.is_empty() {
+                        nodes.push(Node::Text(rest.to_string()));
+                    }
+                    self.position = self.template.len();
+                    return Ok((nodes, Tag::End));
+                }
+            };
+            if start > 0 {
+                nodes.push(Node::Text(rest[..start].to_string()));
+            }
+            self.position += start + 2;
+
+            let tokens = self.tokenize()?;
+            match tokens.first() {
+                Some(Token::Ident(name)) if name == "#if" => {
+                    let condition = self.parse_expr(&tokens[1..])?;
+                    let (then, tag) = self.parse_nodes()?;
+                    let otherwise = match tag {
+                        Tag::Else => {
+                            let (otherwise, tag) = self.parse_nodes()?;
+                            if tag != Tag::EndIf {
+                                return self.error("missing {{/if}}");
+                            }
+                            otherwise
+                        }
+                        Tag::EndIf => Vec::new(),
+                        Tag::End => return self.error("missing {{/if}}"),
+                    };
+                    nodes.push(Node::If { condition, then, otherwise });
+                }
+                Some(Token::Ident(name)) if name == "else" && tokens.len() == 1 => {
+                    return Ok((nodes, Tag::Else));
+                }
+                Some(Token::Ident(name)) if name == "/if" && tokens.len() == 1 => {
+                    return Ok((nodes, Tag::EndIf));
+                }
+                _ => nodes.push(Node::Expr(self.parse_expr(&tokens)?)),
+            }
+        }
+    }
+
+    /// Splits the expression up to the next `}}` into tokens.
+    fn tokenize(&mut self) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        loop {
+            let rest = &self.template[self.position..];
+            let c = match rest.chars().next() {
+                Some(c) => c,
+                None => return self.error("missing }}"),
+            };
+            if rest.starts_with("}}") {
+                self.position += 2;
+                return Ok(tokens);
+            }
+
+            let (token, len) = match c {
+                ' ' | '\t' | '\n' => {
+                    self.position += c.len_utf8();
+                    continue;
+                }
+                '(' => (Token::Open, 1),
+                ')' => (Token::Close, 1),
+                ',' => (Token::Comma, 1),
+                '=' if rest.starts_with("==") => (Token::Equal, 2),
+                '!' if rest.starts_with("!=") => (Token::NotEqual, 2),
+                '"' => {
+                    let mut text = String::new();
+                    let mut chars = rest.char_indices().skip(1);
+                    let len = loop {
+                        match chars.next() {
+                            Some((i, '"')) => break i + 1,
+                            Some((_, '\\')) => match chars.next() {
+                                Some((_, c)) => text.push(c),
+                                None => return self.error("unterminated string"),
+                            },
+                            Some((_, c)) => text.push(c),
+                            None => return self.error("unterminated string"),
+                        }
+                    };
+                    (Token::Str(text), len)
+                }
+                c if c.is_ascii_digit() || c == '-' => {
+                    let len = rest[1..].find(|c: char| !c.is_ascii_digit()).map(|len| len + 1).unwrap_or(rest.len());
+                    match rest[..len].parse() {
+                        Ok(number) => (Token::Int(number), len),
+                        Err(_) => return self.error("invalid number"),
+                    }
+                }
+                c if c.is_alphanumeric() || c == '#' || c == '/' || c == '_' => {
+                    let len = rest.find(|c: char| !(c.is_alphanumeric() || "#/_:-.".contains(c)))
+                        .unwrap_or(rest.len());
+                    (Token::Ident(rest[..len].to_string()), len)
+                }
+                _ => return self.error(&format!("unexpected character '{}'", c)),
+            };
+            tokens.push(token);
+            self.position += len;
+        }
+    }
+
+    /// Parses the tokens of an expression.
+    fn parse_expr(&self, tokens: &[Token]) -> Result<Expr> {
+        let (expr, rest) = self.parse_comparison(tokens)?;
+        if !rest.is_empty() {
+            return self.error("unexpected tokens after the expression");
+        }
+        Ok(expr)
+    }
+
+    fn parse_comparison<'t>(&self, tokens: &'t [Token]) -> Result<(Expr, &'t [Token])> {
+        let (left, rest) = self.parse_primary(tokens)?;
+        let equal = match rest.first() {
+            Some(&Token::Equal) => true,
+            Some(&Token::NotEqual) => false,
+            _ => return Ok((left, rest)),
+        };
+        let (right, rest) = self.parse_primary(&rest[1..])?;
+        Ok((Expr::Compare { left: Box::new(left), right: Box::new(right), equal }, rest))
+    }
+
+    fn parse_primary<'t>(&self, tokens: &'t [Token]) -> Result<(Expr, &'t [Token])> {
+        match tokens.first() {
+            Some(Token::Str(text)) => Ok((Expr::Literal(Value::Text(text.clone())), &tokens[1..])),
+            Some(&Token::Int(number)) => Ok((Expr::Literal(Value::Int(number)), &tokens[1..])),
+            Some(Token::Ident(name)) if tokens.get(1) == Some(&Token::Open) => {
+                let (function, arity) = match Function::from_name(name) {
+                    Some(function) => function,
+                    None => return self.error(&format!("unknown function '{}'", name)),
+                };
+                let mut args = Vec::new();
+                let mut rest = &tokens[2..];
+                if rest.first() != Some(&Token::Close) {
+                    loop {
+                        let (arg, after_arg) = self.parse_comparison(rest)?;
+                        args.push(arg);
+                        match after_arg.first() {
+                            Some(&Token::Comma) => rest = &after_arg[1..],
+                            Some(&Token::Close) => {
+                                rest = after_arg;
+                                break;
+                            }
+                            _ => return self.error("expected ',' or ')'"),
+                        }
+                    }
+                }
+                if args.len() != arity {
+                    return self.error(&format!("'{}' takes {} argument(s)", name, arity));
+                }
+                Ok((Expr::Call { function, args }, &rest[1..]))
+            }
+            Some(Token::Ident(name)) => Ok((Expr::Key(name.clone()), &tokens[1..])),
+            _ => self.error("expected an expression"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use dbus::arg::RefArg;
+    use MetadataMap;
+    use super::*;
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_Template() {
+        let mut example_map: HashMap<String, Rc<dyn RefArg>> = HashMap::new();
+        example_map.insert("mpris:trackid".to_string(), Rc::new("/foo/bar/baz".to_string()));
+        example_map.insert("mpris:length".to_string(), Rc::new(215_000_000 as ::TimeInUs));
+        example_map.insert("xesam:artist".to_string(), Rc::new(vec!["first artist".to_string(), "second artist".to_string()]));
+        example_map.insert("xesam:title".to_string(), Rc::new("example title".to_string()));
+        example_map.insert("xesam:album".to_string(), Rc::new("".to_string()));
+        let mmap = MetadataMap::from_map(example_map).unwrap();
+
+        let render = |template: &str| Template::parse(template).unwrap().render(&mmap);
+        assert_eq!(render("{{artist}} - {{title}} [{{duration(mpris:length)}}]"),
+                   "first artist, second artist - example title [3:35]");
+        assert_eq!(render("{{join(xesam:artist, \" & \")}}"), "first artist & second artist");
+        assert_eq!(render("{{trunc(title, 7)}}|{{trunc(title, 20)}}"), "example…|example title");
+        assert_eq!(render("{{default(album, \"Unknown\")}} {{default(genre, \"-\")}}"), "Unknown -");
+        assert_eq!(render("{{#if album}}on {{album}}{{else}}no album{{/if}}"), "no album");
+        assert_eq!(render("{{#if title == \"example title\"}}{{#if genre}}x{{else}}y{{/if}}{{/if}}"), "y");
+        assert_eq!(render("{{genre}}{{xesam:title}}"), "example title");
+
+        assert!(Template::parse("{{title").is_err());
+        assert!(Template::parse("{{#if title}}").is_err());
+        assert!(Template::parse("{{/if}}").is_err());
+        assert!(Template::parse("{{unknown(title)}}").is_err());
+        assert!(Template::parse("{{trunc(title)}}").is_err());
+        match Template::parse("ab{{title $}}") {
+            Err(Error(ErrorKind::InvalidTemplate(position, _), _)) => assert_eq!(position, 10),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert_eq!(format_duration(59_999_999), "0:59");
+        assert_eq!(format_duration(3_723_000_000), "1:02:03");
+    }
+}
@@ -15,20 +15,43 @@ extern crate dbus;
 extern crate chrono;
 #[macro_use]
 extern crate error_chain;
+#[cfg(feature = "async")]
+extern crate futures;
+#[cfg(feature = "async")]
+extern crate libc;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 
 
+#[cfg(feature = "async")]
+pub mod async_client;
 pub mod client;
+pub mod decode;
 pub mod errors;
+pub mod format;
+pub mod position;
+#[macro_use]
+pub mod properties;
+#[cfg(feature = "serde")]
+mod serialization;
+pub mod server;
+pub mod state;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+pub mod watcher;
 
 
 use dbus::{Path, MessageItem};
-use dbus::arg::RefArg;
+use dbus::arg::{ArgType, RefArg, Variant};
 use chrono::{DateTime, FixedOffset};
 use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 use std::rc::Rc;
+use std::time::Duration;
+use decode::{Coercion, DecodeMode, RawEntry};
 use errors::*;
-use ::dbus::arg::cast;
 
 
 /// A unique resource identifier.
@@ -50,7 +73,7 @@ type PlaybackRate = f64;
 type Volume = f64;
 
 /// Time in microseconds.
-type TimeInUs = f64;
+type TimeInUs = i64;
 
 
 /// Unique track identifier.
@@ -95,8 +118,15 @@ impl AsRef<str> for TrackId {
     }
 }
 
+impl From<TrackId> for MessageItem {
+    fn from(track_id: TrackId) -> MessageItem {
+        MessageItem::ObjectPath(Path::from(track_id.track_id))
+    }
+}
+
 /// A playback state.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PlaybackStatus {
     /// A track is currently playing.
     Playing,
@@ -120,6 +150,16 @@ impl FromStr for PlaybackStatus {
     }
 }
 
+impl AsRef<str> for PlaybackStatus {
+    fn as_ref(&self) -> &str {
+        match *self {
+            PlaybackStatus::Playing => "Playing",
+            PlaybackStatus::Paused => "Paused",
+            PlaybackStatus::Stopped => "Stopped",
+        }
+    }
+}
+
 impl Into<MessageItem> for PlaybackStatus {
     fn into(self) -> MessageItem {
         match self {
@@ -132,6 +172,7 @@ impl Into<MessageItem> for PlaybackStatus {
 
 /// A repeat / loop status
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum LoopStatus {
     /// The playback will stop when there are no more tracks to play
     None,
@@ -154,6 +195,16 @@ impl FromStr for LoopStatus {
     }
 }
 
+impl AsRef<str> for LoopStatus {
+    fn as_ref(&self) -> &str {
+        match *self {
+            LoopStatus::None => "None",
+            LoopStatus::Track => "Track",
+            LoopStatus::Playlist => "Playlist",
+        }
+    }
+}
+
 impl Into<MessageItem> for LoopStatus {
     fn into(self) -> MessageItem {
         match self {
@@ -164,46 +215,404 @@ impl Into<MessageItem> for LoopStatus {
     }
 }
 
+/// The type of ordering that a media player can use for its playlists.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaylistOrdering {
+    /// Alphabetical ordering by name, ascending.
+    Alphabetical,
+    /// Ordering by creation date, oldest first.
+    CreationDate,
+    /// Ordering by last modified date, oldest first.
+    ModifiedDate,
+    /// Ordering by date of last playback, oldest first.
+    LastPlayDate,
+    /// A user-defined ordering.
+    UserDefined,
+}
+
+impl FromStr for PlaylistOrdering {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<PlaylistOrdering> {
+        match s.to_lowercase().as_str() {
+            "alphabetical" => Ok(PlaylistOrdering::Alphabetical),
+            "created" => Ok(PlaylistOrdering::CreationDate),
+            "modified" => Ok(PlaylistOrdering::ModifiedDate),
+            "played" => Ok(PlaylistOrdering::LastPlayDate),
+            "user" => Ok(PlaylistOrdering::UserDefined),
+            _ => bail!(ErrorKind::TypeBuildError(stringify!(PlaylistOrdering), s.to_string())),
+        }
+    }
+}
+
+impl AsRef<str> for PlaylistOrdering {
+    fn as_ref(&self) -> &str {
+        match *self {
+            PlaylistOrdering::Alphabetical => "Alphabetical",
+            PlaylistOrdering::CreationDate => "Created",
+            PlaylistOrdering::ModifiedDate => "Modified",
+            PlaylistOrdering::LastPlayDate => "Played",
+            PlaylistOrdering::UserDefined => "User",
+        }
+    }
+}
+
+impl From<PlaylistOrdering> for MessageItem {
+    fn from(ordering: PlaylistOrdering) -> MessageItem {
+        match ordering {
+            PlaylistOrdering::Alphabetical => "Alphabetical".into(),
+            PlaylistOrdering::CreationDate => "Created".into(),
+            PlaylistOrdering::ModifiedDate => "Modified".into(),
+            PlaylistOrdering::LastPlayDate => "Played".into(),
+            PlaylistOrdering::UserDefined => "User".into(),
+        }
+    }
+}
+
+/// A data structure describing a playlist.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Playlist {
+    /// A unique identifier for the playlist.
+    ///
+    /// This is a valid D-Bus object path and should remain the same if the playlist is renamed.
+    pub id: String,
+    /// The name of the playlist, typically given by the user.
+    pub name: String,
+    /// The URI of an (optional) icon.
+    pub icon: Uri,
+}
+
+/// A data structure describing a playlist, or nothing.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MaybePlaylist {
+    /// Whether this structure refers to a valid playlist.
+    pub valid: bool,
+    /// The playlist, providing `valid` is `true`, otherwise undefined.
+    ///
+    /// When constructing this type, it should be noted that the playlist id must be a valid
+    /// object path, or D-Bus implementations may reject it. This is true even when `valid` is
+    /// `false`. It is suggested that "/" is used as the playlist id in this case.
+    pub playlist: Playlist,
+}
+
+impl MaybePlaylist {
+    /// Returns the playlist if it is valid.
+    pub fn into_option(self) -> Option<Playlist> {
+        if self.valid { Some(self.playlist) } else { None }
+    }
+}
+
+/// The value of a metadata entry.
+///
+/// Signed integers of any width are stored as `Int`, unsigned ones as `UInt`. Both compare equal
+/// if they hold the same number.
+#[derive(Debug, Clone)]
+pub enum MetadataValue {
+    /// A string, e.g. `xesam:title`.
+    String(String),
+    /// A list of strings, e.g. `xesam:artist`.
+    StringList(Vec<String>),
+    /// A signed integer, e.g. `mpris:length`.
+    Int(i64),
+    /// An unsigned integer.
+    UInt(u64),
+    /// A floating point number, e.g. `xesam:userRating`.
+    Double(f64),
+    /// A boolean.
+    Bool(bool),
+    /// A D-Bus object path, e.g. `mpris:trackid`.
+    ObjectPath(String),
+    /// A date, e.g. `xesam:contentCreated`.
+    DateTime(DateTime<FixedOffset>),
+    /// A list of values which are not all strings.
+    List(Vec<MetadataValue>),
+    /// A dictionary of further values.
+    Nested(HashMap<String, MetadataValue>),
+}
+
+impl MetadataValue {
+    /// Returns the value as a string slice, if it is a string or an object path.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            MetadataValue::String(ref value) | MetadataValue::ObjectPath(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as an `i64`, if it is an integer which fits into it.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            MetadataValue::Int(value) => Some(value),
+            MetadataValue::UInt(value) if value <= i64::MAX as u64 => Some(value as i64),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a `u64`, if it is an integer which fits into it.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            MetadataValue::Int(value) if value >= 0 => Some(value as u64),
+            MetadataValue::UInt(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Converts an argument read from D-Bus. Returns `None` for arguments which cannot be
+    /// represented, e.g. file descriptors.
+    pub(crate) fn from_refarg(arg: &dyn RefArg) -> Option<Self> {
+        let value = match arg.arg_type() {
+            ArgType::Boolean => MetadataValue::Bool(arg.as_i64()? == 1),
+            ArgType::Byte | ArgType::UInt16 | ArgType::UInt32 | ArgType::UInt64 => MetadataValue::UInt(arg.as_u64()?),
+            ArgType::Int16 | ArgType::Int32 | ArgType::Int64 => MetadataValue::Int(arg.as_i64()?),
+            ArgType::Double => MetadataValue::Double(arg.as_f64()?),
+            ArgType::String | ArgType::Signature => MetadataValue::String(arg.as_str()?.to_string()),
+            ArgType::ObjectPath => MetadataValue::ObjectPath(arg.as_str()?.to_string()),
+            ArgType::Variant => MetadataValue::from_refarg(arg.as_iter()?.next()?)?,
+            ArgType::Array if arg.signature().starts_with("a{") => {
+                let mut iter = arg.as_iter()?;
+                let mut values = HashMap::new();
+                while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+                    let key = match MetadataValue::from_refarg(key)? {
+                        MetadataValue::String(key) | MetadataValue::ObjectPath(key) => key,
+                        key => key.to_string(),
+                    };
+                    values.extend(MetadataValue::from_refarg(value).map(|value| (key, value)));
+                }
+                MetadataValue::Nested(values)
+            }
+            ArgType::Array if &*arg.signature() == "as" => {
+                MetadataValue::StringList(arg.as_iter()?.filter_map(|value| value.as_str()).map(String::from).collect())
+            }
+            ArgType::Array | ArgType::Struct => {
+                MetadataValue::List(arg.as_iter()?.filter_map(MetadataValue::from_refarg).collect())
+            }
+            ArgType::DictEntry | ArgType::UnixFd | ArgType::Invalid => return None,
+        };
+        Some(value)
+    }
+
+    /// Converts the value into an argument which can be sent over D-Bus. Integers are sent as
+    /// `int64` or `uint64`, and dates as strings.
+    pub(crate) fn to_refarg(&self) -> Box<dyn RefArg> {
+        match *self {
+            MetadataValue::String(ref value) => Box::new(value.clone()),
+            MetadataValue::StringList(ref values) => Box::new(values.clone()),
+            MetadataValue::Int(value) => Box::new(value),
+            MetadataValue::UInt(value) => Box::new(value),
+            MetadataValue::Double(value) => Box::new(value),
+            MetadataValue::Bool(value) => Box::new(value),
+            MetadataValue::ObjectPath(ref value) => Box::new(Path::from(value.clone())),
+            MetadataValue::DateTime(ref value) => Box::new(value.to_rfc3339()),
+            MetadataValue::List(ref values) => {
+                let values: Vec<Variant<Box<dyn RefArg>>> = values.iter().map(|value| Variant(value.to_refarg())).collect();
+                Box::new(values)
+            }
+            MetadataValue::Nested(ref values) => {
+                let values: HashMap<String, Variant<Box<dyn RefArg>>> = values.iter()
+                    .map(|(key, value)| (key.clone(), Variant(value.to_refarg())))
+                    .collect();
+                Box::new(values)
+            }
+        }
+    }
+}
+
+impl PartialEq for MetadataValue {
+    fn eq(&self, other: &MetadataValue) -> bool {
+        use MetadataValue::*;
+
+        match (self, other) {
+            (String(a), String(b)) | (ObjectPath(a), ObjectPath(b)) => a == b,
+            (StringList(a), StringList(b)) => a == b,
+            (Int(..), Int(..)) | (Int(..), UInt(..)) | (UInt(..), Int(..)) | (UInt(..), UInt(..)) => {
+                self.as_i64().is_some() && self.as_i64() == other.as_i64()
+                    || self.as_u64().is_some() && self.as_u64() == other.as_u64()
+            }
+            (Double(a), Double(b)) => a == b,
+            (Bool(a), Bool(b)) => a == b,
+            (DateTime(a), DateTime(b)) => a == b,
+            (List(a), List(b)) => a == b,
+            (Nested(a), Nested(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for MetadataValue {
+    /// Formats the value as text. The elements of lists are separated by commas.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MetadataValue::String(ref value) | MetadataValue::ObjectPath(ref value) => f.write_str(value),
+            MetadataValue::StringList(ref values) => f.write_str(&values.join(", ")),
+            MetadataValue::Int(value) => write!(f, "{}", value),
+            MetadataValue::UInt(value) => write!(f, "{}", value),
+            MetadataValue::Double(value) => write!(f, "{}", value),
+            MetadataValue::Bool(value) => write!(f, "{}", value),
+            MetadataValue::DateTime(ref value) => f.write_str(&value.to_rfc3339()),
+            MetadataValue::List(ref values) => {
+                let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                f.write_str(&values.join(", "))
+            }
+            MetadataValue::Nested(ref values) => {
+                let mut keys: Vec<&String> = values.keys().collect();
+                keys.sort();
+                let entries: Vec<String> = keys.iter().map(|key| format!("{}: {}", key, values[*key])).collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
+
 /// The metadata of a track
+///
+/// Entries which a media player sent with another type than the specification requires are
+/// converted when the metadata is decoded, see the `decode` module. The applied conversions are
+/// not compared by `==`.
 #[derive(Debug, Clone)]
 pub struct MetadataMap {
     trackid: TrackId,
-    raw_map: HashMap<String, Rc<RefArg>>,
+    values: HashMap<String, MetadataValue>,
+    coercions: Vec<Coercion>,
+}
+
+impl PartialEq for MetadataMap {
+    fn eq(&self, other: &MetadataMap) -> bool {
+        self.trackid == other.trackid && self.values == other.values
+    }
 }
 
 macro_rules! mm_getter {
     ($name:ident, u32, $map_name:expr) => {
         pub fn $name(&self) -> Option<u32> {
-            let argref: &Rc<RefArg> = self.raw_map.get($map_name)?;
-            Some(argref.as_u64()? as u32)
+            let value = self.values.get($map_name)?.as_u64()?;
+            if value <= u64::from(u32::MAX) { Some(value as u32) } else { None }
         }
     };
     ($name:ident, DateTime<FixedOffset>, $map_name:expr) => {
         pub fn $name(&self) -> Option<DateTime<FixedOffset>> {
-            let argref: &Rc<RefArg> = self.raw_map.get($map_name)?;
-            DateTime::parse_from_rfc3339(cast::<String>(argref)?).ok()
+            match *self.values.get($map_name)? {
+                MetadataValue::DateTime(value) => Some(value),
+                _ => None,
+            }
+        }
+    };
+    ($name:ident, TimeInUs, $map_name:expr) => {
+        pub fn $name(&self) -> Option<TimeInUs> {
+            self.values.get($map_name)?.as_i64()
+        }
+    };
+    ($name:ident, f64, $map_name:expr) => {
+        pub fn $name(&self) -> Option<f64> {
+            match *self.values.get($map_name)? {
+                MetadataValue::Double(value) => Some(value),
+                _ => None,
+            }
+        }
+    };
+    ($name:ident, Vec<String>, $map_name:expr) => {
+        pub fn $name(&self) -> Option<Vec<String>> {
+            match *self.values.get($map_name)? {
+                MetadataValue::StringList(ref values) => Some(values.clone()),
+                _ => None,
+            }
         }
     };
     ($name:ident, $return_type:ty, $map_name:expr) => {
         pub fn $name(&self) -> Option<$return_type> {
-            let argref: &Rc<RefArg> = self.raw_map.get($map_name)?;
-            Some(cast::<$return_type>(argref)?.to_owned())
+            match *self.values.get($map_name)? {
+                MetadataValue::String(ref value) => Some(value.clone()),
+                _ => None,
+            }
         }
     };
 }
 
 impl MetadataMap {
     /// Creates a new `MetadataMap` from a Map of names and variants.
-    pub fn from_map(raw_map: HashMap<String, Rc<RefArg>>) -> Result<Self> {
-        let trackid;
-        if let Some(track_id) = raw_map.get("mpris:trackid") {
-            let track_id_str = track_id.as_str().chain_err(|| "Could not cast to str.")?;
-            trackid = TrackId::from_str(track_id_str)?;
-        } else {
-            bail!("Mandatory 'mpris:trackid' is not present. Could not construct MetadataMap.");
-        }
+    ///
+    /// Entries with another type than the specified one are converted, and a missing or invalid
+    /// track id is replaced by `/org/mpris/MediaPlayer2/TrackList/NoTrack`.
+    pub fn from_map(raw_map: HashMap<String, Rc<dyn RefArg>>) -> Result<Self> {
+        MetadataMap::from_map_with_mode(raw_map, DecodeMode::Lenient)
+    }
+
+    /// Creates a new `MetadataMap` from a Map of names and variants, which is decoded in the
+    /// given mode.
+    ///
+    /// In the `DecodeMode::Strict` mode, an `ErrorKind::NonConformingMetadata` error is returned
+    /// for the first entry which does not have the type of the specification.
+    pub fn from_map_with_mode(raw_map: HashMap<String, Rc<dyn RefArg>>, mode: DecodeMode) -> Result<Self> {
+        let entries = raw_map.iter().filter_map(|(key, value)| RawEntry::from_refarg(key, &**value));
+        decode::decode_metadata(entries, mode)
+    }
 
-        Ok(MetadataMap { trackid, raw_map })
+    /// Creates a new `MetadataMap` from a map of names and values.
+    ///
+    /// The track id may be given as a string or as an object path. The other entries are
+    /// converted to the types of the specification where possible, e.g. the dates are parsed if
+    /// they are given as strings.
+    pub fn from_values(values: HashMap<String, MetadataValue>) -> Result<Self> {
+        match values.get("mpris:trackid") {
+            Some(track_id) => {
+                TrackId::from_str(track_id.as_str().chain_err(|| "Could not cast to str.")?)?;
+            }
+            None => bail!("Mandatory 'mpris:trackid' is not present. Could not construct MetadataMap."),
+        };
+        let entries = values.into_iter().map(|(key, value)| RawEntry { key, value, signature: None });
+        decode::decode_metadata(entries, DecodeMode::Lenient)
+    }
+
+    /// Creates a `MetadataMap` from decoded values, which contain a valid track id.
+    pub(crate) fn from_decoded(values: HashMap<String, MetadataValue>, coercions: Vec<Coercion>) -> Self {
+        let trackid = values.get("mpris:trackid")
+            .and_then(MetadataValue::as_str)
+            .and_then(|track_id| TrackId::from_str(track_id).ok())
+            .expect("The decoded metadata has no valid track id.");
+        let mut metadata = MetadataMap { trackid: trackid.clone(), values, coercions };
+        metadata.set_trackid(trackid);
+        metadata
+    }
+
+    /// Returns a builder for metadata, which is sent by a media player.
+    pub fn builder() -> MetadataMapBuilder {
+        MetadataMapBuilder { values: HashMap::new(), error: None }
+    }
+
+    /// Converts the metadata into the map of an `a{sv}` argument, in which the entries of the
+    /// specification have their specified types.
+    pub fn to_dict(&self) -> HashMap<String, Variant<Box<dyn RefArg>>> {
+        client::metadata_to_dict(self)
+    }
+
+    /// Returns the entries which did not have the type of the specification and were converted
+    /// when the metadata was decoded.
+    pub fn coercions(&self) -> &[Coercion] {
+        &self.coercions
+    }
+
+    /// Replaces the track id.
+    pub(crate) fn set_trackid(&mut self, trackid: TrackId) {
+        self.values.insert("mpris:trackid".to_string(), MetadataValue::ObjectPath(trackid.track_id.clone()));
+        self.trackid = trackid;
+    }
+
+    /// Returns the value of the entry `key`, e.g. `xesam:title`.
+    pub fn get(&self, key: &str) -> Option<&MetadataValue> {
+        self.values.get(key)
+    }
+
+    /// Returns the keys of all entries, including the ones which are not part of the
+    /// specification.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(|key| key as &str)
+    }
+
+    /// Returns all entries, including the ones which are not part of the specification, like
+    /// `spotify:*` or `vlc:*`.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &MetadataValue)> {
+        self.values.iter().map(|(key, value)| (key as &str, value))
     }
 
     // MPRIS-specific
@@ -259,10 +668,174 @@ impl MetadataMap {
 }
 
 
-impl PartialEq for MetadataMap {
-    fn eq(&self, other: &MetadataMap) -> bool {
-        self.trackid == other.trackid
+macro_rules! mmb_setter {
+    ($(#[$attr:meta])* $name:ident, String, $map_name:expr) => {
+        $(#[$attr])*
+        pub fn $name(self, value: &str) -> Self {
+            self.value($map_name, MetadataValue::String(value.to_string()))
+        }
+    };
+    ($(#[$attr:meta])* $name:ident, Vec<String>, $map_name:expr) => {
+        $(#[$attr])*
+        pub fn $name(self, values: &[&str]) -> Self {
+            self.value($map_name, MetadataValue::StringList(values.iter().map(|value| value.to_string()).collect()))
+        }
+    };
+    ($(#[$attr:meta])* $name:ident, i32, $map_name:expr) => {
+        $(#[$attr])*
+        pub fn $name(self, value: i32) -> Self {
+            self.value($map_name, MetadataValue::Int(i64::from(value)))
+        }
+    };
+    ($(#[$attr:meta])* $name:ident, f64, $map_name:expr) => {
+        $(#[$attr])*
+        pub fn $name(self, value: f64) -> Self {
+            self.value($map_name, MetadataValue::Double(value))
+        }
+    };
+    ($(#[$attr:meta])* $name:ident, DateTime<FixedOffset>, $map_name:expr) => {
+        $(#[$attr])*
+        pub fn $name(self, date: &str) -> Self {
+            match decode::parse_date(date) {
+                Some(date) => self.value($map_name, MetadataValue::DateTime(date)),
+                None => self.fail(ErrorKind::TypeBuildError("DateTime", date.to_string()).into()),
+            }
+        }
+    };
+}
+
+/// Builds the metadata of a track, e.g. for the tracks of a media player.
+///
+/// Each entry of the specification has a method with the name of its getter in `MetadataMap`.
+/// The track id and the dates are checked when they are set, and `build` returns the first
+/// invalid value as an error.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let metadata = mpris::MetadataMap::builder()
+///     .trackid("/org/example/Track/1")
+///     .title("Example title")
+///     .artist(&["First artist", "Second artist"])
+///     .length(Duration::from_secs(215))
+///     .content_created("2007-04-29T14:35:51+02:00")
+///     .build()
+///     .unwrap();
+/// assert_eq!(metadata.length(), Some(215_000_000));
+/// ```
+#[derive(Debug)]
+pub struct MetadataMapBuilder {
+    values: HashMap<String, MetadataValue>,
+    error: Option<Error>,
+}
+
+impl MetadataMapBuilder {
+    /// Sets the entry `key`, which may also be an entry which is not part of the specification,
+    /// like `vlc:*`. Entries of the specification are converted to their specified type, if
+    /// necessary.
+    pub fn value(mut self, key: &str, value: MetadataValue) -> Self {
+        self.values.insert(key.to_string(), value);
+        self
+    }
+
+    /// Keeps the first error, which is returned by `build`.
+    fn fail(mut self, error: Error) -> Self {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+        self
     }
+
+    /// Builds the metadata. Fails if no track id was set, or if a value was invalid.
+    pub fn build(self) -> Result<MetadataMap> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        MetadataMap::from_values(self.values)
+    }
+
+    // MPRIS-specific
+    /// Sets the track id, which must be a valid D-Bus object path.
+    pub fn trackid(self, trackid: &str) -> Self {
+        match TrackId::from_str(trackid) {
+            Ok(trackid) => self.value("mpris:trackid", MetadataValue::ObjectPath(trackid.track_id)),
+            Err(error) => self.fail(error),
+        }
+    }
+    /// Sets the duration of the track, which is sent in microseconds.
+    pub fn length(self, length: Duration) -> Self {
+        let micros = length.as_secs().checked_mul(1_000_000)
+            .and_then(|micros| micros.checked_add(u64::from(length.subsec_micros())))
+            .filter(|&micros| micros <= i64::MAX as u64);
+        match micros {
+            Some(micros) => self.value("mpris:length", MetadataValue::Int(micros as i64)),
+            None => self.fail(ErrorKind::TypeBuildError("TimeInUs", format!("{:?}", length)).into()),
+        }
+    }
+    mmb_setter!(
+        /// Sets the location of an image representing the track or album.
+        art_url, String, "mpris:artUrl");
+
+    // Common Xesam properties
+    mmb_setter!(
+        /// Sets the album name.
+        album, String, "xesam:album");
+    mmb_setter!(
+        /// Sets the album artist(s).
+        album_artist, Vec<String>, "xesam:albumArtist");
+    mmb_setter!(
+        /// Sets the track artist(s).
+        artist, Vec<String>, "xesam:artist");
+    mmb_setter!(
+        /// Sets the track lyrics.
+        as_text, String, "xesam:asText");
+    mmb_setter!(
+        /// Sets the speed of the music, in beats per minute.
+        audio_bpm, i32, "xesam:audioBPM");
+    mmb_setter!(
+        /// Sets an automatically-generated rating, based on things such as how often it has been
+        /// played. This should be in the range 0.0 to 1.0.
+        auto_rating, f64, "xesam:autoRating");
+    mmb_setter!(
+        /// Sets a (list of) freeform comment(s).
+        comment, Vec<String>, "xesam:comment");
+    mmb_setter!(
+        /// Sets the composer(s) of the track.
+        composer, Vec<String>, "xesam:composer");
+    mmb_setter!(
+        /// Sets when the track was created, in the ISO 8601 format. Usually only the year component
+        /// will be useful.
+        content_created, DateTime<FixedOffset>, "xesam:contentCreated");
+    mmb_setter!(
+        /// Sets the disc number on the album that this track is from.
+        disc_number, i32, "xesam:discNumber");
+    mmb_setter!(
+        /// Sets when the track was first played, in the ISO 8601 format.
+        first_used, DateTime<FixedOffset>, "xesam:firstUsed");
+    mmb_setter!(
+        /// Sets the genre(s) of the track.
+        genre, Vec<String>, "xesam:genre");
+    mmb_setter!(
+        /// Sets when the track was last played, in the ISO 8601 format.
+        last_used, DateTime<FixedOffset>, "xesam:lastUsed");
+    mmb_setter!(
+        /// Sets the lyricist(s) of the track.
+        lyricist, Vec<String>, "xesam:lyricist");
+    mmb_setter!(
+        /// Sets the track title.
+        title, String, "xesam:title");
+    mmb_setter!(
+        /// Sets the track number on the album disc.
+        track_number, i32, "xesam:trackNumber");
+    mmb_setter!(
+        /// Sets the location of the media file.
+        url, String, "xesam:url");
+    mmb_setter!(
+        /// Sets the number of times the track has been played.
+        user_count, i32, "xesam:userCount");
+    mmb_setter!(
+        /// Sets a user-specified rating. This should be in the range 0.0 to 1.0.
+        user_rating, f64, "xesam:userRating");
 }
 
 
@@ -274,10 +847,24 @@ mod test {
     use std::str::FromStr;
     use super::*;
 
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_PlaylistOrdering() {
+        assert_eq!(PlaylistOrdering::from_str("Alphabetical").unwrap(), PlaylistOrdering::Alphabetical);
+        assert_eq!(PlaylistOrdering::from_str("Created").unwrap(), PlaylistOrdering::CreationDate);
+        assert_eq!(PlaylistOrdering::from_str("Modified").unwrap(), PlaylistOrdering::ModifiedDate);
+        assert_eq!(PlaylistOrdering::from_str("Played").unwrap(), PlaylistOrdering::LastPlayDate);
+        assert_eq!(PlaylistOrdering::from_str("User").unwrap(), PlaylistOrdering::UserDefined);
+        assert!(PlaylistOrdering::from_str("Random").is_err());
+
+        let item: MessageItem = PlaylistOrdering::LastPlayDate.into();
+        assert_eq!(item, MessageItem::Str("Played".to_string()));
+    }
+
     #[test]
     #[allow(non_snake_case)]
     fn test_MetadataMap() {
-        let mut example_map: HashMap<String, Rc<RefArg>> = HashMap::with_capacity(22);
+        let mut example_map: HashMap<String, Rc<dyn RefArg>> = HashMap::with_capacity(22);
         example_map.insert("mpris:trackid".to_string(), Rc::new("/foo/bar/baz".to_string()));
         example_map.insert("mpris:length".to_string(), Rc::new(23 as super::TimeInUs));
         example_map.insert("mpris:artUrl".to_string(), Rc::new("/example/dir/art.png".to_string()));
@@ -326,4 +913,150 @@ mod test {
         assert_eq!(mmap.user_count(), Some(42));
         assert_eq!(mmap.user_rating(), Some(0.31415));
     }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_MetadataValue() {
+        let mut vendor_map: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+        vendor_map.insert("bitrate".to_string(), Variant(Box::new(320u32)));
+        let mut example_map: HashMap<String, Rc<dyn RefArg>> = HashMap::new();
+        example_map.insert("mpris:trackid".to_string(), Rc::new("/foo/bar/baz".to_string()));
+        example_map.insert("xesam:title".to_string(), Rc::new("example title".to_string()));
+        example_map.insert("xesam:trackNumber".to_string(), Rc::new(23i32));
+        example_map.insert("xesam:lastUsed".to_string(), Rc::new("2009-04-29T14:35:51+02:00".to_string()));
+        example_map.insert("spotify:explicit".to_string(), Rc::new(true));
+        example_map.insert("vlc:stream".to_string(), Rc::new(vendor_map));
+        let mmap = MetadataMap::from_map(example_map).unwrap();
+
+        let mut entries: Vec<(&str, &MetadataValue)> = mmap.iter().collect();
+        entries.sort_by_key(|&(key, _)| key);
+        let date = DateTime::parse_from_rfc3339("2009-04-29T14:35:51+02:00").unwrap();
+        let mut stream = HashMap::new();
+        stream.insert("bitrate".to_string(), MetadataValue::UInt(320));
+        assert_eq!(entries, vec![
+            ("mpris:trackid", &MetadataValue::ObjectPath("/foo/bar/baz".to_string())),
+            ("spotify:explicit", &MetadataValue::Bool(true)),
+            ("vlc:stream", &MetadataValue::Nested(stream)),
+            ("xesam:lastUsed", &MetadataValue::DateTime(date)),
+            ("xesam:title", &MetadataValue::String("example title".to_string())),
+            ("xesam:trackNumber", &MetadataValue::Int(23)),
+        ]);
+        assert_eq!(mmap.track_number(), Some(23));
+        assert_eq!(mmap.last_used(), Some(date));
+        assert_eq!(MetadataValue::Int(23), MetadataValue::UInt(23));
+        assert_ne!(MetadataValue::Int(-1), MetadataValue::UInt(u64::MAX));
+
+        // the metadata is sent with the types of the specification
+        let dict = client::metadata_to_dict(&mmap);
+        assert_eq!(&*dict["mpris:trackid"].0.signature(), "o");
+        assert_eq!(&*dict["xesam:trackNumber"].0.signature(), "i");
+        assert_eq!(&*dict["xesam:lastUsed"].0.signature(), "s");
+
+        // all entries are compared, not only the track id
+        let mut other_map: HashMap<String, Rc<dyn RefArg>> = HashMap::new();
+        other_map.insert("mpris:trackid".to_string(), Rc::new(Path::from("/foo/bar/baz")));
+        let other = MetadataMap::from_map(other_map).unwrap();
+        assert_eq!(other.trackid(), mmap.trackid());
+        assert_ne!(other, mmap);
+        let cloned = ::std::thread::spawn(move || other).join().unwrap();
+        assert_eq!(cloned.trackid(), mmap.trackid());
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_Coercion() {
+        let mut example_map: HashMap<String, Rc<dyn RefArg>> = HashMap::new();
+        example_map.insert("mpris:trackid".to_string(), Rc::new("/foo/bar/baz".to_string()));
+        example_map.insert("mpris:length".to_string(), Rc::new(23u64));
+        example_map.insert("xesam:artist".to_string(), Rc::new("example artist".to_string()));
+        example_map.insert("xesam:trackNumber".to_string(), Rc::new(Variant(Box::new(7i64) as Box<dyn RefArg>)));
+        example_map.insert("xesam:contentCreated".to_string(), Rc::new("2007".to_string()));
+        example_map.insert("xesam:userRating".to_string(), Rc::new(1u8));
+        let mmap = MetadataMap::from_map(example_map.clone()).unwrap();
+
+        assert_eq!(mmap.trackid(), &TrackId::from_str("/foo/bar/baz").unwrap());
+        assert_eq!(mmap.length(), Some(23));
+        assert_eq!(mmap.artist(), Some(vec!["example artist".to_string()]));
+        assert_eq!(mmap.track_number(), Some(7));
+        assert_eq!(mmap.content_created(), Some(DateTime::parse_from_rfc3339("2007-01-01T00:00:00+00:00").unwrap()));
+        assert_eq!(mmap.user_rating(), Some(1.0));
+        let mut coercions: Vec<(&str, &str, &str)> = mmap.coercions().iter()
+            .map(|coercion| (&coercion.key as &str, &coercion.found as &str, coercion.expected))
+            .collect();
+        coercions.sort();
+        assert_eq!(coercions, vec![
+            ("mpris:length", "t", "x"),
+            ("mpris:trackid", "s", "o"),
+            ("xesam:artist", "s", "as"),
+            ("xesam:trackNumber", "x", "i"),
+            ("xesam:userRating", "y", "d"),
+        ]);
+
+        // converted metadata is not converted again, and the conversions are not compared
+        let values = mmap.iter().map(|(key, value)| (key.to_string(), value.clone())).collect();
+        let converted = MetadataMap::from_values(values).unwrap();
+        assert!(converted.coercions().is_empty());
+        assert_eq!(converted, mmap);
+
+        let error = MetadataMap::from_map_with_mode(example_map, DecodeMode::Strict).unwrap_err();
+        assert!(matches!(*error.kind(), ErrorKind::NonConformingMetadata(..)));
+
+        // dates in the shorter ISO 8601 forms conform to the specification
+        let mut example_map: HashMap<String, Rc<dyn RefArg>> = HashMap::new();
+        example_map.insert("mpris:trackid".to_string(), Rc::new(Path::from("/foo/bar/baz")));
+        example_map.insert("xesam:contentCreated".to_string(), Rc::new("2007-04-29".to_string()));
+        example_map.insert("xesam:firstUsed".to_string(), Rc::new("2007".to_string()));
+        let mmap = MetadataMap::from_map_with_mode(example_map, DecodeMode::Strict).unwrap();
+        assert_eq!(mmap.content_created(), Some(DateTime::parse_from_rfc3339("2007-04-29T00:00:00+00:00").unwrap()));
+        assert_eq!(mmap.first_used(), Some(DateTime::parse_from_rfc3339("2007-01-01T00:00:00+00:00").unwrap()));
+        assert!(mmap.coercions().is_empty());
+
+        // a missing track id is replaced
+        let mut example_map: HashMap<String, Rc<dyn RefArg>> = HashMap::new();
+        example_map.insert("xesam:title".to_string(), Rc::new("example title".to_string()));
+        let mmap = MetadataMap::from_map(example_map.clone()).unwrap();
+        assert_eq!(mmap.trackid(), &TrackId::from_str("/org/mpris/MediaPlayer2/TrackList/NoTrack").unwrap());
+        assert_eq!(mmap.coercions()[0].found, "");
+        assert!(MetadataMap::from_map_with_mode(example_map, DecodeMode::Strict).is_err());
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_MetadataMapBuilder() {
+        let mmap = MetadataMap::builder()
+            .trackid("/foo/bar/baz")
+            .length(Duration::new(3, 500_000_000))
+            .album_artist(&["first", "second"])
+            .title("example title")
+            .disc_number(2)
+            .user_rating(0.5)
+            .last_used("2009-04-29")
+            .value("vlc:explicit", MetadataValue::Bool(true))
+            .build()
+            .unwrap();
+        assert_eq!(mmap.trackid(), &TrackId::from_str("/foo/bar/baz").unwrap());
+        assert_eq!(mmap.length(), Some(3_500_000));
+        assert_eq!(mmap.album_artist(), Some(vec!["first".to_string(), "second".to_string()]));
+        assert_eq!(mmap.title(), Some("example title".to_string()));
+        assert_eq!(mmap.disc_number(), Some(2));
+        assert_eq!(mmap.user_rating(), Some(0.5));
+        assert_eq!(mmap.last_used(), Some(DateTime::parse_from_rfc3339("2009-04-29T00:00:00+00:00").unwrap()));
+        assert_eq!(mmap.get("vlc:explicit"), Some(&MetadataValue::Bool(true)));
+        assert!(mmap.coercions().is_empty());
+
+        let dict = mmap.to_dict();
+        assert_eq!(&*dict["mpris:trackid"].0.signature(), "o");
+        assert_eq!(&*dict["mpris:length"].0.signature(), "x");
+        assert_eq!(&*dict["xesam:albumArtist"].0.signature(), "as");
+        assert_eq!(&*dict["xesam:discNumber"].0.signature(), "i");
+        assert_eq!(&*dict["xesam:userRating"].0.signature(), "d");
+        assert_eq!(&*dict["xesam:lastUsed"].0.signature(), "s");
+
+        // the first invalid value is returned by `build`
+        let error = MetadataMap::builder().trackid("foo bar").content_created("yesterday").build().unwrap_err();
+        assert!(matches!(*error.kind(), ErrorKind::TypeBuildError("TrackId", _)));
+        let error = MetadataMap::builder().trackid("/foo").first_used("yesterday").build().unwrap_err();
+        assert!(matches!(*error.kind(), ErrorKind::TypeBuildError("DateTime", _)));
+        assert!(MetadataMap::builder().title("no track id").build().is_err());
+    }
 }
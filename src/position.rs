@@ -0,0 +1,199 @@
+//! This module contains the estimation of the playback position.
+//!
+//! Media players do not announce the progress of the playback. Instead, clients are expected to
+//! extrapolate the position from the last known one, the `Rate` and the `PlaybackStatus`, and to
+//! listen for `Seeked` signals, which indicate that the position changed in an unexpected way.
+//! `PositionTracker` implements these rules.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use client::{ChangedProperty, MprisClient, MprisSignal};
+use errors::*;
+
+
+/// A source of the current time, which is used by `PositionTracker`.
+pub trait Clock {
+    /// Returns the current instant. Successive calls must never return an earlier instant.
+    fn now(&self) -> Instant;
+}
+
+/// The monotonic clock of the operating system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock which only moves forward when it is advanced, e.g. in tests.
+///
+/// Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    /// Creates a new `ManualClock`, which starts at the current instant.
+    pub fn new() -> Self {
+        ManualClock { now: Arc::new(Mutex::new(Instant::now())) }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|err| err.into_inner());
+        *now += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Estimates the playback position of a media player.
+///
+/// The position is read once and then extrapolated: while playing, it progresses according to the
+/// playback rate, while paused, it remains constant, and while stopped, it is 0. The estimation is
+/// corrected by the signals passed to `update`.
+#[derive(Debug, Clone)]
+pub struct PositionTracker<C: Clock = MonotonicClock> {
+    clock: C,
+    /// The position in microseconds at `reference`.
+    position: i64,
+    reference: Instant,
+    rate: ::PlaybackRate,
+    playback_status: ::PlaybackStatus,
+    track_id: Option<::TrackId>,
+    /// The length of the current track in microseconds, if it is known.
+    length: Option<i64>,
+}
+
+impl PositionTracker<MonotonicClock> {
+    /// Creates a new `PositionTracker` for the media player of `client`, which uses the monotonic
+    /// clock of the operating system.
+    pub fn new(client: &MprisClient) -> Result<Self> {
+        PositionTracker::with_clock(client, MonotonicClock)
+    }
+}
+
+impl<C: Clock> PositionTracker<C> {
+    /// Creates a new `PositionTracker` for the media player of `client`, which uses `clock`.
+    ///
+    /// The properties of the media player are read with a single `GetAll` call.
+    pub fn with_clock(client: &MprisClient, clock: C) -> Result<Self> {
+        let reference = clock.now();
+        let mut tracker = PositionTracker {
+            clock,
+            position: 0,
+            reference,
+            rate: 1.0,
+            playback_status: ::PlaybackStatus::Stopped,
+            track_id: None,
+            length: None,
+        };
+        let properties = client.player.get_all()?;
+        if let Some(rate) = properties.rate {
+            tracker.rate = rate;
+        }
+        if let Some(playback_status) = properties.playback_status {
+            tracker.playback_status = playback_status;
+        }
+        if let Some(metadata) = properties.metadata {
+            tracker.apply(ChangedProperty::Metadata(metadata));
+        }
+        tracker.set_position(properties.position.unwrap_or(0));
+        Ok(tracker)
+    }
+
+    /// Returns the estimated position in microseconds at the current instant.
+    pub fn position(&self) -> i64 {
+        self.position_at(self.clock.now())
+    }
+
+    /// Returns the last known playback status.
+    pub fn playback_status(&self) -> ::PlaybackStatus {
+        self.playback_status
+    }
+
+    /// Stores a position which was just read from the media player.
+    pub fn set_position(&mut self, position: i64) {
+        self.position = position;
+        self.reference = self.clock.now();
+    }
+
+    /// Updates the estimation from a signal of the media player.
+    ///
+    /// `Seeked` sets the position, while `PropertiesChanged` updates the playback rate, the
+    /// playback status and the current track. When the track changes, the position starts at 0.
+    pub fn update(&mut self, signal: &MprisSignal) {
+        match *signal {
+            MprisSignal::Seeked { position } => self.set_position(position),
+            MprisSignal::PropertiesChanged { ref interface, ref changed_properties, .. }
+                if interface == "org.mpris.MediaPlayer2.Player" => {
+                for property in changed_properties {
+                    self.apply(property.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Applies the new value of a property of the `org.mpris.MediaPlayer2.Player` interface.
+    fn apply(&mut self, property: ChangedProperty) {
+        match property {
+            ChangedProperty::Position(position) => self.set_position(position),
+            ChangedProperty::Rate(rate) => {
+                self.anchor();
+                self.rate = rate;
+            }
+            ChangedProperty::PlaybackStatus(playback_status) => {
+                self.anchor();
+                self.playback_status = playback_status;
+            }
+            ChangedProperty::Metadata(metadata) => {
+                let track_id = Some(metadata.trackid().clone());
+                if self.track_id.is_some() && self.track_id != track_id {
+                    self.set_position(0);
+                }
+                self.track_id = track_id;
+                self.length = metadata.length();
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves the reference point of the estimation to the current instant.
+    fn anchor(&mut self) {
+        let now = self.clock.now();
+        self.position = self.position_at(now);
+        self.reference = now;
+    }
+
+    /// Returns the estimated position in microseconds at `instant`.
+    fn position_at(&self, instant: Instant) -> i64 {
+        match self.playback_status {
+            ::PlaybackStatus::Stopped => return 0,
+            ::PlaybackStatus::Paused => return self.position,
+            ::PlaybackStatus::Playing => {}
+        }
+
+        let elapsed = instant.saturating_duration_since(self.reference);
+        let elapsed_us = elapsed.as_secs() as f64 * 1_000_000.0 + f64::from(elapsed.subsec_micros());
+        let position = (self.position as f64 + elapsed_us * self.rate).max(0.0) as i64;
+        match self.length {
+            Some(length) if length > 0 => position.min(length),
+            _ => position,
+        }
+    }
+}
+
@@ -0,0 +1,255 @@
+//! This module contains typed snapshots of the properties of the MPRIS interfaces.
+//!
+//! `MprisRoot::get_all`, `MprisPlayer::get_all`, `MprisTrackList::get_all` and
+//! `MprisPlaylists::get_all` read all properties of an interface with a single
+//! `org.freedesktop.DBus.Properties.GetAll` call, instead of one `Get` call per property.
+
+/// Defines a struct with one optional field for each of the listed properties, and the methods
+/// which fill the fields from the values read from D-Bus.
+///
+/// Properties which are unknown, or whose value could not be converted, are kept in the `other`
+/// field of the struct.
+macro_rules! properties_struct {
+    ($(#[$struct_attr:meta])* $name:ident {
+        $($(#[$attr:meta])* $field:ident: $member:ident($field_type:ty),)*
+    }) => {
+        $(#[$struct_attr])*
+        #[derive(Debug, Clone, PartialEq, Default)]
+        pub struct $name {
+            $($(#[$attr])* pub $field: Option<$field_type>,)*
+            /// The properties which are not part of the MPRIS specification, or whose value could
+            /// not be converted.
+            pub other: ::std::collections::HashMap<String, ::MetadataValue>,
+        }
+
+        impl $name {
+            /// Stores the new value of a property.
+            ///
+            /// Returns `false` if the property does not belong to this struct.
+            pub fn apply(&mut self, property: ::client::ChangedProperty) -> bool {
+                match property {
+                    $(::client::ChangedProperty::$member(value) => self.$field = Some(value),)*
+                    _ => return false,
+                }
+                true
+            }
+
+            /// Stores the value of the property `name`, as it was read from D-Bus. Metadata is
+            /// decoded in the `mode`.
+            pub(crate) fn insert(&mut self,
+                                 name: &str,
+                                 mut value: ::dbus::arg::Variant<Box<dyn (::dbus::arg::RefArg)>>,
+                                 mode: ::decode::DecodeMode) {
+                let stored = match ::client::ChangedProperty::from_variant(name, &mut value, mode) {
+                    Ok(property) => self.apply(property),
+                    Err(_) => false,
+                };
+                if !stored {
+                    self.clear(name);
+                    if let Some(value) = ::MetadataValue::from_refarg(&*value.0) {
+                        self.other.insert(name.to_string(), value);
+                    }
+                }
+            }
+
+            /// Forgets the value of the property `name`.
+            pub(crate) fn clear(&mut self, name: &str) {
+                match name {
+                    $(stringify!($member) => self.$field = None,)*
+                    _ => {}
+                }
+                self.other.remove(name);
+            }
+
+            /// Builds the struct from the properties returned by `GetAll`. Metadata is decoded in
+            /// the `mode`.
+            pub(crate) fn from_props(props: ::client::PropMap, mode: ::decode::DecodeMode) -> Self {
+                let mut properties = $name::default();
+                for (name, value) in props {
+                    properties.insert(&name, value, mode);
+                }
+                properties
+            }
+
+            /// Reads the properties `names` again with `fetch`, and decodes their metadata in the
+            /// `mode`.
+            ///
+            /// A property is cleared if `fetch` fails or if the media player does not provide it.
+            /// The remaining properties are still read after a failure, and the first error is
+            /// returned.
+            // only the structs which make up a `PlayerState` are reloaded
+            #[allow(dead_code)]
+            pub(crate) fn reload<F>(&mut self,
+                                    names: &[String],
+                                    mode: ::decode::DecodeMode,
+                                    mut fetch: F)
+                                    -> ::errors::Result<()>
+                where F: FnMut(&str) -> ::errors::Result<Option<::dbus::arg::Variant<Box<dyn (::dbus::arg::RefArg)>>>>
+            {
+                let mut first_error = None;
+                for name in names {
+                    match fetch(name) {
+                        Ok(Some(value)) => self.insert(name, value, mode),
+                        Ok(None) => self.clear(name),
+                        Err(e) => {
+                            self.clear(name);
+                            if first_error.is_none() {
+                                first_error = Some(e);
+                            }
+                        }
+                    }
+                }
+                match first_error {
+                    Some(e) => Err(e),
+                    None => Ok(()),
+                }
+            }
+        }
+    };
+}
+
+properties_struct! {
+    /// The properties of the `org.mpris.MediaPlayer2` interface.
+    ///
+    /// A field is `None` if the media player does not provide the property.
+    RootProperties {
+        /// See `MprisRoot::can_quit`.
+        can_quit: CanQuit(bool),
+        /// See `MprisRoot::fullscreen`.
+        fullscreen: Fullscreen(bool),
+        /// See `MprisRoot::can_set_fullscreen`.
+        can_set_fullscreen: CanSetFullscreen(bool),
+        /// See `MprisRoot::can_raise`.
+        can_raise: CanRaise(bool),
+        /// See `MprisRoot::has_track_list`.
+        has_track_list: HasTrackList(bool),
+        /// See `MprisRoot::identity`.
+        identity: Identity(String),
+        /// See `MprisRoot::desktop_entry`.
+        desktop_entry: DesktopEntry(String),
+        /// See `MprisRoot::supported_uri_schemes`.
+        supported_uri_schemes: SupportedUriSchemes(Vec<String>),
+        /// See `MprisRoot::supported_mime_types`.
+        supported_mime_types: SupportedMimeTypes(Vec<String>),
+    }
+}
+
+properties_struct! {
+    /// The properties of the `org.mpris.MediaPlayer2.Player` interface.
+    ///
+    /// A field is `None` if the media player does not provide the property.
+    PlayerProperties {
+        /// See `MprisPlayer::playback_status`.
+        playback_status: PlaybackStatus(::PlaybackStatus),
+        /// See `MprisPlayer::loop_status`.
+        loop_status: LoopStatus(::LoopStatus),
+        /// See `MprisPlayer::rate`.
+        rate: Rate(::PlaybackRate),
+        /// See `MprisPlayer::shuffle`.
+        shuffle: Shuffle(bool),
+        /// See `MprisPlayer::metadata`.
+        metadata: Metadata(::MetadataMap),
+        /// See `MprisPlayer::volume`.
+        volume: Volume(::Volume),
+        /// See `MprisPlayer::position`.
+        position: Position(i64),
+        /// See `MprisPlayer::minimum_rate`.
+        minimum_rate: MinimumRate(::PlaybackRate),
+        /// See `MprisPlayer::maximum_rate`.
+        maximum_rate: MaximumRate(::PlaybackRate),
+        /// See `MprisPlayer::can_go_next`.
+        can_go_next: CanGoNext(bool),
+        /// See `MprisPlayer::can_go_previous`.
+        can_go_previous: CanGoPrevious(bool),
+        /// See `MprisPlayer::can_play`.
+        can_play: CanPlay(bool),
+        /// See `MprisPlayer::can_pause`.
+        can_pause: CanPause(bool),
+        /// See `MprisPlayer::can_seek`.
+        can_seek: CanSeek(bool),
+        /// See `MprisPlayer::can_control`.
+        can_control: CanControl(bool),
+    }
+}
+
+properties_struct! {
+    /// The properties of the `org.mpris.MediaPlayer2.TrackList` interface.
+    ///
+    /// A field is `None` if the media player does not provide the property.
+    TrackListProperties {
+        /// See `MprisTrackList::tracks`.
+        tracks: Tracks(Vec<::TrackId>),
+        /// See `MprisTrackList::can_edit_tracks`.
+        can_edit_tracks: CanEditTracks(bool),
+    }
+}
+
+properties_struct! {
+    /// The properties of the `org.mpris.MediaPlayer2.Playlists` interface.
+    ///
+    /// A field is `None` if the media player does not provide the property.
+    PlaylistsProperties {
+        /// See `MprisPlaylists::playlist_count`.
+        playlist_count: PlaylistCount(u32),
+        /// See `MprisPlaylists::orderings`.
+        orderings: Orderings(Vec<::PlaylistOrdering>),
+        /// See `MprisPlaylists::active_playlist`.
+        active_playlist: ActivePlaylist(::MaybePlaylist),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use dbus::arg::{RefArg, Variant};
+    use super::RootProperties;
+    use MetadataValue;
+    use decode::DecodeMode;
+    use errors::*;
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_RootProperties() {
+        let mut props: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+        props.insert("Identity".to_string(), Variant(Box::new("VLC media player".to_string())));
+        props.insert("CanQuit".to_string(), Variant(Box::new("not a bool".to_string())));
+        props.insert("X-Vendor".to_string(), Variant(Box::new(42i32)));
+
+        let root = RootProperties::from_props(props, DecodeMode::Lenient);
+        assert_eq!(root.identity, Some("VLC media player".to_string()));
+        assert_eq!(root.can_quit, None);
+        assert_eq!(root.other.get("CanQuit"), Some(&MetadataValue::String("not a bool".to_string())));
+        assert_eq!(root.other.get("X-Vendor"), Some(&MetadataValue::Int(42)));
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_RootProperties_reload() {
+        let mut root = RootProperties {
+            identity: Some("Old".to_string()),
+            can_raise: Some(false),
+            ..RootProperties::default()
+        };
+        let names = vec!["Identity".to_string(), "CanRaise".to_string()];
+        let mut fetched = Vec::new();
+
+        let result = root.reload(&names, DecodeMode::Lenient, |name| {
+            fetched.push(name.to_string());
+            match name {
+                "Identity" => bail!(ErrorKind::NoReply("mock".to_string(),
+                                                       "org.freedesktop.DBus.Properties".to_string(),
+                                                       "Get".to_string())),
+                _ => Ok(Some(Variant(Box::new(true) as Box<dyn RefArg>))),
+            }
+        });
+
+        // The second property is read although the first one failed.
+        assert_eq!(fetched, names);
+        match result {
+            Err(Error(ErrorKind::NoReply(..), _)) => {}
+            other => panic!("expected NoReply, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(root.identity, None);
+        assert_eq!(root.can_raise, Some(true));
+    }
+}
@@ -0,0 +1,160 @@
+//! This module contains the `serde` implementations of the types which are not derived.
+//!
+//! Track ids and playlist orderings are serialized as their D-Bus strings. Metadata is serialized
+//! as a map of all its entries, with the values as their closest counterparts: lists become
+//! sequences, nested dictionaries become maps, and object paths and dates become strings.
+//!
+//! When metadata is deserialized, negative integers become `MetadataValue::Int` and other integers
+//! `MetadataValue::UInt`, which compare equal to each other. Sequences of strings become
+//! `StringList`s. The track id becomes an object path and the dates of the specification are
+//! parsed again. Entries whose value is `null` are skipped.
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use MetadataValue;
+
+
+impl Serialize for ::TrackId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_ref())
+    }
+}
+
+impl<'de> Deserialize<'de> for ::TrackId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let track_id = String::deserialize(deserializer)?;
+        ::TrackId::from_str(&track_id).map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for ::PlaylistOrdering {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_ref())
+    }
+}
+
+impl<'de> Deserialize<'de> for ::PlaylistOrdering {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ordering = String::deserialize(deserializer)?;
+        ::PlaylistOrdering::from_str(&ordering).map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for ::MetadataMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_sorted(self.iter(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ::MetadataMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries: HashMap<String, Option<MetadataValue>> = HashMap::deserialize(deserializer)?;
+        let values = entries.into_iter()
+            .filter_map(|(key, value)| Some((key, value?)))
+            .collect();
+        ::MetadataMap::from_values(values).map_err(de::Error::custom)
+    }
+}
+
+/// Serializes the entries of a map sorted by their keys, so that the same metadata is always
+/// serialized the same way.
+fn serialize_sorted<'a, I, S>(entries: I, serializer: S) -> Result<S::Ok, S::Error>
+    where I: Iterator<Item = (&'a str, &'a MetadataValue)>,
+          S: Serializer
+{
+    let mut entries: Vec<(&str, &MetadataValue)> = entries.collect();
+    entries.sort_by_key(|&(key, _)| key);
+    let mut map = serializer.serialize_map(Some(entries.len()))?;
+    for (key, value) in entries {
+        map.serialize_entry(key, value)?;
+    }
+    map.end()
+}
+
+impl Serialize for MetadataValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            MetadataValue::String(ref value) | MetadataValue::ObjectPath(ref value) => serializer.serialize_str(value),
+            MetadataValue::StringList(ref values) => values.serialize(serializer),
+            MetadataValue::Int(value) => serializer.serialize_i64(value),
+            MetadataValue::UInt(value) => serializer.serialize_u64(value),
+            MetadataValue::Double(value) => serializer.serialize_f64(value),
+            MetadataValue::Bool(value) => serializer.serialize_bool(value),
+            MetadataValue::DateTime(ref value) => serializer.serialize_str(&value.to_rfc3339()),
+            MetadataValue::List(ref values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            MetadataValue::Nested(ref values) => {
+                serialize_sorted(values.iter().map(|(key, value)| (key as &str, value)), serializer)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MetadataValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(MetadataValueVisitor)
+    }
+}
+
+struct MetadataValueVisitor;
+
+impl<'de> Visitor<'de> for MetadataValueVisitor {
+    type Value = MetadataValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a metadata value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(MetadataValue::Bool(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        if value >= 0 {
+            Ok(MetadataValue::UInt(value as u64))
+        } else {
+            Ok(MetadataValue::Int(value))
+        }
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(MetadataValue::UInt(value))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(MetadataValue::Double(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(MetadataValue::String(value.to_string()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        if values.iter().all(|value| matches!(*value, MetadataValue::String(..))) {
+            Ok(MetadataValue::StringList(values.iter().filter_map(MetadataValue::as_str).map(String::from).collect()))
+        } else {
+            Ok(MetadataValue::List(values))
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut values = HashMap::new();
+        while let Some((key, value)) = map.next_entry::<String, Option<MetadataValue>>()? {
+            values.extend(value.map(|value| (key, value)));
+        }
+        Ok(MetadataValue::Nested(values))
+    }
+}
@@ -0,0 +1,1721 @@
+//! This module contains the server side of MPRIS, which exports a media player on the D-Bus.
+//!
+//! A media player implements `RootHandler` and `PlayerHandler` and hands itself over to an
+//! `MprisServer`. The server owns the bus name `org.mpris.MediaPlayer2.playerName`, answers
+//! method calls, property requests (`Get`, `GetAll` and `Set`) and `Introspect`, and forwards
+//! everything else to the handler.
+//!
+//! The `org.mpris.MediaPlayer2.TrackList` and `org.mpris.MediaPlayer2.Playlists` interfaces are
+//! optional. They are implemented by `TrackList` and `Playlists`, which keep their entries in a
+//! `TrackStore` or `PlaylistStore`, and are exported with `MprisServer::enable_track_list` and
+//! `MprisServer::enable_playlists`.
+//!
+//! The server remembers the last announced value of every property. Whenever the handler's state
+//! differs from it, one `PropertiesChanged` signal per interface is emitted, and a `Seeked` signal
+//! if the position jumped. Changes of the track list and the playlists are announced with the
+//! matching signals of their interfaces.
+use dbus::{Connection, Message, MessageType, NameFlag, RequestNameReply, Path};
+use dbus::arg::{RefArg, Variant};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Instant;
+
+use client::{ChangedProperty, MprisSignal, metadata_to_dict, open_session_bus, playlist_to_tuple};
+use decode::DecodeMode;
+use errors::*;
+
+
+/// Handler for the `org.mpris.MediaPlayer2` interface.
+///
+/// Optional properties are not exported while their getter returns `None`.
+pub trait RootHandler {
+    /// Brings the media player's user interface to the front using any appropriate mechanism
+    /// available.
+    ///
+    /// Only called if `can_raise` is `true`.
+    fn raise(&mut self) -> Result<()> { Ok(()) }
+
+    /// Causes the media player to stop running.
+    ///
+    /// Only called if `can_quit` is `true`.
+    fn quit(&mut self) -> Result<()> { Ok(()) }
+
+    /// Whether `quit` may be called.
+    fn can_quit(&self) -> bool { false }
+
+    /// Whether the media player is occupying the fullscreen.
+    ///
+    /// This property is optional.
+    fn fullscreen(&self) -> Option<bool> { None }
+
+    /// Asks the media player to enter (`true`) or leave (`false`) fullscreen mode.
+    ///
+    /// Only called if `can_set_fullscreen` is `Some(true)`.
+    fn set_fullscreen(&mut self, _fullscreen: bool) -> Result<()> { Ok(()) }
+
+    /// Whether `set_fullscreen` may be called.
+    ///
+    /// This property is optional.
+    fn can_set_fullscreen(&self) -> Option<bool> { None }
+
+    /// Whether `raise` may be called.
+    fn can_raise(&self) -> bool { false }
+
+    /// A friendly name to identify the media player to users (eg: "VLC media player").
+    fn identity(&self) -> String;
+
+    /// The basename of the media player's .desktop file, with the ".desktop" extension stripped.
+    ///
+    /// This property is optional.
+    fn desktop_entry(&self) -> Option<String> { None }
+
+    /// The URI schemes supported by the media player (eg: "file" or "http").
+    fn supported_uri_schemes(&self) -> Vec<String> { Vec::new() }
+
+    /// The mime-types supported by the media player (eg: "audio/mpeg" or "application/ogg").
+    fn supported_mime_types(&self) -> Vec<String> { Vec::new() }
+}
+
+/// Handler for the `org.mpris.MediaPlayer2.Player` interface.
+///
+/// The server only calls the control methods if the matching capability (`can_go_next` for
+/// `next`, `can_seek` for `seek` and so on) is `true`, so implementations don't have to check it
+/// again. Optional properties are not exported while their getter returns `None`.
+pub trait PlayerHandler {
+    /// Skips to the next track in the tracklist.
+    fn next(&mut self) -> Result<()> { Ok(()) }
+
+    /// Skips to the previous track in the tracklist.
+    fn previous(&mut self) -> Result<()> { Ok(()) }
+
+    /// Pauses playback.
+    fn pause(&mut self) -> Result<()> { Ok(()) }
+
+    /// Pauses playback if playing, starts or resumes it otherwise.
+    fn play_pause(&mut self) -> Result<()> { Ok(()) }
+
+    /// Stops playback.
+    fn stop(&mut self) -> Result<()> { Ok(()) }
+
+    /// Starts or resumes playback.
+    fn play(&mut self) -> Result<()> { Ok(()) }
+
+    /// Seeks forward in the current track by `offset` microseconds. A negative value seeks back.
+    fn seek(&mut self, _offset: i64) -> Result<()> { Ok(()) }
+
+    /// Sets the position of the current track in microseconds.
+    ///
+    /// The server has already checked that `track_id` is the current track and that `position`
+    /// is not negative.
+    fn set_position(&mut self, _track_id: &::TrackId, _position: i64) -> Result<()> { Ok(()) }
+
+    /// Opens and plays the given `uri`.
+    fn open_uri(&mut self, _uri: &str) -> Result<()> { Ok(()) }
+
+    /// The current playback status.
+    fn playback_status(&self) -> ::PlaybackStatus;
+
+    /// The current loop / repeat status.
+    ///
+    /// This property is optional.
+    fn loop_status(&self) -> Option<::LoopStatus> { None }
+
+    /// Sets the loop / repeat status.
+    fn set_loop_status(&mut self, _loop_status: ::LoopStatus) -> Result<()> { Ok(()) }
+
+    /// The current playback rate.
+    fn rate(&self) -> ::PlaybackRate { 1.0 }
+
+    /// Sets the playback rate. The server ensures that the value lies between `minimum_rate` and
+    /// `maximum_rate`. A rate of 0.0 is not passed on, but handled like a call of `pause`.
+    fn set_rate(&mut self, _rate: ::PlaybackRate) -> Result<()> { Ok(()) }
+
+    /// Whether playback is progressing through the playlist in a non-linear order.
+    ///
+    /// This property is optional.
+    fn shuffle(&self) -> Option<bool> { None }
+
+    /// Enables or disables shuffling.
+    fn set_shuffle(&mut self, _shuffle: bool) -> Result<()> { Ok(()) }
+
+    /// The metadata of the current track.
+    ///
+    /// If there is no current track, the `mpris:trackid` entry should be
+    /// `/org/mpris/MediaPlayer2/TrackList/NoTrack`.
+    fn metadata(&self) -> ::MetadataMap;
+
+    /// The volume level.
+    fn volume(&self) -> ::Volume { 1.0 }
+
+    /// Sets the volume level. Negative values are replaced by 0.0 by the server.
+    fn set_volume(&mut self, _volume: ::Volume) -> Result<()> { Ok(()) }
+
+    /// The current track position in microseconds.
+    fn position(&self) -> i64 { 0 }
+
+    /// The minimum value which the `rate` property can take.
+    fn minimum_rate(&self) -> ::PlaybackRate { 1.0 }
+
+    /// The maximum value which the `rate` property can take.
+    fn maximum_rate(&self) -> ::PlaybackRate { 1.0 }
+
+    /// Whether `next` may be called.
+    fn can_go_next(&self) -> bool { false }
+
+    /// Whether `previous` may be called.
+    fn can_go_previous(&self) -> bool { false }
+
+    /// Whether `play` may be called.
+    fn can_play(&self) -> bool { false }
+
+    /// Whether `pause` and `play_pause` may be called.
+    fn can_pause(&self) -> bool { false }
+
+    /// Whether `seek` and `set_position` may be called.
+    fn can_seek(&self) -> bool { false }
+
+    /// Whether the media player may be controlled over this interface at all.
+    ///
+    /// If this is `false`, no methods are called and no properties are written.
+    fn can_control(&self) -> bool { false }
+}
+
+/// Handler for the methods of the `org.mpris.MediaPlayer2.TrackList` interface.
+///
+/// The track list itself is managed by the server, see `TrackList`.
+pub trait TrackListHandler {
+    /// Returns the metadata of the track at `uri`, which is added to the track list by `AddTrack`.
+    ///
+    /// The track id of the returned metadata is replaced by a generated one. Only called if
+    /// `TrackList::can_edit_tracks` is `true`.
+    fn load_track(&mut self, uri: &str) -> Result<::MetadataMap> {
+        bail!(ErrorKind::GeneralError(format!("Could not load {}: adding tracks is not supported.", uri)))
+    }
+
+    /// Skips to the track `track_id`.
+    fn go_to(&mut self, _track_id: &::TrackId, _metadata: &::MetadataMap) -> Result<()> { Ok(()) }
+
+    /// Called before the track `track_id` is removed by `RemoveTrack`.
+    ///
+    /// Only called if `TrackList::can_edit_tracks` is `true`.
+    fn remove_track(&mut self, _track_id: &::TrackId) -> Result<()> { Ok(()) }
+}
+
+/// Handler for the methods of the `org.mpris.MediaPlayer2.Playlists` interface.
+///
+/// The playlists themselves are managed by the server, see `Playlists`.
+pub trait PlaylistsHandler {
+    /// Starts playing `playlist`. Afterwards it becomes the active playlist.
+    fn activate_playlist(&mut self, _playlist: &::Playlist) -> Result<()> { Ok(()) }
+}
+
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const ROOT_INTERFACE: &str = "org.mpris.MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const TRACK_LIST_INTERFACE: &str = "org.mpris.MediaPlayer2.TrackList";
+const PLAYLISTS_INTERFACE: &str = "org.mpris.MediaPlayer2.Playlists";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// The names of the properties of the `org.mpris.MediaPlayer2` interface.
+const ROOT_PROPERTIES: &[&str] = &["CanQuit", "Fullscreen", "CanSetFullscreen", "CanRaise",
+    "HasTrackList", "Identity", "DesktopEntry", "SupportedUriSchemes", "SupportedMimeTypes"];
+
+/// The names of the properties of the `org.mpris.MediaPlayer2.Player` interface.
+const PLAYER_PROPERTIES: &[&str] = &["PlaybackStatus", "LoopStatus", "Rate", "Shuffle",
+    "Metadata", "Volume", "Position", "MinimumRate", "MaximumRate", "CanGoNext", "CanGoPrevious",
+    "CanPlay", "CanPause", "CanSeek", "CanControl"];
+
+/// The names of the properties of the `org.mpris.MediaPlayer2.TrackList` interface.
+const TRACK_LIST_PROPERTIES: &[&str] = &["Tracks", "CanEditTracks"];
+
+/// The names of the properties of the `org.mpris.MediaPlayer2.Playlists` interface.
+const PLAYLISTS_PROPERTIES: &[&str] = &["PlaylistCount", "Orderings", "ActivePlaylist"];
+
+/// Properties which are not announced with `PropertiesChanged`.
+const NOT_EMITTING_PROPERTIES: &[&str] = &["Position", "CanControl"];
+
+/// Properties which are only announced as invalidated by `PropertiesChanged`.
+const INVALIDATING_PROPERTIES: &[&str] = &["Tracks"];
+
+/// The orderings supported by `Playlists`.
+const PLAYLIST_ORDERINGS: &[::PlaylistOrdering] = &[::PlaylistOrdering::Alphabetical,
+    ::PlaylistOrdering::UserDefined];
+
+/// The default deviation from the expected position in microseconds, which is tolerated before
+/// `Seeked` is emitted.
+const DEFAULT_SEEK_TOLERANCE: i64 = 250_000;
+
+const INTROSPECTION: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="org.freedesktop.DBus.Introspectable">
+    <method name="Introspect">
+      <arg name="xml_data" type="s" direction="out"/>
+    </method>
+  </interface>
+  <interface name="org.freedesktop.DBus.Peer">
+    <method name="Ping"/>
+    <method name="GetMachineId">
+      <arg name="machine_uuid" type="s" direction="out"/>
+    </method>
+  </interface>
+  <interface name="org.freedesktop.DBus.Properties">
+    <method name="Get">
+      <arg name="interface_name" type="s" direction="in"/>
+      <arg name="property_name" type="s" direction="in"/>
+      <arg name="value" type="v" direction="out"/>
+    </method>
+    <method name="GetAll">
+      <arg name="interface_name" type="s" direction="in"/>
+      <arg name="properties" type="a{sv}" direction="out"/>
+    </method>
+    <method name="Set">
+      <arg name="interface_name" type="s" direction="in"/>
+      <arg name="property_name" type="s" direction="in"/>
+      <arg name="value" type="v" direction="in"/>
+    </method>
+    <signal name="PropertiesChanged">
+      <arg name="interface_name" type="s"/>
+      <arg name="changed_properties" type="a{sv}"/>
+      <arg name="invalidated_properties" type="as"/>
+    </signal>
+  </interface>
+  <interface name="org.mpris.MediaPlayer2">
+    <method name="Raise"/>
+    <method name="Quit"/>
+    <property name="CanQuit" type="b" access="read"/>
+    <property name="Fullscreen" type="b" access="readwrite"/>
+    <property name="CanSetFullscreen" type="b" access="read"/>
+    <property name="CanRaise" type="b" access="read"/>
+    <property name="HasTrackList" type="b" access="read"/>
+    <property name="Identity" type="s" access="read"/>
+    <property name="DesktopEntry" type="s" access="read"/>
+    <property name="SupportedUriSchemes" type="as" access="read"/>
+    <property name="SupportedMimeTypes" type="as" access="read"/>
+  </interface>
+  <interface name="org.mpris.MediaPlayer2.Player">
+    <method name="Next"/>
+    <method name="Previous"/>
+    <method name="Pause"/>
+    <method name="PlayPause"/>
+    <method name="Stop"/>
+    <method name="Play"/>
+    <method name="Seek">
+      <arg name="Offset" type="x" direction="in"/>
+    </method>
+    <method name="SetPosition">
+      <arg name="TrackId" type="o" direction="in"/>
+      <arg name="Position" type="x" direction="in"/>
+    </method>
+    <method name="OpenUri">
+      <arg name="Uri" type="s" direction="in"/>
+    </method>
+    <signal name="Seeked">
+      <arg name="Position" type="x"/>
+    </signal>
+    <property name="PlaybackStatus" type="s" access="read"/>
+    <property name="LoopStatus" type="s" access="readwrite"/>
+    <property name="Rate" type="d" access="readwrite"/>
+    <property name="Shuffle" type="b" access="readwrite"/>
+    <property name="Metadata" type="a{sv}" access="read"/>
+    <property name="Volume" type="d" access="readwrite"/>
+    <property name="Position" type="x" access="read">
+      <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+    </property>
+    <property name="MinimumRate" type="d" access="read"/>
+    <property name="MaximumRate" type="d" access="read"/>
+    <property name="CanGoNext" type="b" access="read"/>
+    <property name="CanGoPrevious" type="b" access="read"/>
+    <property name="CanPlay" type="b" access="read"/>
+    <property name="CanPause" type="b" access="read"/>
+    <property name="CanSeek" type="b" access="read"/>
+    <property name="CanControl" type="b" access="read">
+      <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+    </property>
+  </interface>
+"#;
+
+const TRACK_LIST_INTROSPECTION: &str = r#"  <interface name="org.mpris.MediaPlayer2.TrackList">
+    <method name="GetTracksMetadata">
+      <arg name="TrackIds" type="ao" direction="in"/>
+      <arg name="Metadata" type="aa{sv}" direction="out"/>
+    </method>
+    <method name="AddTrack">
+      <arg name="Uri" type="s" direction="in"/>
+      <arg name="AfterTrack" type="o" direction="in"/>
+      <arg name="SetAsCurrent" type="b" direction="in"/>
+    </method>
+    <method name="RemoveTrack">
+      <arg name="TrackId" type="o" direction="in"/>
+    </method>
+    <method name="GoTo">
+      <arg name="TrackId" type="o" direction="in"/>
+    </method>
+    <signal name="TrackListReplaced">
+      <arg name="Tracks" type="ao"/>
+      <arg name="CurrentTrack" type="o"/>
+    </signal>
+    <signal name="TrackAdded">
+      <arg name="Metadata" type="a{sv}"/>
+      <arg name="AfterTrack" type="o"/>
+    </signal>
+    <signal name="TrackRemoved">
+      <arg name="TrackId" type="o"/>
+    </signal>
+    <signal name="TrackMetadataChanged">
+      <arg name="TrackId" type="o"/>
+      <arg name="Metadata" type="a{sv}"/>
+    </signal>
+    <property name="Tracks" type="ao" access="read">
+      <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="invalidates"/>
+    </property>
+    <property name="CanEditTracks" type="b" access="read"/>
+  </interface>
+"#;
+
+const PLAYLISTS_INTROSPECTION: &str = r#"  <interface name="org.mpris.MediaPlayer2.Playlists">
+    <method name="ActivatePlaylist">
+      <arg name="PlaylistId" type="o" direction="in"/>
+    </method>
+    <method name="GetPlaylists">
+      <arg name="Index" type="u" direction="in"/>
+      <arg name="MaxCount" type="u" direction="in"/>
+      <arg name="Order" type="s" direction="in"/>
+      <arg name="ReverseOrder" type="b" direction="in"/>
+      <arg name="Playlists" type="a(oss)" direction="out"/>
+    </method>
+    <signal name="PlaylistChanged">
+      <arg name="Playlist" type="(oss)"/>
+    </signal>
+    <property name="PlaylistCount" type="u" access="read"/>
+    <property name="Orderings" type="as" access="read"/>
+    <property name="ActivePlaylist" type="(b(oss))" access="read"/>
+  </interface>
+"#;
+
+
+/// An error which is sent back to the caller of a method.
+#[derive(Debug)]
+struct MethodErr {
+    name: &'static str,
+    message: String,
+}
+
+impl MethodErr {
+    fn new(name: &'static str, message: &str) -> Self {
+        MethodErr { name, message: message.to_string() }
+    }
+
+    fn invalid_args(msg: &Message) -> Self {
+        MethodErr::new(
+            "org.freedesktop.DBus.Error.InvalidArgs",
+            &format!("Invalid arguments for {}", msg.member().map(|m| m.to_string()).unwrap_or_default()),
+        )
+    }
+
+    fn unknown_property(interface: &str, name: &str) -> Self {
+        MethodErr::new(
+            "org.freedesktop.DBus.Error.UnknownProperty",
+            &format!("Unknown property {} on interface {}", name, interface),
+        )
+    }
+
+    fn unknown_method(interface: &str, member: &str) -> Self {
+        MethodErr::new(
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            &format!("Unknown method {} on interface {}", member, interface),
+        )
+    }
+
+    fn unknown_object(path: &str) -> Self {
+        MethodErr::new(
+            "org.freedesktop.DBus.Error.UnknownObject",
+            &format!("No object at path {}", path),
+        )
+    }
+
+    fn unknown_interface(interface: &str) -> Self {
+        MethodErr::new(
+            "org.freedesktop.DBus.Error.UnknownInterface",
+            &format!("Unknown interface {}", interface),
+        )
+    }
+}
+
+impl From<Error> for MethodErr {
+    fn from(err: Error) -> Self {
+        MethodErr::new("org.freedesktop.DBus.Error.Failed", &err.to_string())
+    }
+}
+
+type MethodResult = ::std::result::Result<Message, MethodErr>;
+
+/// Dispatches a method call of an optional interface with the state `T` to the handler `H`.
+type CallHandler<H, T> = fn(&mut H, &mut T, &str, &Message) -> MethodResult;
+
+
+/// The last known position, which is used to decide whether `Seeked` has to be emitted.
+#[derive(Debug)]
+struct PositionState {
+    instant: Instant,
+    position: i64,
+    rate: ::PlaybackRate,
+    playback_status: ::PlaybackStatus,
+    track_id: ::TrackId,
+}
+
+impl PositionState {
+    /// Returns the position which is expected now, if the media player neither seeked nor
+    /// changed the track.
+    fn expected_position(&self, now: Instant) -> i64 {
+        match self.playback_status {
+            ::PlaybackStatus::Stopped => 0,
+            ::PlaybackStatus::Playing => {
+                let elapsed = now.duration_since(self.instant);
+                let elapsed_us = elapsed.as_secs() as f64 * 1e6 + f64::from(elapsed.subsec_nanos()) / 1e3;
+                self.position + (elapsed_us * self.rate) as i64
+            }
+            ::PlaybackStatus::Paused => self.position,
+        }
+    }
+}
+
+
+/// Exports a media player as `org.mpris.MediaPlayer2.playerName` on the session bus.
+///
+/// The server is driven by calling `process` or `run`, which dispatch incoming requests to the
+/// handler. Changes of the handler's state are announced automatically after each request and
+/// after `update`.
+pub struct MprisServer<H> {
+    conn: Connection,
+    bus_name: String,
+    handler: H,
+    /// The last announced properties, keyed by interface and property name.
+    properties: HashMap<(&'static str, &'static str), ChangedProperty>,
+    position: Option<PositionState>,
+    seek_tolerance: i64,
+    track_list: Option<(TrackList, CallHandler<H, TrackList>)>,
+    playlists: Option<(Playlists, CallHandler<H, Playlists>)>,
+}
+
+impl<H: RootHandler + PlayerHandler> MprisServer<H> {
+    /// Creates a new `MprisServer` and requests the bus name `org.mpris.MediaPlayer2.playerName`.
+    ///
+    /// Fails if the bus name is already owned by another connection.
+    pub fn new(player_name: &str, handler: H) -> Result<Self> {
+        let conn = open_session_bus()?;
+        let bus_name = format!("org.mpris.MediaPlayer2.{}", player_name);
+
+        match conn.register_name(&bus_name, NameFlag::DoNotQueue.value())? {
+            RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => {}
+            _ => bail!(ErrorKind::GeneralError(format!("The bus name {} is already taken.", bus_name))),
+        }
+        conn.register_object_path(MPRIS_PATH)?;
+        // `handle_message` answers all method calls, so libdbus must not answer the ones for other
+        // objects itself
+        if let Some(mut queue) = conn.replace_message_callback(None) {
+            conn.replace_message_callback(Some(Box::new(move |conn, msg| {
+                let method_call = msg.msg_type() == MessageType::MethodCall;
+                queue(conn, msg) || method_call
+            })));
+        }
+
+        let mut server = MprisServer {
+            conn,
+            bus_name,
+            handler,
+            properties: HashMap::new(),
+            position: None,
+            seek_tolerance: DEFAULT_SEEK_TOLERANCE,
+            track_list: None,
+            playlists: None,
+        };
+        server.properties = server.current_properties();
+        server.position = Some(server.current_position());
+        Ok(server)
+    }
+
+    /// Returns the well-known bus name of the media player.
+    pub fn bus_name(&self) -> &str {
+        &self.bus_name
+    }
+
+    /// Returns the unique bus name of the media player's connection.
+    pub fn unique_bus_name(&self) -> String {
+        self.conn.unique_name()
+    }
+
+    /// Returns a reference to the handler.
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// Returns a mutable reference to the handler.
+    ///
+    /// Changes made through this reference are announced with the next call of `process`. Use
+    /// `update` to announce them immediately.
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    /// Modifies the handler with `f` and emits `PropertiesChanged` and `Seeked` for the changes.
+    pub fn update<F, T>(&mut self, f: F) -> Result<T>
+        where F: FnOnce(&mut H) -> T
+    {
+        let result = f(&mut self.handler);
+        self.emit_changes()?;
+        Ok(result)
+    }
+
+    /// Returns the exported track list, if there is one.
+    pub fn track_list(&self) -> Option<&TrackList> {
+        self.track_list.as_ref().map(|(track_list, _)| track_list)
+    }
+
+    /// Returns the exported track list mutably, if there is one.
+    ///
+    /// Changes are announced with the next call of `process` or `emit_changes`.
+    pub fn track_list_mut(&mut self) -> Option<&mut TrackList> {
+        self.track_list.as_mut().map(|(track_list, _)| track_list)
+    }
+
+    /// Returns the exported playlists, if there are any.
+    pub fn playlists(&self) -> Option<&Playlists> {
+        self.playlists.as_ref().map(|(playlists, _)| playlists)
+    }
+
+    /// Returns the exported playlists mutably, if there are any.
+    ///
+    /// Changes are announced with the next call of `process` or `emit_changes`.
+    pub fn playlists_mut(&mut self) -> Option<&mut Playlists> {
+        self.playlists.as_mut().map(|(playlists, _)| playlists)
+    }
+
+    /// Sets the deviation from the expected position in microseconds, which is tolerated before
+    /// `Seeked` is emitted. The default is 250 ms.
+    pub fn set_seek_tolerance(&mut self, tolerance: i64) {
+        self.seek_tolerance = tolerance;
+    }
+
+    /// Handles incoming requests. Blocks at most `timeout_ms` milliseconds while waiting for the
+    /// first request, and returns as soon as there are no more pending requests.
+    pub fn process(&mut self, timeout_ms: u32) -> Result<()> {
+        let mut timeout = timeout_ms;
+        while let Some(msg) = self.conn.incoming(timeout).next() {
+            timeout = 0;
+            if let Some(reply) = self.handle_message(&msg) {
+                self.send(reply)?;
+            }
+        }
+        self.emit_changes()?;
+
+        if self.conn.is_connected() {
+            Ok(())
+        } else {
+            bail!(ErrorKind::GeneralError("The D-Bus connection was closed.".to_string()))
+        }
+    }
+
+    /// Handles incoming requests until an error occurs.
+    pub fn run(&mut self) -> Result<()> {
+        loop {
+            self.process(1000)?;
+        }
+    }
+
+    /// Sends a message.
+    fn send(&self, msg: Message) -> Result<()> {
+        self.conn.send(msg)
+            .map(|_| ())
+            .map_err(|_| ErrorKind::GeneralError("Could not send D-Bus message.".to_string()).into())
+    }
+
+    /// Emits `signal` as is, without comparing any state.
+    fn send_signal(&self, signal: &MprisSignal) -> Result<()> {
+        self.send(signal.to_message())
+    }
+
+    /// Sends `msg` as is.
+    #[cfg(feature = "test-support")]
+    pub(crate) fn send_message(&self, msg: Message) -> Result<()> {
+        self.send(msg)
+    }
+
+    /// Compares the handler's state with the last announced one and emits `PropertiesChanged` and
+    /// `Seeked` for the differences, as well as the signals of the track list and the playlists.
+    ///
+    /// This is done automatically by `process` and `update`.
+    pub fn emit_changes(&mut self) -> Result<()> {
+        let current_track = self.handler.metadata().trackid().clone();
+        let mut signals = Vec::new();
+        if let Some((ref mut track_list, _)) = self.track_list {
+            signals.extend(track_list.take_signals(&current_track));
+        }
+        if let Some((ref mut playlists, _)) = self.playlists {
+            signals.extend(playlists.take_signals());
+        }
+        for signal in signals {
+            self.send_signal(&signal)?;
+        }
+
+        let properties = self.current_properties();
+        for interface in self.interfaces() {
+            let mut changed = Vec::new();
+            let mut invalidated = Vec::new();
+            for name in self.property_names(interface) {
+                if NOT_EMITTING_PROPERTIES.contains(name) {
+                    continue;
+                }
+                let key = (interface, *name);
+                match (self.properties.get(&key), properties.get(&key)) {
+                    (old, Some(new)) if old != Some(new) && INVALIDATING_PROPERTIES.contains(name) => {
+                        invalidated.push(name.to_string())
+                    }
+                    (old, Some(new)) if old != Some(new) => changed.push(new.clone()),
+                    (Some(_), None) => invalidated.push(name.to_string()),
+                    _ => {}
+                }
+            }
+
+            if !changed.is_empty() || !invalidated.is_empty() {
+                self.send_signal(&MprisSignal::PropertiesChanged {
+                    interface: interface.to_string(),
+                    changed_properties: changed,
+                    invalidated_properties: invalidated,
+                })?;
+            }
+        }
+        self.properties = properties;
+
+        let position = self.current_position();
+        if let Some(seeked) = self.position.as_ref().and_then(|old| self.seeked_position(old, &position)) {
+            self.send_signal(&MprisSignal::Seeked { position: seeked })?;
+        }
+        self.position = Some(position);
+        Ok(())
+    }
+
+    /// Returns the new position if it is not the expected one, as described at
+    /// `MprisSignal::Seeked`.
+    fn seeked_position(&self, old: &PositionState, new: &PositionState) -> Option<i64> {
+        if new.playback_status == ::PlaybackStatus::Stopped {
+            return None;
+        }
+        let expected = if old.track_id != new.track_id {
+            0
+        } else {
+            old.expected_position(new.instant)
+        };
+        if (new.position - expected).abs() > self.seek_tolerance {
+            Some(new.position)
+        } else {
+            None
+        }
+    }
+
+    /// Reads all present properties from the handler.
+    fn current_properties(&self) -> HashMap<(&'static str, &'static str), ChangedProperty> {
+        let mut properties = HashMap::new();
+        for interface in self.interfaces() {
+            for name in self.property_names(interface) {
+                if let Some(prop) = self.property(interface, name) {
+                    properties.insert((interface, *name), prop);
+                }
+            }
+        }
+        properties
+    }
+
+    /// Reads the current position from the handler.
+    fn current_position(&self) -> PositionState {
+        PositionState {
+            instant: Instant::now(),
+            position: self.handler.position(),
+            rate: self.handler.rate(),
+            playback_status: self.handler.playback_status(),
+            track_id: self.handler.metadata().trackid().clone(),
+        }
+    }
+
+    /// Handles a single message and returns the reply, if there is one.
+    fn handle_message(&mut self, msg: &Message) -> Option<Message> {
+        let (msg_type, path, interface, member) = msg.headers();
+        if msg_type != MessageType::MethodCall {
+            return None;
+        }
+
+        let path = path.unwrap_or_default();
+        let result = match (interface, member) {
+            _ if path != MPRIS_PATH => Err(MethodErr::unknown_object(&path)),
+            (Some(interface), Some(member)) => self.handle_method_call(&interface, &member, msg),
+            (None, Some(member)) => {
+                // the interface is optional in method calls
+                match self.find_interface(&member) {
+                    Some(interface) => self.handle_method_call(interface, &member, msg),
+                    None => Err(MethodErr::new("org.freedesktop.DBus.Error.UnknownMethod",
+                                               &format!("Unknown method {}", member))),
+                }
+            }
+            _ => Err(MethodErr::new("org.freedesktop.DBus.Error.UnknownMethod", "No member given")),
+        };
+
+        if msg.get_no_reply() {
+            return None;
+        }
+        match result {
+            Ok(reply) => Some(reply),
+            Err(err) => Message::new_error(msg, err.name, &err.message),
+        }
+    }
+
+    /// Returns the interface which implements the method `member`, or `None` if no interface
+    /// does.
+    fn find_interface(&self, member: &str) -> Option<&'static str> {
+        let interface = match member {
+            "Introspect" => "org.freedesktop.DBus.Introspectable",
+            "Get" | "GetAll" | "Set" => "org.freedesktop.DBus.Properties",
+            "Raise" | "Quit" => ROOT_INTERFACE,
+            "Next" | "Previous" | "Pause" | "PlayPause" | "Stop" | "Play" | "Seek" | "SetPosition"
+            | "OpenUri" => PLAYER_INTERFACE,
+            "GetTracksMetadata" | "AddTrack" | "RemoveTrack" | "GoTo" => TRACK_LIST_INTERFACE,
+            "ActivatePlaylist" | "GetPlaylists" => PLAYLISTS_INTERFACE,
+            _ => return None,
+        };
+        Some(interface)
+    }
+
+    fn handle_method_call(&mut self, interface: &str, member: &str, msg: &Message) -> MethodResult {
+        match (interface, member) {
+            ("org.freedesktop.DBus.Introspectable", "Introspect") => {
+                Ok(msg.method_return().append1(self.introspect()))
+            }
+            ("org.freedesktop.DBus.Properties", "Get") => {
+                let (interface, name): (&str, &str) = msg.read2()
+                    .map_err(|_| MethodErr::invalid_args(msg))?;
+                self.check_interface(interface)?;
+                let value = self.property(interface, name)
+                    .and_then(|prop| prop.to_refarg())
+                    .ok_or_else(|| MethodErr::unknown_property(interface, name))?;
+                Ok(msg.method_return().append1(Variant(value)))
+            }
+            ("org.freedesktop.DBus.Properties", "GetAll") => {
+                let interface: &str = msg.read1().map_err(|_| MethodErr::invalid_args(msg))?;
+                self.check_interface(interface)?;
+                Ok(msg.method_return().append1(self.properties_dict(interface)))
+            }
+            ("org.freedesktop.DBus.Properties", "Set") => {
+                let mut iter = msg.iter_init();
+                let interface: &str = iter.read().map_err(|_| MethodErr::invalid_args(msg))?;
+                let name: &str = iter.read().map_err(|_| MethodErr::invalid_args(msg))?;
+                let mut value = Variant::new_refarg(&mut iter).ok_or_else(|| MethodErr::invalid_args(msg))?;
+                self.check_interface(interface)?;
+                if self.property(interface, name).is_none() {
+                    return Err(MethodErr::unknown_property(interface, name));
+                }
+                let value = ChangedProperty::from_variant(name, &mut value, DecodeMode::Lenient)
+                    .map_err(|_| MethodErr::invalid_args(msg))?;
+                self.set_property(interface, value)?;
+                Ok(msg.method_return())
+            }
+            (ROOT_INTERFACE, _) | (PLAYER_INTERFACE, _) => {
+                self.call_handler(interface, member, msg)?;
+                Ok(msg.method_return())
+            }
+            (TRACK_LIST_INTERFACE, _) => match self.track_list {
+                Some((ref mut track_list, call)) => call(&mut self.handler, track_list, member, msg),
+                None => Err(MethodErr::unknown_interface(interface)),
+            },
+            (PLAYLISTS_INTERFACE, _) => match self.playlists {
+                Some((ref mut playlists, call)) => call(&mut self.handler, playlists, member, msg),
+                None => Err(MethodErr::unknown_interface(interface)),
+            },
+            _ => Err(MethodErr::unknown_interface(interface)),
+        }
+    }
+
+    /// Calls the handler method which implements `interface.member`.
+    fn call_handler(&mut self, interface: &str, member: &str, msg: &Message) -> ::std::result::Result<(), MethodErr> {
+        let handler = &mut self.handler;
+        let controllable = handler.can_control();
+        match (interface, member) {
+            (ROOT_INTERFACE, "Raise") => if handler.can_raise() { handler.raise()? },
+            (ROOT_INTERFACE, "Quit") => if handler.can_quit() { handler.quit()? },
+            (PLAYER_INTERFACE, "Next") => if controllable && handler.can_go_next() { handler.next()? },
+            (PLAYER_INTERFACE, "Previous") => {
+                if controllable && handler.can_go_previous() { handler.previous()? }
+            }
+            (PLAYER_INTERFACE, "Pause") => if controllable && handler.can_pause() { handler.pause()? },
+            (PLAYER_INTERFACE, "PlayPause") => {
+                if controllable && handler.can_pause() { handler.play_pause()? }
+            }
+            (PLAYER_INTERFACE, "Stop") => if controllable { handler.stop()? },
+            (PLAYER_INTERFACE, "Play") => if controllable && handler.can_play() { handler.play()? },
+            (PLAYER_INTERFACE, "Seek") => {
+                let offset: i64 = msg.read1().map_err(|_| MethodErr::invalid_args(msg))?;
+                if controllable && handler.can_seek() { handler.seek(offset)? }
+            }
+            (PLAYER_INTERFACE, "SetPosition") => {
+                let (track_id, position): (Path, i64) = msg.read2()
+                    .map_err(|_| MethodErr::invalid_args(msg))?;
+                let track_id = ::TrackId::from_str(&track_id).map_err(|_| MethodErr::invalid_args(msg))?;
+                // calls with a stale track id or an invalid position are ignored
+                if controllable && handler.can_seek() && position >= 0
+                    && &track_id == handler.metadata().trackid() {
+                    handler.set_position(&track_id, position)?
+                }
+            }
+            (PLAYER_INTERFACE, "OpenUri") => {
+                let uri: &str = msg.read1().map_err(|_| MethodErr::invalid_args(msg))?;
+                if controllable { handler.open_uri(uri)? }
+            }
+            _ => return Err(MethodErr::unknown_method(interface, member)),
+        }
+        Ok(())
+    }
+
+    /// Returns the exported MPRIS interfaces.
+    fn interfaces(&self) -> Vec<&'static str> {
+        let mut interfaces = vec![ROOT_INTERFACE, PLAYER_INTERFACE];
+        if self.track_list.is_some() {
+            interfaces.push(TRACK_LIST_INTERFACE);
+        }
+        if self.playlists.is_some() {
+            interfaces.push(PLAYLISTS_INTERFACE);
+        }
+        interfaces
+    }
+
+    /// Fails if `interface` is not exported.
+    fn check_interface(&self, interface: &str) -> ::std::result::Result<(), MethodErr> {
+        if self.interfaces().contains(&interface) {
+            Ok(())
+        } else {
+            Err(MethodErr::unknown_interface(interface))
+        }
+    }
+
+    /// Returns the names of the properties of `interface`.
+    fn property_names(&self, interface: &str) -> &'static [&'static str] {
+        match interface {
+            ROOT_INTERFACE => ROOT_PROPERTIES,
+            PLAYER_INTERFACE => PLAYER_PROPERTIES,
+            TRACK_LIST_INTERFACE => TRACK_LIST_PROPERTIES,
+            PLAYLISTS_INTERFACE => PLAYLISTS_PROPERTIES,
+            _ => &[],
+        }
+    }
+
+    /// Returns all present properties of `interface` as an `a{sv}`.
+    fn properties_dict(&self, interface: &str) -> HashMap<String, Variant<Box<dyn RefArg>>> {
+        self.property_names(interface)
+            .iter()
+            .filter_map(|name| self.property(interface, name))
+            .filter_map(|prop| Some((prop.name()?.to_string(), Variant(prop.to_refarg()?))))
+            .collect()
+    }
+
+    /// Reads a property from the handler. Returns `None` if the property does not exist or if it
+    /// is optional and absent.
+    fn property(&self, interface: &str, name: &str) -> Option<ChangedProperty> {
+        use client::ChangedProperty::*;
+
+        let handler = &self.handler;
+        let prop = match (interface, name) {
+            (ROOT_INTERFACE, "CanQuit") => CanQuit(handler.can_quit()),
+            (ROOT_INTERFACE, "Fullscreen") => Fullscreen(handler.fullscreen()?),
+            (ROOT_INTERFACE, "CanSetFullscreen") => CanSetFullscreen(handler.can_set_fullscreen()?),
+            (ROOT_INTERFACE, "CanRaise") => CanRaise(handler.can_raise()),
+            (ROOT_INTERFACE, "HasTrackList") => HasTrackList(self.track_list.is_some()),
+            (ROOT_INTERFACE, "Identity") => Identity(handler.identity()),
+            (ROOT_INTERFACE, "DesktopEntry") => DesktopEntry(handler.desktop_entry()?),
+            (ROOT_INTERFACE, "SupportedUriSchemes") => SupportedUriSchemes(handler.supported_uri_schemes()),
+            (ROOT_INTERFACE, "SupportedMimeTypes") => SupportedMimeTypes(handler.supported_mime_types()),
+
+            (PLAYER_INTERFACE, "PlaybackStatus") => PlaybackStatus(handler.playback_status()),
+            (PLAYER_INTERFACE, "LoopStatus") => LoopStatus(handler.loop_status()?),
+            (PLAYER_INTERFACE, "Rate") => Rate(handler.rate()),
+            (PLAYER_INTERFACE, "Shuffle") => Shuffle(handler.shuffle()?),
+            (PLAYER_INTERFACE, "Metadata") => Metadata(handler.metadata()),
+            (PLAYER_INTERFACE, "Volume") => Volume(handler.volume()),
+            (PLAYER_INTERFACE, "Position") => Position(handler.position()),
+            (PLAYER_INTERFACE, "MinimumRate") => MinimumRate(handler.minimum_rate()),
+            (PLAYER_INTERFACE, "MaximumRate") => MaximumRate(handler.maximum_rate()),
+            (PLAYER_INTERFACE, "CanGoNext") => CanGoNext(handler.can_go_next()),
+            (PLAYER_INTERFACE, "CanGoPrevious") => CanGoPrevious(handler.can_go_previous()),
+            (PLAYER_INTERFACE, "CanPlay") => CanPlay(handler.can_play()),
+            (PLAYER_INTERFACE, "CanPause") => CanPause(handler.can_pause()),
+            (PLAYER_INTERFACE, "CanSeek") => CanSeek(handler.can_seek()),
+            (PLAYER_INTERFACE, "CanControl") => CanControl(handler.can_control()),
+
+            (TRACK_LIST_INTERFACE, "Tracks") => Tracks(self.track_list()?.tracks()),
+            (TRACK_LIST_INTERFACE, "CanEditTracks") => CanEditTracks(self.track_list()?.can_edit_tracks()),
+
+            (PLAYLISTS_INTERFACE, "PlaylistCount") => PlaylistCount(self.playlists()?.playlist_count()),
+            (PLAYLISTS_INTERFACE, "Orderings") => Orderings(PLAYLIST_ORDERINGS.to_vec()),
+            (PLAYLISTS_INTERFACE, "ActivePlaylist") => ActivePlaylist(self.playlists()?.maybe_active_playlist()),
+            _ => return None,
+        };
+        Some(prop)
+    }
+
+    /// Writes a property via the handler.
+    fn set_property(&mut self, interface: &str, value: ChangedProperty) -> ::std::result::Result<(), MethodErr> {
+        use client::ChangedProperty::*;
+
+        let handler = &mut self.handler;
+        let read_only = || {
+            MethodErr::new("org.freedesktop.DBus.Error.PropertyReadOnly", "The property is read-only")
+        };
+        match (interface, value) {
+            (ROOT_INTERFACE, Fullscreen(fullscreen)) => {
+                if handler.can_set_fullscreen() != Some(true) {
+                    return Err(read_only());
+                }
+                handler.set_fullscreen(fullscreen)?
+            }
+            (PLAYER_INTERFACE, value) => {
+                if !handler.can_control() {
+                    return Err(read_only());
+                }
+                match value {
+                    LoopStatus(loop_status) => handler.set_loop_status(loop_status)?,
+                    Rate(rate) => {
+                        // a rate of 0.0 acts as a call of `Pause`, whatever the minimum rate is
+                        if rate == 0.0 {
+                            if handler.can_pause() { handler.pause()? }
+                        } else if rate < handler.minimum_rate() || rate > handler.maximum_rate() {
+                            return Err(MethodErr::new(
+                                "org.freedesktop.DBus.Error.InvalidArgs",
+                                "The rate is out of range",
+                            ));
+                        } else {
+                            handler.set_rate(rate)?
+                        }
+                    }
+                    Shuffle(shuffle) => handler.set_shuffle(shuffle)?,
+                    Volume(volume) => handler.set_volume(volume.max(0.0))?,
+                    _ => return Err(read_only()),
+                }
+            }
+            _ => return Err(read_only()),
+        }
+        Ok(())
+    }
+
+    /// Returns the introspection data of the MPRIS object.
+    fn introspect(&self) -> String {
+        let mut xml = INTROSPECTION.to_string();
+        if self.track_list.is_some() {
+            xml.push_str(TRACK_LIST_INTROSPECTION);
+        }
+        if self.playlists.is_some() {
+            xml.push_str(PLAYLISTS_INTROSPECTION);
+        }
+        xml.push_str("</node>\n");
+        xml
+    }
+}
+
+impl<H: RootHandler + PlayerHandler + TrackListHandler> MprisServer<H> {
+    /// Exports the `org.mpris.MediaPlayer2.TrackList` interface for `track_list`.
+    ///
+    /// Its methods are forwarded to the `TrackListHandler`.
+    pub fn enable_track_list(&mut self, track_list: TrackList) {
+        self.track_list = Some((track_list, call_track_list::<H>));
+    }
+}
+
+impl<H: RootHandler + PlayerHandler + PlaylistsHandler> MprisServer<H> {
+    /// Exports the `org.mpris.MediaPlayer2.Playlists` interface for `playlists`.
+    ///
+    /// Its methods are forwarded to the `PlaylistsHandler`.
+    pub fn enable_playlists(&mut self, playlists: Playlists) {
+        self.playlists = Some((playlists, call_playlists::<H>));
+    }
+}
+
+/// Reads a `TrackId` from an object path argument.
+fn read_track_id(path: &Path, msg: &Message) -> ::std::result::Result<::TrackId, MethodErr> {
+    ::TrackId::from_str(path).map_err(|_| MethodErr::invalid_args(msg))
+}
+
+/// Handles a method call of the `org.mpris.MediaPlayer2.TrackList` interface.
+fn call_track_list<H: TrackListHandler>(handler: &mut H,
+                                        track_list: &mut TrackList,
+                                        member: &str,
+                                        msg: &Message)
+                                        -> MethodResult {
+    match member {
+        "GetTracksMetadata" => {
+            let track_ids: Vec<Path> = msg.read1().map_err(|_| MethodErr::invalid_args(msg))?;
+            let metadata: Vec<HashMap<String, Variant<Box<dyn RefArg>>>> = track_ids.iter()
+                .filter_map(|path| ::TrackId::from_str(path).ok())
+                .filter_map(|track_id| track_list.metadata(&track_id))
+                .map(metadata_to_dict)
+                .collect();
+            Ok(msg.method_return().append1(metadata))
+        }
+        "AddTrack" => {
+            let (uri, after_track, set_as_current): (&str, Path, bool) = msg.read3()
+                .map_err(|_| MethodErr::invalid_args(msg))?;
+            let after_track = read_track_id(&after_track, msg)?;
+            if track_list.can_edit_tracks() {
+                let metadata = handler.load_track(uri)?;
+                let after_track = if after_track.is_no_track() { None } else { Some(&after_track) };
+                let track_id = track_list.add_track(metadata, after_track)?;
+                if set_as_current {
+                    if let Some(metadata) = track_list.metadata(&track_id) {
+                        handler.go_to(&track_id, metadata)?;
+                    }
+                }
+            }
+            Ok(msg.method_return())
+        }
+        "RemoveTrack" => {
+            let track_id: Path = msg.read1().map_err(|_| MethodErr::invalid_args(msg))?;
+            let track_id = read_track_id(&track_id, msg)?;
+            if track_list.can_edit_tracks() && track_list.metadata(&track_id).is_some() {
+                handler.remove_track(&track_id)?;
+                track_list.remove_track(&track_id)?;
+            }
+            Ok(msg.method_return())
+        }
+        "GoTo" => {
+            let track_id: Path = msg.read1().map_err(|_| MethodErr::invalid_args(msg))?;
+            let track_id = read_track_id(&track_id, msg)?;
+            // unknown track ids are ignored
+            if let Some(metadata) = track_list.metadata(&track_id) {
+                handler.go_to(&track_id, metadata)?;
+            }
+            Ok(msg.method_return())
+        }
+        _ => Err(MethodErr::unknown_method(TRACK_LIST_INTERFACE, member)),
+    }
+}
+
+/// Handles a method call of the `org.mpris.MediaPlayer2.Playlists` interface.
+fn call_playlists<H: PlaylistsHandler>(handler: &mut H,
+                                       playlists: &mut Playlists,
+                                       member: &str,
+                                       msg: &Message)
+                                       -> MethodResult {
+    match member {
+        "ActivatePlaylist" => {
+            let playlist_id: Path = msg.read1().map_err(|_| MethodErr::invalid_args(msg))?;
+            // unknown playlist ids are ignored
+            if let Some(playlist) = playlists.playlist(&playlist_id).cloned() {
+                handler.activate_playlist(&playlist)?;
+                playlists.set_active_playlist(Some(&playlist.id))?;
+            }
+            Ok(msg.method_return())
+        }
+        "GetPlaylists" => {
+            let mut iter = msg.iter_init();
+            let index: u32 = iter.read().map_err(|_| MethodErr::invalid_args(msg))?;
+            let max_count: u32 = iter.read().map_err(|_| MethodErr::invalid_args(msg))?;
+            let order: &str = iter.read().map_err(|_| MethodErr::invalid_args(msg))?;
+            let reverse_order: bool = iter.read().map_err(|_| MethodErr::invalid_args(msg))?;
+            let order = ::PlaylistOrdering::from_str(order).map_err(|_| MethodErr::invalid_args(msg))?;
+            let list: Vec<(Path<'static>, String, String)> = playlists
+                .get_playlists(index, max_count, order, reverse_order)
+                .iter()
+                .map(playlist_to_tuple)
+                .collect();
+            Ok(msg.method_return().append1(list))
+        }
+        _ => Err(MethodErr::unknown_method(PLAYLISTS_INTERFACE, member)),
+    }
+}
+
+
+
+/// The storage of the tracks of a `TrackList`.
+///
+/// Tracks are identified by the track id of their metadata, which is generated by `TrackList`.
+pub trait TrackStore {
+    /// Returns the ids of all tracks in the order of the track list.
+    fn track_ids(&self) -> Vec<::TrackId>;
+
+    /// Returns the metadata of the track `track_id`.
+    fn lookup(&self, track_id: &::TrackId) -> Option<&::MetadataMap>;
+
+    /// Inserts a track at `index`, shifting all tracks after it.
+    fn insert(&mut self, index: usize, metadata: ::MetadataMap);
+
+    /// Removes the track `track_id` and returns its metadata.
+    fn remove(&mut self, track_id: &::TrackId) -> Option<::MetadataMap>;
+
+    /// Moves the track `track_id` to `index`.
+    fn reorder(&mut self, track_id: &::TrackId, index: usize);
+}
+
+/// A `TrackStore` which keeps the tracks in memory.
+#[derive(Debug, Default)]
+pub struct VecTrackStore {
+    tracks: Vec<::MetadataMap>,
+}
+
+impl VecTrackStore {
+    /// Creates a new, empty `VecTrackStore`.
+    pub fn new() -> Self {
+        VecTrackStore::default()
+    }
+
+    fn index_of(&self, track_id: &::TrackId) -> Option<usize> {
+        self.tracks.iter().position(|metadata| metadata.trackid() == track_id)
+    }
+}
+
+impl TrackStore for VecTrackStore {
+    fn track_ids(&self) -> Vec<::TrackId> {
+        self.tracks.iter().map(|metadata| metadata.trackid().clone()).collect()
+    }
+
+    fn lookup(&self, track_id: &::TrackId) -> Option<&::MetadataMap> {
+        self.tracks.iter().find(|metadata| metadata.trackid() == track_id)
+    }
+
+    fn insert(&mut self, index: usize, metadata: ::MetadataMap) {
+        let index = index.min(self.tracks.len());
+        self.tracks.insert(index, metadata);
+    }
+
+    fn remove(&mut self, track_id: &::TrackId) -> Option<::MetadataMap> {
+        let index = self.index_of(track_id)?;
+        Some(self.tracks.remove(index))
+    }
+
+    fn reorder(&mut self, track_id: &::TrackId, index: usize) {
+        if let Some(metadata) = self.remove(track_id) {
+            self.insert(index, metadata);
+        }
+    }
+}
+
+/// A change of a `TrackList`, which has not been announced yet.
+#[derive(Debug)]
+enum TrackListChange {
+    Added { metadata: ::MetadataMap, after_track: Option<::TrackId> },
+    Removed(::TrackId),
+    MetadataChanged(::TrackId),
+    Replaced,
+}
+
+/// The track list of an `MprisServer`.
+///
+/// All modifications generate the matching `TrackAdded`, `TrackRemoved`, `TrackListReplaced` and
+/// `TrackMetadataChanged` signals. The track ids are generated by the track list, so they are
+/// unique and never start with `/org/mpris`.
+pub struct TrackList {
+    store: Box<dyn TrackStore>,
+    next_id: u64,
+    can_edit_tracks: bool,
+    changes: Vec<TrackListChange>,
+}
+
+impl TrackList {
+    /// Creates a new `TrackList`, which keeps its tracks in `store`.
+    ///
+    /// Tracks which are already in `store` keep their ids.
+    pub fn new<S: TrackStore + 'static>(store: S) -> Self {
+        TrackList {
+            store: Box::new(store),
+            next_id: 0,
+            can_edit_tracks: false,
+            changes: Vec::new(),
+        }
+    }
+
+    /// Returns the ids of all tracks.
+    pub fn tracks(&self) -> Vec<::TrackId> {
+        self.store.track_ids()
+    }
+
+    /// Returns the metadata of the track `track_id`.
+    pub fn metadata(&self, track_id: &::TrackId) -> Option<&::MetadataMap> {
+        self.store.lookup(track_id)
+    }
+
+    /// Whether tracks may be added and removed by clients.
+    pub fn can_edit_tracks(&self) -> bool {
+        self.can_edit_tracks
+    }
+
+    /// Allows or forbids clients to add and remove tracks. The default is `false`.
+    pub fn set_can_edit_tracks(&mut self, can_edit_tracks: bool) {
+        self.can_edit_tracks = can_edit_tracks;
+    }
+
+    /// Adds a track after `after_track`, or at the beginning if it is `None`, and returns its id.
+    ///
+    /// The track id of `metadata` is replaced by a generated one.
+    pub fn add_track(&mut self, mut metadata: ::MetadataMap, after_track: Option<&::TrackId>) -> Result<::TrackId> {
+        let index = match after_track {
+            Some(after_track) => self.index_of(after_track)? + 1,
+            None => 0,
+        };
+        let track_id = self.generate_track_id();
+        metadata.set_trackid(track_id.clone());
+        self.store.insert(index, metadata.clone());
+        self.changes.push(TrackListChange::Added { metadata, after_track: after_track.cloned() });
+        Ok(track_id)
+    }
+
+    /// Removes the track `track_id` and returns its metadata.
+    pub fn remove_track(&mut self, track_id: &::TrackId) -> Result<::MetadataMap> {
+        let metadata = self.store.remove(track_id)
+            .ok_or_else(|| unknown_track(track_id))?;
+        self.changes.push(TrackListChange::Removed(track_id.clone()));
+        Ok(metadata)
+    }
+
+    /// Moves the track `track_id` after `after_track`, or to the beginning if it is `None`.
+    pub fn move_track(&mut self, track_id: &::TrackId, after_track: Option<&::TrackId>) -> Result<()> {
+        let from = self.index_of(track_id)?;
+        let index = match after_track {
+            Some(after_track) => {
+                let after = self.index_of(after_track)?;
+                if after < from { after + 1 } else { after }
+            }
+            None => 0,
+        };
+        self.store.reorder(track_id, index);
+        self.changes.push(TrackListChange::Replaced);
+        Ok(())
+    }
+
+    /// Replaces all tracks and returns the ids of the new tracks.
+    ///
+    /// The track ids of `tracks` are replaced by generated ones.
+    pub fn replace_tracks(&mut self, tracks: Vec<::MetadataMap>) -> Vec<::TrackId> {
+        for track_id in self.store.track_ids() {
+            self.store.remove(&track_id);
+        }
+        let mut track_ids = Vec::with_capacity(tracks.len());
+        for (index, mut metadata) in tracks.into_iter().enumerate() {
+            let track_id = self.generate_track_id();
+            metadata.set_trackid(track_id.clone());
+            self.store.insert(index, metadata);
+            track_ids.push(track_id);
+        }
+        self.changes.push(TrackListChange::Replaced);
+        track_ids
+    }
+
+    /// Replaces the metadata of the track `track_id`. Its track id stays the same.
+    pub fn set_metadata(&mut self, track_id: &::TrackId, mut metadata: ::MetadataMap) -> Result<()> {
+        let index = self.index_of(track_id)?;
+        metadata.set_trackid(track_id.clone());
+        self.store.remove(track_id);
+        self.store.insert(index, metadata);
+        self.changes.push(TrackListChange::MetadataChanged(track_id.clone()));
+        Ok(())
+    }
+
+    fn index_of(&self, track_id: &::TrackId) -> Result<usize> {
+        self.store.track_ids()
+            .iter()
+            .position(|id| id == track_id)
+            .ok_or_else(|| unknown_track(track_id))
+    }
+
+    /// Returns a new, unique track id.
+    fn generate_track_id(&mut self) -> ::TrackId {
+        loop {
+            self.next_id += 1;
+            let track_id = ::TrackId::from_str(&format!("/track/{}", self.next_id))
+                .expect("Generated track id is not a valid object path.");
+            if self.store.lookup(&track_id).is_none() {
+                return track_id;
+            }
+        }
+    }
+
+    /// Returns the signals for the changes since the last call. If the track list was replaced,
+    /// only a `TrackListReplaced` signal is returned.
+    fn take_signals(&mut self, current_track: &::TrackId) -> Vec<MprisSignal> {
+        let changes: Vec<TrackListChange> = self.changes.drain(..).collect();
+        if changes.iter().any(|change| matches!(*change, TrackListChange::Replaced)) {
+            return vec![MprisSignal::TrackListReplaced {
+                tracks: self.tracks(),
+                current_track: current_track.clone(),
+            }];
+        }
+
+        changes.into_iter()
+            .filter_map(|change| match change {
+                TrackListChange::Added { metadata, after_track } => {
+                    let after_track = after_track.unwrap_or_else(no_track);
+                    Some(MprisSignal::TrackAdded { metadata, after_track })
+                }
+                TrackListChange::Removed(track_id) => Some(MprisSignal::TrackRemoved { track_id }),
+                TrackListChange::MetadataChanged(track_id) => {
+                    // the track might have been removed in the meantime
+                    let metadata = self.metadata(&track_id)?.clone();
+                    Some(MprisSignal::TrackMetadataChanged { track_id, metadata })
+                }
+                TrackListChange::Replaced => None,
+            })
+            .collect()
+    }
+}
+
+fn no_track() -> ::TrackId {
+    ::TrackId::from_str(NO_TRACK).expect("NoTrack is not a valid object path.")
+}
+
+fn unknown_track(track_id: &::TrackId) -> Error {
+    ErrorKind::GeneralError(format!("Unknown track {}.", track_id.as_ref())).into()
+}
+
+
+/// The storage of the playlists of `Playlists`.
+pub trait PlaylistStore {
+    /// Returns all playlists in their user-defined order.
+    fn playlists(&self) -> Vec<::Playlist>;
+
+    /// Returns the playlist with the id `playlist_id`.
+    fn lookup(&self, playlist_id: &str) -> Option<&::Playlist>;
+
+    /// Inserts a playlist at `index`, shifting all playlists after it.
+    fn insert(&mut self, index: usize, playlist: ::Playlist);
+
+    /// Removes the playlist `playlist_id` and returns it.
+    fn remove(&mut self, playlist_id: &str) -> Option<::Playlist>;
+
+    /// Moves the playlist `playlist_id` to `index`.
+    fn reorder(&mut self, playlist_id: &str, index: usize);
+}
+
+/// A `PlaylistStore` which keeps the playlists in memory.
+#[derive(Debug, Default)]
+pub struct VecPlaylistStore {
+    playlists: Vec<::Playlist>,
+}
+
+impl VecPlaylistStore {
+    /// Creates a new, empty `VecPlaylistStore`.
+    pub fn new() -> Self {
+        VecPlaylistStore::default()
+    }
+}
+
+impl PlaylistStore for VecPlaylistStore {
+    fn playlists(&self) -> Vec<::Playlist> {
+        self.playlists.clone()
+    }
+
+    fn lookup(&self, playlist_id: &str) -> Option<&::Playlist> {
+        self.playlists.iter().find(|playlist| playlist.id == playlist_id)
+    }
+
+    fn insert(&mut self, index: usize, playlist: ::Playlist) {
+        let index = index.min(self.playlists.len());
+        self.playlists.insert(index, playlist);
+    }
+
+    fn remove(&mut self, playlist_id: &str) -> Option<::Playlist> {
+        let index = self.playlists.iter().position(|playlist| playlist.id == playlist_id)?;
+        Some(self.playlists.remove(index))
+    }
+
+    fn reorder(&mut self, playlist_id: &str, index: usize) {
+        if let Some(playlist) = self.remove(playlist_id) {
+            self.insert(index, playlist);
+        }
+    }
+}
+
+/// The playlists of an `MprisServer`.
+///
+/// Changes of a playlist generate a `PlaylistChanged` signal. The playlist ids are generated, so
+/// they are unique and never start with `/org/mpris`. `GetPlaylists` supports the alphabetical
+/// and the user-defined ordering.
+pub struct Playlists {
+    store: Box<dyn PlaylistStore>,
+    next_id: u64,
+    active_playlist: Option<String>,
+    changed: Vec<String>,
+}
+
+impl Playlists {
+    /// Creates new `Playlists`, which are kept in `store`.
+    pub fn new<S: PlaylistStore + 'static>(store: S) -> Self {
+        Playlists {
+            store: Box::new(store),
+            next_id: 0,
+            active_playlist: None,
+            changed: Vec::new(),
+        }
+    }
+
+    /// Returns all playlists in their user-defined order.
+    pub fn playlists(&self) -> Vec<::Playlist> {
+        self.store.playlists()
+    }
+
+    /// Returns the playlist `playlist_id`.
+    pub fn playlist(&self, playlist_id: &str) -> Option<&::Playlist> {
+        self.store.lookup(playlist_id)
+    }
+
+    /// Returns the number of playlists.
+    pub fn playlist_count(&self) -> u32 {
+        self.store.playlists().len() as u32
+    }
+
+    /// Returns the active playlist.
+    pub fn active_playlist(&self) -> Option<&::Playlist> {
+        self.store.lookup(self.active_playlist.as_ref()?)
+    }
+
+    /// Sets the active playlist, or unsets it if `playlist_id` is `None`.
+    pub fn set_active_playlist(&mut self, playlist_id: Option<&str>) -> Result<()> {
+        if let Some(playlist_id) = playlist_id {
+            if self.store.lookup(playlist_id).is_none() {
+                bail!(unknown_playlist(playlist_id));
+            }
+        }
+        self.active_playlist = playlist_id.map(str::to_string);
+        Ok(())
+    }
+
+    /// Adds a playlist at the end and returns its id.
+    pub fn add_playlist(&mut self, name: &str, icon: &str) -> String {
+        let playlist_id = self.generate_playlist_id();
+        let index = self.store.playlists().len();
+        self.store.insert(index, ::Playlist {
+            id: playlist_id.clone(),
+            name: name.to_string(),
+            icon: icon.to_string(),
+        });
+        playlist_id
+    }
+
+    /// Removes the playlist `playlist_id` and returns it. If it is the active playlist, there is
+    /// no active playlist afterwards.
+    pub fn remove_playlist(&mut self, playlist_id: &str) -> Result<::Playlist> {
+        let playlist = self.store.remove(playlist_id).ok_or_else(|| unknown_playlist(playlist_id))?;
+        if self.active_playlist.as_ref().map(|id| id as &str) == Some(playlist_id) {
+            self.active_playlist = None;
+        }
+        Ok(playlist)
+    }
+
+    /// Moves the playlist `playlist_id` to `index` in the user-defined order.
+    pub fn move_playlist(&mut self, playlist_id: &str, index: usize) -> Result<()> {
+        if self.store.lookup(playlist_id).is_none() {
+            bail!(unknown_playlist(playlist_id));
+        }
+        self.store.reorder(playlist_id, index);
+        Ok(())
+    }
+
+    /// Changes the name and icon of the playlist with the id `playlist.id`.
+    pub fn set_playlist(&mut self, playlist: ::Playlist) -> Result<()> {
+        let index = self.store.playlists()
+            .iter()
+            .position(|p| p.id == playlist.id)
+            .ok_or_else(|| unknown_playlist(&playlist.id))?;
+        self.store.remove(&playlist.id);
+        self.changed.push(playlist.id.clone());
+        self.store.insert(index, playlist);
+        Ok(())
+    }
+
+    /// Returns the active playlist as `MaybePlaylist`.
+    fn maybe_active_playlist(&self) -> ::MaybePlaylist {
+        match self.active_playlist() {
+            Some(playlist) => ::MaybePlaylist { valid: true, playlist: playlist.clone() },
+            None => ::MaybePlaylist {
+                valid: false,
+                playlist: ::Playlist { id: "/".to_string(), name: String::new(), icon: String::new() },
+            },
+        }
+    }
+
+    /// Returns at most `max_count` playlists starting at `index` in the given order.
+    fn get_playlists(&self, index: u32, max_count: u32, order: ::PlaylistOrdering, reverse_order: bool) -> Vec<::Playlist> {
+        let mut playlists = self.store.playlists();
+        // unsupported orderings fall back to the user-defined one
+        if order == ::PlaylistOrdering::Alphabetical {
+            playlists.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        if reverse_order {
+            playlists.reverse();
+        }
+        playlists.into_iter()
+            .skip(index as usize)
+            .take(max_count as usize)
+            .collect()
+    }
+
+    /// Returns a new, unique playlist id.
+    fn generate_playlist_id(&mut self) -> String {
+        loop {
+            self.next_id += 1;
+            let playlist_id = format!("/playlist/{}", self.next_id);
+            if self.store.lookup(&playlist_id).is_none() {
+                return playlist_id;
+            }
+        }
+    }
+
+    /// Returns the signals for the changes since the last call.
+    fn take_signals(&mut self) -> Vec<MprisSignal> {
+        let changed: Vec<String> = self.changed.drain(..).collect();
+        changed.iter()
+            .filter_map(|playlist_id| self.store.lookup(playlist_id))
+            .map(|playlist| MprisSignal::PlaylistChanged { playlist: playlist.clone() })
+            .collect()
+    }
+}
+
+fn unknown_playlist(playlist_id: &str) -> Error {
+    ErrorKind::GeneralError(format!("Unknown playlist {}.", playlist_id)).into()
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use client::MprisClient;
+    use test_support::session_bus_address;
+
+    struct UnitPlayer {
+        playback_status: ::PlaybackStatus,
+        volume: f64,
+        fullscreen: bool,
+        position: i64,
+    }
+
+    impl RootHandler for UnitPlayer {
+        fn identity(&self) -> String {
+            "Unit player".to_string()
+        }
+
+        fn fullscreen(&self) -> Option<bool> {
+            Some(self.fullscreen)
+        }
+    }
+
+    impl PlayerHandler for UnitPlayer {
+        fn playback_status(&self) -> ::PlaybackStatus {
+            self.playback_status
+        }
+
+        fn metadata(&self) -> ::MetadataMap {
+            ::MetadataMap::builder().trackid("/track/1").build().unwrap()
+        }
+
+        fn volume(&self) -> ::Volume {
+            self.volume
+        }
+
+        fn position(&self) -> i64 {
+            self.position
+        }
+    }
+
+    fn new_server(player_name: &str) -> MprisServer<UnitPlayer> {
+        session_bus_address().unwrap();
+        let player = UnitPlayer {
+            playback_status: ::PlaybackStatus::Paused,
+            volume: 0.5,
+            fullscreen: false,
+            position: 0,
+        };
+        MprisServer::new(player_name, player).unwrap()
+    }
+
+    fn position_state(instant: Instant,
+                      position: i64,
+                      rate: ::PlaybackRate,
+                      playback_status: ::PlaybackStatus,
+                      track_id: &str)
+                      -> PositionState {
+        PositionState { instant, position, rate, playback_status, track_id: track_id.parse().unwrap() }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_MprisServer_update() {
+        let mut server = new_server("unit_update");
+        let client = MprisClient::new("unit_update", 1000).unwrap();
+
+        server.update(|player| {
+            player.playback_status = ::PlaybackStatus::Playing;
+            player.volume = 0.2;
+            player.fullscreen = true;
+        }).unwrap();
+
+        let mut root = Vec::new();
+        let mut player = Vec::new();
+        for signal in client.signals(500) {
+            match signal {
+                MprisSignal::PropertiesChanged { ref interface, ref changed_properties, .. }
+                    if interface == "org.mpris.MediaPlayer2" => root.push(changed_properties.clone()),
+                MprisSignal::PropertiesChanged { ref interface, ref changed_properties, .. }
+                    if interface == "org.mpris.MediaPlayer2.Player" => player.push(changed_properties.clone()),
+                signal => panic!("unexpected signal: {:?}", signal),
+            }
+        }
+        // all changes of one update are announced with one signal per interface
+        assert_eq!(root, vec![vec![ChangedProperty::Fullscreen(true)]]);
+        assert_eq!(player.len(), 1);
+        assert_eq!(player[0].len(), 2);
+        assert!(player[0].contains(&ChangedProperty::PlaybackStatus(::PlaybackStatus::Playing)));
+        assert!(player[0].contains(&ChangedProperty::Volume(0.2)));
+
+        // a position jump while paused is announced with Seeked
+        server.update(|player| {
+            player.playback_status = ::PlaybackStatus::Paused;
+            player.position = 3_000_000;
+        }).unwrap();
+        let seeked: Vec<_> = client.signals(500)
+            .filter(|signal| match *signal {
+                MprisSignal::Seeked { .. } => true,
+                _ => false,
+            })
+            .collect();
+        assert_eq!(seeked, vec![MprisSignal::Seeked { position: 3_000_000 }]);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_MprisServer_seeked_position() {
+        use PlaybackStatus::*;
+
+        let server = new_server("unit_seeked_position");
+        let then = Instant::now();
+        let now = then + Duration::from_secs(1);
+
+        // the position advanced as expected at the rate
+        let old = position_state(then, 0, 1.0, Playing, "/a");
+        assert_eq!(server.seeked_position(&old, &position_state(now, 1_000_000, 1.0, Playing, "/a")), None);
+        assert_eq!(server.seeked_position(&old, &position_state(now, 1_100_000, 1.0, Playing, "/a")), None);
+        let old = position_state(then, 0, 2.0, Playing, "/a");
+        assert_eq!(server.seeked_position(&old, &position_state(now, 2_000_000, 2.0, Playing, "/a")), None);
+        let old = position_state(then, 500_000, 1.0, Paused, "/a");
+        assert_eq!(server.seeked_position(&old, &position_state(now, 500_000, 1.0, Paused, "/a")), None);
+
+        // the position jumped
+        let old = position_state(then, 0, 1.0, Playing, "/a");
+        assert_eq!(server.seeked_position(&old, &position_state(now, 5_000_000, 1.0, Playing, "/a")),
+                   Some(5_000_000));
+        let old = position_state(then, 0, 2.0, Playing, "/a");
+        assert_eq!(server.seeked_position(&old, &position_state(now, 1_000_000, 2.0, Playing, "/a")),
+                   Some(1_000_000));
+        let old = position_state(then, 500_000, 1.0, Paused, "/a");
+        assert_eq!(server.seeked_position(&old, &position_state(now, 0, 1.0, Paused, "/a")), Some(0));
+
+        // a new track starts at 0, and a stopped player has no position
+        let old = position_state(then, 4_000_000, 1.0, Playing, "/a");
+        assert_eq!(server.seeked_position(&old, &position_state(now, 0, 1.0, Playing, "/b")), None);
+        assert_eq!(server.seeked_position(&old, &position_state(now, 3_000_000, 1.0, Playing, "/b")),
+                   Some(3_000_000));
+        assert_eq!(server.seeked_position(&old, &position_state(now, 9_000_000, 1.0, Stopped, "/a")), None);
+    }
+}
@@ -0,0 +1,78 @@
+//! This module contains a cached model of the state of a media player.
+//!
+//! A `PlayerState` is filled with one `GetAll` call per interface and then kept in sync with the
+//! `PropertiesChanged` signals of the media player, so that reading it does not need any D-Bus
+//! round trips.
+use client::{MprisClient, MprisSignal};
+use errors::*;
+use properties::{PlayerProperties, RootProperties};
+
+
+/// Snapshot of the properties of the `org.mpris.MediaPlayer2` and
+/// `org.mpris.MediaPlayer2.Player` interfaces of a media player.
+///
+/// A field of `root` or `player` is `None` if the media player does not provide the property, or
+/// if its value could not be read.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PlayerState {
+    /// The properties of the `org.mpris.MediaPlayer2` interface.
+    pub root: RootProperties,
+    /// The properties of the `org.mpris.MediaPlayer2.Player` interface.
+    ///
+    /// The `position` is the one which was last read or announced by `Seeked`. The media player
+    /// does not announce the progress of the playback, so this value gets out of date while
+    /// playing.
+    pub player: PlayerProperties,
+}
+
+impl PlayerState {
+    /// Reads the state of a media player with one `GetAll` call per interface.
+    ///
+    /// The metadata is decoded in the decode mode of `client`, see `MprisClient::set_decode_mode`.
+    pub fn new(client: &MprisClient) -> Result<Self> {
+        let mode = client.decode_mode();
+        Ok(PlayerState {
+            root: RootProperties::from_props(client.get_all_props("org.mpris.MediaPlayer2")?, mode),
+            player: PlayerProperties::from_props(client.get_all_props("org.mpris.MediaPlayer2.Player")?, mode),
+        })
+    }
+
+    /// Updates the state from a signal of the media player.
+    ///
+    /// The changed properties of `PropertiesChanged` are stored, while the invalidated ones are
+    /// read again from the media player. `Seeked` updates the position. Other signals are ignored.
+    ///
+    /// Every invalidated property is read, even if reading another one failed. The properties
+    /// which could not be read are cleared, and the first error is returned afterwards.
+    ///
+    /// Returns whether the signal concerned the state.
+    pub fn update(&mut self, client: &MprisClient, signal: &MprisSignal) -> Result<bool> {
+        match *signal {
+            MprisSignal::PropertiesChanged { ref interface, ref changed_properties, ref invalidated_properties } => {
+                let mode = client.decode_mode();
+                let fetch = |name: &str| client.get_optional_variant_prop(interface, name);
+                match interface as &str {
+                    "org.mpris.MediaPlayer2" => {
+                        for property in changed_properties {
+                            self.root.apply(property.clone());
+                        }
+                        self.root.reload(invalidated_properties, mode, fetch)?;
+                    }
+                    "org.mpris.MediaPlayer2.Player" => {
+                        for property in changed_properties {
+                            self.player.apply(property.clone());
+                        }
+                        self.player.reload(invalidated_properties, mode, fetch)?;
+                    }
+                    _ => return Ok(false),
+                }
+                Ok(true)
+            }
+            MprisSignal::Seeked { position } => {
+                self.player.position = Some(position);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
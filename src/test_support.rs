@@ -0,0 +1,520 @@
+//! This module contains a hermetic test environment: a private session bus and a scriptable mock
+//! media player. It is only available with the `test-support` feature.
+//!
+//! The private bus is started once per process by `session_bus_address`. Afterwards,
+//! `MprisClient`, `MprisServer` and the other types of this crate connect to it instead of the
+//! user's session bus. The bus is shut down when the process exits.
+//!
+//! ```no_run
+//! use mpris::client::{ChangedProperty, MprisClient};
+//! use mpris::test_support::MockPlayer;
+//!
+//! let mock = MockPlayer::start().unwrap();
+//! mock.unset("Fullscreen");
+//! mock.set(ChangedProperty::Identity("My player".to_string()));
+//!
+//! let client = MprisClient::new(mock.player_name(), 1000).unwrap();
+//! assert_eq!(client.root.fullscreen().unwrap(), None);
+//! ```
+use dbus::{Connection, Message};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, mpsc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use client::{ChangedProperty, MprisSignal, open_session_bus};
+use errors::*;
+use server::{MprisServer, PlayerHandler, Playlists, PlaylistsHandler, RootHandler, TrackList,
+             TrackListHandler, VecPlaylistStore, VecTrackStore};
+
+
+/// The player name of a `MockPlayer`, unless another one is given.
+pub const DEFAULT_PLAYER_NAME: &str = "mock";
+
+/// The address of the private bus, or why it could not be started.
+static SESSION_BUS: OnceLock<::std::result::Result<String, String>> = OnceLock::new();
+
+/// Keeps the private bus alive. When this pipe is closed, i.e. when the process exits, the bus is
+/// shut down.
+static SESSION_BUS_LIFELINE: Mutex<Option<ChildStdin>> = Mutex::new(None);
+
+/// Serializes the mock players with the default name.
+static DEFAULT_PLAYER_LOCK: Mutex<()> = Mutex::new(());
+
+/// Starts the private session bus, if it is not running yet, and returns its address.
+///
+/// All session bus connections which this crate opens afterwards use the private bus. Other
+/// programs, like child processes, can be pointed at it with `DBUS_SESSION_BUS_ADDRESS`.
+pub fn session_bus_address() -> Result<String> {
+    SESSION_BUS
+        .get_or_init(|| start_session_bus().map_err(|err| err.to_string()))
+        .clone()
+        .map_err(|err| ErrorKind::GeneralError(format!("Could not start a private session bus: {}", err)).into())
+}
+
+/// Opens a new connection to the private session bus, which is started if it is not running yet.
+pub fn session_connection() -> Result<Connection> {
+    session_bus_address()?;
+    open_session_bus()
+}
+
+/// Returns the address of the private session bus, if it has been started.
+pub(crate) fn started_bus_address() -> Option<String> {
+    SESSION_BUS.get().and_then(|bus| bus.clone().ok())
+}
+
+/// Spawns `dbus-daemon --session`, which is killed as soon as its stdin is closed.
+fn start_session_bus() -> Result<String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg("dbus-daemon --session --nofork --print-address & pid=$!; read _; kill $pid")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .chain_err(|| "Could not spawn dbus-daemon. Is it installed?")?;
+
+    let stdout = child.stdout.take().chain_err(|| "Could not read the output of dbus-daemon.")?;
+    let mut address = String::new();
+    BufReader::new(stdout).read_line(&mut address).chain_err(|| "Could not read the bus address.")?;
+    if address.trim().is_empty() {
+        bail!("dbus-daemon did not print an address.");
+    }
+
+    *SESSION_BUS_LIFELINE.lock().unwrap_or_else(|err| err.into_inner()) = child.stdin.take();
+    Ok(address.trim().to_string())
+}
+
+
+/// The state of a `MockPlayer`, which is shared with its server thread.
+#[derive(Debug, Default)]
+struct MockState {
+    properties: HashMap<String, ChangedProperty>,
+    calls: Vec<String>,
+    signals: Vec<(Instant, Message)>,
+    stopped: bool,
+}
+
+/// The default properties of a `MockPlayer`.
+fn default_properties() -> Vec<ChangedProperty> {
+    use client::ChangedProperty::*;
+
+    vec![
+        CanQuit(true),
+        Fullscreen(false),
+        CanSetFullscreen(true),
+        CanRaise(true),
+        Identity("Mock player".to_string()),
+        DesktopEntry("mock".to_string()),
+        SupportedUriSchemes(vec!["file".to_string()]),
+        SupportedMimeTypes(vec!["audio/mpeg".to_string()]),
+        PlaybackStatus(::PlaybackStatus::Stopped),
+        LoopStatus(::LoopStatus::None),
+        Rate(1.0),
+        Shuffle(false),
+        Metadata(mock_metadata("/track/1", "Mock track")),
+        Volume(1.0),
+        Position(0),
+        MinimumRate(1.0),
+        MaximumRate(1.0),
+        CanGoNext(true),
+        CanGoPrevious(true),
+        CanPlay(true),
+        CanPause(true),
+        CanSeek(true),
+        CanControl(true),
+    ]
+}
+
+/// Builds metadata with a track id and a title.
+fn mock_metadata(track_id: &str, title: &str) -> ::MetadataMap {
+    ::MetadataMap::builder()
+        .trackid(track_id)
+        .title(title)
+        .build()
+        .expect("Could not build mock metadata.")
+}
+
+
+/// The handler of a `MockPlayer`, which lives in the server thread.
+struct MockHandler {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockHandler {
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn get(&self, name: &str) -> Option<ChangedProperty> {
+        self.state().properties.get(name).cloned()
+    }
+
+    fn set(&self, prop: &ChangedProperty) {
+        if let Some(name) = prop.name() {
+            self.state().properties.insert(name.to_string(), prop.clone());
+        }
+    }
+
+    fn record(&self, call: String) -> Result<()> {
+        self.state().calls.push(call);
+        Ok(())
+    }
+}
+
+/// Implements the getter of a mandatory property, which falls back to `$default` if it is unset.
+macro_rules! mock_getter {
+    ($name:ident, $prop:ident, $ty:ty, $default:expr) => {
+        fn $name(&self) -> $ty {
+            match self.get(stringify!($prop)) {
+                Some(ChangedProperty::$prop(value)) => value,
+                _ => $default,
+            }
+        }
+    };
+}
+
+/// Implements the getter of an optional property, which is absent if it is unset.
+macro_rules! mock_optional_getter {
+    ($name:ident, $prop:ident, $ty:ty) => {
+        fn $name(&self) -> Option<$ty> {
+            match self.get(stringify!($prop)) {
+                Some(ChangedProperty::$prop(value)) => Some(value),
+                _ => None,
+            }
+        }
+    };
+}
+
+impl RootHandler for MockHandler {
+    fn raise(&mut self) -> Result<()> { self.record("Raise".to_string()) }
+    fn quit(&mut self) -> Result<()> { self.record("Quit".to_string()) }
+    fn set_fullscreen(&mut self, fullscreen: bool) -> Result<()> {
+        self.set(&ChangedProperty::Fullscreen(fullscreen));
+        Ok(())
+    }
+
+    mock_getter!(can_quit, CanQuit, bool, false);
+    mock_optional_getter!(fullscreen, Fullscreen, bool);
+    mock_optional_getter!(can_set_fullscreen, CanSetFullscreen, bool);
+    mock_getter!(can_raise, CanRaise, bool, false);
+    mock_getter!(identity, Identity, String, String::new());
+    mock_optional_getter!(desktop_entry, DesktopEntry, String);
+    mock_getter!(supported_uri_schemes, SupportedUriSchemes, Vec<String>, Vec::new());
+    mock_getter!(supported_mime_types, SupportedMimeTypes, Vec<String>, Vec::new());
+}
+
+impl PlayerHandler for MockHandler {
+    fn next(&mut self) -> Result<()> { self.record("Next".to_string()) }
+    fn previous(&mut self) -> Result<()> { self.record("Previous".to_string()) }
+
+    fn pause(&mut self) -> Result<()> {
+        self.set(&ChangedProperty::PlaybackStatus(::PlaybackStatus::Paused));
+        self.record("Pause".to_string())
+    }
+
+    fn play_pause(&mut self) -> Result<()> {
+        let playback_status = match self.playback_status() {
+            ::PlaybackStatus::Playing => ::PlaybackStatus::Paused,
+            _ => ::PlaybackStatus::Playing,
+        };
+        self.set(&ChangedProperty::PlaybackStatus(playback_status));
+        self.record("PlayPause".to_string())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.set(&ChangedProperty::PlaybackStatus(::PlaybackStatus::Stopped));
+        self.set(&ChangedProperty::Position(0));
+        self.record("Stop".to_string())
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.set(&ChangedProperty::PlaybackStatus(::PlaybackStatus::Playing));
+        self.record("Play".to_string())
+    }
+
+    fn seek(&mut self, offset: i64) -> Result<()> {
+        let position = (self.position() + offset).max(0);
+        self.set(&ChangedProperty::Position(position));
+        self.record(format!("Seek({})", offset))
+    }
+
+    fn set_position(&mut self, track_id: &::TrackId, position: i64) -> Result<()> {
+        self.set(&ChangedProperty::Position(position));
+        self.record(format!("SetPosition({}, {})", track_id.as_ref(), position))
+    }
+
+    fn open_uri(&mut self, uri: &str) -> Result<()> {
+        self.record(format!("OpenUri({})", uri))
+    }
+
+    fn set_loop_status(&mut self, loop_status: ::LoopStatus) -> Result<()> {
+        self.set(&ChangedProperty::LoopStatus(loop_status));
+        Ok(())
+    }
+
+    fn set_rate(&mut self, rate: ::PlaybackRate) -> Result<()> {
+        self.set(&ChangedProperty::Rate(rate));
+        Ok(())
+    }
+
+    fn set_shuffle(&mut self, shuffle: bool) -> Result<()> {
+        self.set(&ChangedProperty::Shuffle(shuffle));
+        Ok(())
+    }
+
+    fn set_volume(&mut self, volume: ::Volume) -> Result<()> {
+        self.set(&ChangedProperty::Volume(volume));
+        Ok(())
+    }
+
+    mock_getter!(playback_status, PlaybackStatus, ::PlaybackStatus, ::PlaybackStatus::Stopped);
+    mock_optional_getter!(loop_status, LoopStatus, ::LoopStatus);
+    mock_getter!(rate, Rate, ::PlaybackRate, 1.0);
+    mock_optional_getter!(shuffle, Shuffle, bool);
+    mock_getter!(metadata, Metadata, ::MetadataMap, mock_metadata("/org/mpris/MediaPlayer2/TrackList/NoTrack", ""));
+    mock_getter!(volume, Volume, ::Volume, 1.0);
+    mock_getter!(position, Position, i64, 0);
+    mock_getter!(minimum_rate, MinimumRate, ::PlaybackRate, 1.0);
+    mock_getter!(maximum_rate, MaximumRate, ::PlaybackRate, 1.0);
+    mock_getter!(can_go_next, CanGoNext, bool, false);
+    mock_getter!(can_go_previous, CanGoPrevious, bool, false);
+    mock_getter!(can_play, CanPlay, bool, false);
+    mock_getter!(can_pause, CanPause, bool, false);
+    mock_getter!(can_seek, CanSeek, bool, false);
+    mock_getter!(can_control, CanControl, bool, false);
+}
+
+impl TrackListHandler for MockHandler {
+    fn load_track(&mut self, uri: &str) -> Result<::MetadataMap> {
+        self.record(format!("AddTrack({})", uri))?;
+        Ok(mock_metadata("/track/new", uri))
+    }
+
+    fn go_to(&mut self, track_id: &::TrackId, metadata: &::MetadataMap) -> Result<()> {
+        self.set(&ChangedProperty::Metadata(metadata.clone()));
+        self.record(format!("GoTo({})", track_id.as_ref()))
+    }
+
+    fn remove_track(&mut self, track_id: &::TrackId) -> Result<()> {
+        self.record(format!("RemoveTrack({})", track_id.as_ref()))
+    }
+}
+
+impl PlaylistsHandler for MockHandler {
+    fn activate_playlist(&mut self, playlist: &::Playlist) -> Result<()> {
+        self.record(format!("ActivatePlaylist({})", playlist.id))
+    }
+}
+
+
+/// Builds a `MockPlayer`.
+#[derive(Debug)]
+pub struct MockPlayerBuilder {
+    player_name: String,
+    tracks: Option<Vec<String>>,
+    playlists: Option<Vec<String>>,
+}
+
+impl MockPlayerBuilder {
+    /// Sets the player name, so the mock player is exported as
+    /// `org.mpris.MediaPlayer2.playerName`. The default is `DEFAULT_PLAYER_NAME`.
+    ///
+    /// Mock players with the default name run one after another, while mock players with other
+    /// names may run in parallel.
+    pub fn player_name(mut self, player_name: &str) -> Self {
+        self.player_name = player_name.to_string();
+        self
+    }
+
+    /// Exports a track list with tracks of the given titles.
+    pub fn track_list(mut self, titles: &[&str]) -> Self {
+        self.tracks = Some(titles.iter().map(|title| title.to_string()).collect());
+        self
+    }
+
+    /// Exports the playlists of the given names.
+    pub fn playlists(mut self, names: &[&str]) -> Self {
+        self.playlists = Some(names.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    /// Starts the private session bus, if necessary, and exports the mock player on it.
+    ///
+    /// With the default player name, this blocks until all other mock players with the default
+    /// name are dropped.
+    pub fn start(self) -> Result<MockPlayer> {
+        session_bus_address()?;
+
+        let guard = if self.player_name == DEFAULT_PLAYER_NAME {
+            Some(DEFAULT_PLAYER_LOCK.lock().unwrap_or_else(|err| err.into_inner()))
+        } else {
+            None
+        };
+
+        let state = Arc::new(Mutex::new(MockState::default()));
+        {
+            let handler = MockHandler { state: state.clone() };
+            for prop in default_properties() {
+                handler.set(&prop);
+            }
+        }
+
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let thread_state = state.clone();
+        let player_name = self.player_name.clone();
+        let (tracks, playlists) = (self.tracks, self.playlists);
+        let thread = thread::spawn(move || {
+            let handler = MockHandler { state: thread_state.clone() };
+            let mut server = match MprisServer::new(&player_name, handler) {
+                Ok(server) => server,
+                Err(err) => {
+                    let _ = ready_tx.send(Err(err.to_string()));
+                    return;
+                }
+            };
+            if let Some(titles) = tracks {
+                let mut track_list = TrackList::new(VecTrackStore::new());
+                track_list.set_can_edit_tracks(true);
+                let tracks = titles.iter()
+                    .map(|title| mock_metadata("/track/new", title))
+                    .collect();
+                track_list.replace_tracks(tracks);
+                server.enable_track_list(track_list);
+            }
+            if let Some(names) = playlists {
+                let mut playlists = Playlists::new(VecPlaylistStore::new());
+                for name in names {
+                    playlists.add_playlist(&name, "");
+                }
+                server.enable_playlists(playlists);
+            }
+            let _ = ready_tx.send(server.emit_changes().map_err(|err| err.to_string()));
+
+            run_mock_server(&mut server, &thread_state);
+        });
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => bail!(ErrorKind::GeneralError(format!("Could not start the mock player: {}", err))),
+            Err(_) => bail!(ErrorKind::GeneralError("The mock player thread died.".to_string())),
+        }
+
+        Ok(MockPlayer {
+            player_name: self.player_name,
+            state,
+            thread: Some(thread),
+            _guard: guard,
+        })
+    }
+}
+
+/// Handles requests and sends the scripted signals until the mock player is stopped.
+fn run_mock_server(server: &mut MprisServer<MockHandler>, state: &Arc<Mutex<MockState>>) {
+    loop {
+        if server.process(10).is_err() {
+            return;
+        }
+
+        let (due, stopped) = {
+            let mut state = state.lock().unwrap_or_else(|err| err.into_inner());
+            let now = Instant::now();
+            let (due, pending) = state.signals.drain(..).partition(|&(at, _)| at <= now);
+            state.signals = pending;
+            (due, state.stopped)
+        };
+        for (_, msg) in due {
+            let _ = server.send_message(msg);
+        }
+        if stopped {
+            return;
+        }
+    }
+}
+
+
+/// A mock media player, which is exported on the private session bus in its own thread.
+///
+/// It starts with a complete set of properties, which can be changed with `set` and removed with
+/// `unset`. Changes are announced with `PropertiesChanged` as a real player would do. Method calls
+/// are recorded, and some of them change the state, e.g. `Play` sets the `PlaybackStatus` to
+/// `Playing`. The player is removed from the bus when it is dropped.
+pub struct MockPlayer {
+    player_name: String,
+    state: Arc<Mutex<MockState>>,
+    thread: Option<JoinHandle<()>>,
+    _guard: Option<MutexGuard<'static, ()>>,
+}
+
+impl MockPlayer {
+    /// Starts a mock player with the default name and neither a track list nor playlists.
+    pub fn start() -> Result<MockPlayer> {
+        MockPlayer::builder().start()
+    }
+
+    /// Returns a builder for a mock player.
+    pub fn builder() -> MockPlayerBuilder {
+        MockPlayerBuilder {
+            player_name: DEFAULT_PLAYER_NAME.to_string(),
+            tracks: None,
+            playlists: None,
+        }
+    }
+
+    /// Returns the player name, which is passed to `MprisClient::new`.
+    pub fn player_name(&self) -> &str {
+        &self.player_name
+    }
+
+    /// Returns the well-known bus name of the mock player.
+    pub fn bus_name(&self) -> String {
+        format!("org.mpris.MediaPlayer2.{}", self.player_name)
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Sets a property.
+    pub fn set(&self, prop: ChangedProperty) {
+        if let Some(name) = prop.name() {
+            self.state().properties.insert(name.to_string(), prop);
+        }
+    }
+
+    /// Removes the property `name`, so that an optional property is absent.
+    pub fn unset(&self, name: &str) {
+        self.state().properties.remove(name);
+    }
+
+    /// Returns the property `name`, if it is set.
+    pub fn get(&self, name: &str) -> Option<ChangedProperty> {
+        self.state().properties.get(name).cloned()
+    }
+
+    /// Returns the method calls received so far, e.g. `Play` or `Seek(-1000)`.
+    pub fn calls(&self) -> Vec<String> {
+        self.state().calls.clone()
+    }
+
+    /// Emits `signal` as it is, regardless of the player's state.
+    pub fn emit(&self, signal: MprisSignal) {
+        self.emit_after(signal, Duration::from_secs(0));
+    }
+
+    /// Emits `signal` as it is after `delay`.
+    pub fn emit_after(&self, signal: MprisSignal, delay: Duration) {
+        self.state().signals.push((Instant::now() + delay, signal.to_message()));
+    }
+}
+
+impl Drop for MockPlayer {
+    fn drop(&mut self) {
+        self.state().stopped = true;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
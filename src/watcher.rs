@@ -0,0 +1,377 @@
+//! This module contains the discovery of media players.
+//!
+//! A `PlayerWatcher` follows the `org.freedesktop.DBus.NameOwnerChanged` signal for all names
+//! starting with `org.mpris.MediaPlayer2.`, so media players can be added and removed as they come
+//! and go, instead of polling `MprisClient::list_players`.
+//!
+//! A `SignalWatcher` receives the signals of all media players at once and tags each of them with
+//! the well-known bus name of the player which sent it. Its `events` additionally report the media
+//! players as they appear and vanish.
+use dbus::{Connection, Message, MessageType};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use client::{MprisSignal, MPRIS_MATCH_RULES, open_session_bus};
+use decode::DecodeMode;
+use errors::*;
+
+
+/// The prefix of the bus names of all media players.
+pub(crate) const MPRIS_BUS_NAME_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+/// The match rule for the ownership changes of media player bus names.
+pub(crate) const NAME_OWNER_CHANGED_MATCH_RULE: &str =
+    "type='signal',sender='org.freedesktop.DBus',interface='org.freedesktop.DBus',\
+     member='NameOwnerChanged',arg0namespace='org.mpris.MediaPlayer2'";
+
+/// Enum for the changes of the media players on the bus.
+///
+/// `bus_name` is the well-known bus name of the media player, e.g.
+/// `org.mpris.MediaPlayer2.vlc`, while the unique bus names, e.g. `:1.42`, identify the connection
+/// which owns it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerEvent {
+    /// A media player appeared on the bus.
+    PlayerAppeared {
+        bus_name: String,
+        unique_bus_name: String,
+    },
+    /// A media player vanished from the bus.
+    PlayerVanished {
+        bus_name: String,
+        unique_bus_name: String,
+    },
+    /// The bus name of a media player was taken over by another connection, e.g. because the
+    /// media player was replaced by a new instance.
+    PlayerOwnerChanged {
+        bus_name: String,
+        old_unique_bus_name: String,
+        new_unique_bus_name: String,
+    },
+}
+
+impl PlayerEvent {
+    /// Decodes a `NameOwnerChanged` signal of a media player's bus name.
+    pub(crate) fn from_message(msg: &Message) -> Option<Self> {
+        if msg.msg_type() != MessageType::Signal
+            || msg.sender().as_ref().map(|sender| sender as &str) != Some("org.freedesktop.DBus")
+            || msg.interface().as_ref().map(|interface| interface as &str) != Some("org.freedesktop.DBus")
+            || msg.member().as_ref().map(|member| member as &str) != Some("NameOwnerChanged") {
+            return None;
+        }
+
+        let (bus_name, old_owner, new_owner) = msg.read3::<String, String, String>().ok()?;
+        if !bus_name.starts_with(MPRIS_BUS_NAME_PREFIX) {
+            return None;
+        }
+        match (old_owner.is_empty(), new_owner.is_empty()) {
+            (true, false) => Some(PlayerEvent::PlayerAppeared { bus_name, unique_bus_name: new_owner }),
+            (false, true) => Some(PlayerEvent::PlayerVanished { bus_name, unique_bus_name: old_owner }),
+            (false, false) => {
+                Some(PlayerEvent::PlayerOwnerChanged {
+                    bus_name,
+                    old_unique_bus_name: old_owner,
+                    new_unique_bus_name: new_owner,
+                })
+            }
+            (true, true) => None,
+        }
+    }
+
+    /// Returns the well-known bus name of the media player.
+    pub fn bus_name(&self) -> &str {
+        match *self {
+            PlayerEvent::PlayerAppeared { ref bus_name, .. }
+            | PlayerEvent::PlayerVanished { ref bus_name, .. }
+            | PlayerEvent::PlayerOwnerChanged { ref bus_name, .. } => bus_name,
+        }
+    }
+
+    /// Returns the player name, which is passed to `MprisClient::new`.
+    pub fn player_name(&self) -> &str {
+        &self.bus_name()[MPRIS_BUS_NAME_PREFIX.len()..]
+    }
+}
+
+
+/// Watches the media players on the session bus.
+pub struct PlayerWatcher {
+    conn: Connection,
+    queued_events: VecDeque<PlayerEvent>,
+}
+
+impl PlayerWatcher {
+    /// Creates a new `PlayerWatcher` instance.
+    ///
+    /// The media players which are already running are reported as `PlayerAppeared` first, so
+    /// that no player is missed in between.
+    ///
+    /// `timeout_ms` specifies the maximum time a D-Bus method call blocks. The value -1 disables
+    /// the timeout.
+    pub fn new(timeout_ms: i32) -> Result<Self> {
+        let conn = open_session_bus()?;
+        conn.add_match(NAME_OWNER_CHANGED_MATCH_RULE)?;
+
+        let queued_events = list_player_owners(&conn, timeout_ms)?
+            .into_iter()
+            .map(|(bus_name, unique_bus_name)| PlayerEvent::PlayerAppeared { bus_name, unique_bus_name })
+            .collect();
+        Ok(PlayerWatcher { conn, queued_events })
+    }
+
+    /// Returns an iterator of `PlayerEvent`s. `timeout_ms` specifies the maximum amount of time
+    /// the iterator blocks (and waits for new events).
+    pub fn events(&mut self, timeout_ms: u32) -> PlayerEvents<'_> {
+        PlayerEvents { watcher: self, timeout_ms }
+    }
+}
+
+/// Returns the well-known and unique bus names of all media players.
+///
+/// Players which vanish while they are listed are skipped.
+pub(crate) fn list_player_owners(conn: &Connection, timeout_ms: i32) -> Result<Vec<(String, String)>> {
+    let msg = Message::new_method_call("org.freedesktop.DBus",
+                                       "/org/freedesktop/DBus",
+                                       "org.freedesktop.DBus",
+                                       "ListNames")
+        .expect("Could not construct method call.");
+    let reply = conn.send_with_reply_and_block(msg, timeout_ms)?;
+    let bus_names: Vec<String> = reply.read1().chain_err(|| "Could not typecast return value")?;
+
+    let mut owners = Vec::new();
+    for bus_name in bus_names.into_iter().filter(|bus_name| bus_name.starts_with(MPRIS_BUS_NAME_PREFIX)) {
+        let msg = Message::new_method_call("org.freedesktop.DBus",
+                                           "/org/freedesktop/DBus",
+                                           "org.freedesktop.DBus",
+                                           "GetNameOwner")
+            .expect("Could not construct method call.")
+            .append1(&bus_name);
+        if let Ok(reply) = conn.send_with_reply_and_block(msg, timeout_ms) {
+            let unique_bus_name: String = reply.read1().chain_err(|| "Could not convert to String")?;
+            owners.push((bus_name, unique_bus_name));
+        }
+    }
+    Ok(owners)
+}
+
+/// Waits for the next message on `conn` for at most `timeout_ms`, and converts it with `convert`.
+///
+/// Messages which are not converted are skipped.
+fn next_message<T, F>(conn: &Connection, timeout_ms: u32, mut convert: F) -> Option<T>
+    where F: FnMut(Message) -> Option<T>
+{
+    let deadline = Instant::now() + Duration::from_millis(u64::from(timeout_ms));
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let remaining_ms = remaining.as_secs() as u32 * 1000 + remaining.subsec_millis();
+        match conn.incoming(remaining_ms).next() {
+            Some(msg) => {
+                if let Some(item) = convert(msg) {
+                    return Some(item);
+                }
+            }
+            None if remaining_ms == 0 => return None,
+            None => {}
+        }
+    }
+}
+
+/// Iterator over `PlayerEvent`s.
+pub struct PlayerEvents<'a> {
+    watcher: &'a mut PlayerWatcher,
+    timeout_ms: u32,
+}
+
+impl<'a> Iterator for PlayerEvents<'a> {
+    type Item = PlayerEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.watcher.queued_events.pop_front() {
+            return Some(event);
+        }
+
+        next_message(&self.watcher.conn, self.timeout_ms, |msg| PlayerEvent::from_message(&msg))
+    }
+}
+
+
+/// A signal of a media player, which is tagged with the player's well-known bus name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerSignal {
+    /// The well-known bus name of the media player, e.g. `org.mpris.MediaPlayer2.vlc`.
+    pub bus_name: String,
+    /// The signal.
+    pub signal: MprisSignal,
+}
+
+impl PlayerSignal {
+    /// Returns the player name, which is passed to `MprisClient::new`.
+    pub fn player_name(&self) -> &str {
+        &self.bus_name[MPRIS_BUS_NAME_PREFIX.len()..]
+    }
+}
+
+/// Maps the unique bus names of connections to the well-known bus names of the media players they
+/// own.
+#[derive(Debug, Default)]
+struct BusNames(HashMap<String, Vec<String>>);
+
+impl BusNames {
+    /// Keeps the mapping current.
+    fn update(&mut self, event: &PlayerEvent) {
+        match *event {
+            PlayerEvent::PlayerAppeared { ref bus_name, ref unique_bus_name } => {
+                self.add(unique_bus_name, bus_name);
+            }
+            PlayerEvent::PlayerVanished { ref bus_name, ref unique_bus_name } => {
+                self.remove(unique_bus_name, bus_name);
+            }
+            PlayerEvent::PlayerOwnerChanged { ref bus_name, ref old_unique_bus_name, ref new_unique_bus_name } => {
+                self.remove(old_unique_bus_name, bus_name);
+                self.add(new_unique_bus_name, bus_name);
+            }
+        }
+    }
+
+    fn add(&mut self, unique_bus_name: &str, bus_name: &str) {
+        let bus_names = self.0.entry(unique_bus_name.to_string()).or_default();
+        if !bus_names.iter().any(|name| name == bus_name) {
+            bus_names.push(bus_name.to_string());
+        }
+    }
+
+    fn remove(&mut self, unique_bus_name: &str, bus_name: &str) {
+        let now_empty = match self.0.get_mut(unique_bus_name) {
+            Some(bus_names) => {
+                bus_names.retain(|name| name != bus_name);
+                bus_names.is_empty()
+            }
+            None => false,
+        };
+        if now_empty {
+            self.0.remove(unique_bus_name);
+        }
+    }
+
+    /// Returns the well-known bus names owned by the connection with the given unique bus name.
+    fn get(&self, unique_bus_name: &str) -> &[String] {
+        self.0.get(unique_bus_name).map(|bus_names| bus_names as &[String]).unwrap_or(&[])
+    }
+}
+
+/// An event of a `SignalWatcher`: either a change of the media players on the bus, or a signal of
+/// one of them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatcherEvent {
+    /// A media player appeared, vanished or was replaced.
+    Player(PlayerEvent),
+    /// A media player emitted a signal.
+    Signal(PlayerSignal),
+}
+
+/// Receives the signals of all media players on the session bus.
+///
+/// Signals are sent from the unique bus name of a player's connection, so the watcher keeps track
+/// of which well-known bus names each connection owns. If a connection owns several of them, its
+/// signals are reported once for each name.
+pub struct SignalWatcher {
+    conn: Connection,
+    bus_names: BusNames,
+    queued_events: VecDeque<WatcherEvent>,
+}
+
+impl SignalWatcher {
+    /// Creates a new `SignalWatcher` instance.
+    ///
+    /// The media players which are already running are reported as `PlayerAppeared` by `events`
+    /// first, so that no player is missed in between.
+    ///
+    /// `timeout_ms` specifies the maximum time a D-Bus method call blocks. The value -1 disables
+    /// the timeout.
+    pub fn new(timeout_ms: i32) -> Result<Self> {
+        let conn = open_session_bus()?;
+        conn.add_match(NAME_OWNER_CHANGED_MATCH_RULE)?;
+        for rule in &MPRIS_MATCH_RULES {
+            conn.add_match(rule)?;
+        }
+
+        let mut bus_names = BusNames::default();
+        let mut queued_events = VecDeque::new();
+        for (bus_name, unique_bus_name) in list_player_owners(&conn, timeout_ms)? {
+            bus_names.add(&unique_bus_name, &bus_name);
+            queued_events.push_back(WatcherEvent::Player(PlayerEvent::PlayerAppeared { bus_name, unique_bus_name }));
+        }
+        Ok(SignalWatcher { conn, bus_names, queued_events })
+    }
+
+    /// Returns an iterator of `PlayerSignal`s. `timeout_ms` specifies the maximum amount of time
+    /// the iterator blocks (and waits for new signals).
+    pub fn signals(&mut self, timeout_ms: u32) -> PlayerSignals<'_> {
+        PlayerSignals { events: self.events(timeout_ms) }
+    }
+
+    /// Returns an iterator of `WatcherEvent`s, which contains the appearing and vanishing media
+    /// players as well as their signals. `timeout_ms` specifies the maximum amount of time the
+    /// iterator blocks (and waits for new events).
+    pub fn events(&mut self, timeout_ms: u32) -> WatcherEvents<'_> {
+        WatcherEvents { watcher: self, timeout_ms }
+    }
+
+    /// Returns the well-known bus names of the media players which are currently known.
+    pub fn bus_names(&self) -> Vec<&str> {
+        self.bus_names.0.values().flat_map(|bus_names| bus_names.iter().map(|bus_name| bus_name as &str)).collect()
+    }
+}
+
+/// Iterator over `WatcherEvent`s.
+pub struct WatcherEvents<'a> {
+    watcher: &'a mut SignalWatcher,
+    timeout_ms: u32,
+}
+
+impl<'a> Iterator for WatcherEvents<'a> {
+    type Item = WatcherEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let SignalWatcher { ref conn, ref mut bus_names, ref mut queued_events } = *self.watcher;
+        if let Some(event) = queued_events.pop_front() {
+            return Some(event);
+        }
+
+        next_message(conn, self.timeout_ms, |msg| {
+            if let Some(event) = PlayerEvent::from_message(&msg) {
+                bus_names.update(&event);
+                return Some(WatcherEvent::Player(event));
+            }
+
+            // Signals of other connections than media players, or signals sent by a player after
+            // it released its name, are dropped.
+            let sender = msg.sender()?.to_string();
+            let signal = MprisSignal::from_message(&msg, DecodeMode::Lenient)?;
+            for bus_name in bus_names.get(&sender) {
+                let signal = PlayerSignal { bus_name: bus_name.clone(), signal: signal.clone() };
+                queued_events.push_back(WatcherEvent::Signal(signal));
+            }
+            queued_events.pop_front()
+        })
+    }
+}
+
+/// Iterator over `PlayerSignal`s.
+pub struct PlayerSignals<'a> {
+    events: WatcherEvents<'a>,
+}
+
+impl<'a> Iterator for PlayerSignals<'a> {
+    type Item = PlayerSignal;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.events.next()? {
+                WatcherEvent::Signal(signal) => return Some(signal),
+                WatcherEvent::Player(..) => {}
+            }
+        }
+    }
+}
+
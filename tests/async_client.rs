@@ -0,0 +1,130 @@
+extern crate futures;
+extern crate mpris;
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use futures::executor::block_on;
+
+use mpris::{MetadataMap, MetadataValue, PlaybackStatus};
+use mpris::async_client::AsyncMprisClient;
+use mpris::client::{ChangedProperty, MprisSignal};
+use mpris::decode::DecodeMode;
+use mpris::errors::*;
+use mpris::test_support::MockPlayer;
+
+fn setup_mock(player_name: &str) -> (MockPlayer, AsyncMprisClient) {
+    let mock = MockPlayer::builder()
+        .player_name(player_name)
+        .start()
+        .expect("Could not start the mock player.");
+    let client = AsyncMprisClient::new(mock.player_name(), 1000)
+        .expect("Could not connect to the mock player's MPRIS Interface.");
+    (mock, client)
+}
+
+#[test]
+fn test_async_methods() {
+    let (mock, client) = setup_mock("async_methods");
+    block_on(client.player.play()).unwrap();
+    block_on(client.player.seek(1_000_000)).unwrap();
+    assert_eq!(mock.calls(), vec!["Play", "Seek(1000000)"]);
+}
+
+#[test]
+fn test_async_properties() {
+    let (mock, client) = setup_mock("async_properties");
+    mock.unset("Fullscreen");
+
+    let (identity, playback_status, fullscreen) = block_on(futures::future::join3(
+        client.root.identity(),
+        client.player.playback_status(),
+        client.root.fullscreen(),
+    ));
+    assert_eq!(identity.unwrap(), "Mock player");
+    assert_eq!(playback_status.unwrap(), PlaybackStatus::Stopped);
+    assert_eq!(fullscreen.unwrap(), None);
+
+    block_on(client.player.set_volume(0.25)).unwrap();
+    assert_eq!(block_on(client.player.volume()).unwrap(), 0.25);
+    assert_eq!(block_on(client.player.get_all()).unwrap().volume, Some(0.25));
+
+    match block_on(client.root.set_fullscreen(true)) {
+        Err(Error(ErrorKind::AccessedAbsentOptionalProperty(..), ..)) => {}
+        Err(e) => panic!("wrong kind of error: {:?}", e),
+        Ok(..) => panic!("error expected"),
+    }
+}
+
+#[test]
+fn test_async_errors() {
+    let (mock, client) = setup_mock("async_errors");
+    // the mock player has no track list
+    match block_on(client.tracklist.tracks()) {
+        Err(Error(ErrorKind::UnknownInterface(ref player, ref interface, ref member), _)) => {
+            assert_eq!(player, "async_errors");
+            assert_eq!(interface, "org.mpris.MediaPlayer2.TrackList");
+            assert_eq!(member, "Tracks");
+        }
+        other => panic!("wrong result: {:?}", other),
+    }
+
+    drop(mock);
+    match block_on(client.player.play()) {
+        Err(Error(ErrorKind::ServiceUnknown(_, _, ref member), _)) => assert_eq!(member, "Play"),
+        other => panic!("wrong result: {:?}", other),
+    }
+    match block_on(client.player.volume()) {
+        Err(Error(ErrorKind::ServiceUnknown(_, _, ref member), _)) => assert_eq!(member, "Volume"),
+        other => panic!("wrong result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_async_signals() {
+    let (mock, client) = setup_mock("async_signals");
+    let mut signals = client.signals();
+
+    mock.set(ChangedProperty::Volume(0.5));
+    assert_eq!(block_on(signals.next()), Some(MprisSignal::PropertiesChanged {
+        interface: "org.mpris.MediaPlayer2.Player".to_string(),
+        changed_properties: vec![ChangedProperty::Volume(0.5)],
+        invalidated_properties: vec![],
+    }));
+
+    mock.emit(MprisSignal::Seeked { position: 1_000_000 });
+    mock.emit_after(MprisSignal::Seeked { position: 2_000_000 }, Duration::from_millis(50));
+    let seeked: Vec<MprisSignal> = block_on(signals.take(2).collect());
+    assert_eq!(seeked, vec![
+        MprisSignal::Seeked { position: 1_000_000 },
+        MprisSignal::Seeked { position: 2_000_000 },
+    ]);
+}
+
+#[test]
+fn test_async_decode_mode() {
+    let (mock, client) = setup_mock("async_decode_mode");
+    let metadata = MetadataMap::builder()
+        .trackid("/track/1")
+        .value("mpris:length", MetadataValue::String("long".to_string()))
+        .build()
+        .unwrap();
+    mock.set(ChangedProperty::Metadata(metadata));
+
+    // the lenient default keeps the entry which cannot be converted
+    assert_eq!(client.decode_mode(), DecodeMode::Lenient);
+    let metadata = block_on(client.player.metadata()).unwrap();
+    assert_eq!(metadata.get("mpris:length"), Some(&MetadataValue::String("long".to_string())));
+
+    client.set_decode_mode(DecodeMode::Strict);
+    match block_on(client.player.metadata()) {
+        Err(Error(ErrorKind::NonConformingMetadata(ref key, ref found, ref expected), _)) => {
+            assert_eq!((key as &str, found as &str, expected as &str), ("mpris:length", "s", "x"));
+        }
+        other => panic!("expected NonConformingMetadata, got {:?}", other.map(|_| ())),
+    }
+    // get_all keeps the metadata in the other field instead
+    let player = block_on(client.player.get_all()).unwrap();
+    assert_eq!(player.metadata, None);
+    assert!(player.other.contains_key("Metadata"));
+}
@@ -0,0 +1,155 @@
+extern crate mpris;
+extern crate serde_json;
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use mpris::PlaybackStatus;
+use mpris::client::{ChangedProperty, MprisSignal};
+use mpris::test_support::{MockPlayer, session_bus_address};
+
+/// Runs the `mpris` binary on the private session bus.
+fn mpris(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mpris"))
+        .args(args)
+        .env("DBUS_SESSION_BUS_ADDRESS", session_bus_address().unwrap())
+        .output()
+        .expect("Could not run the mpris binary.")
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "mpris failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn test_cli_status_and_controls() {
+    let mock = MockPlayer::builder().player_name("cli_controls").start().unwrap();
+    assert_eq!(stdout(&mpris(&["--player", "cli_controls", "status"])), "Stopped\n");
+
+    stdout(&mpris(&["--player", "cli_controls", "play"]));
+    stdout(&mpris(&["--player", "cli_controls", "seek", "-1.5"]));
+    assert_eq!(mock.calls(), vec!["Play", "Seek(-1500000)"]);
+    assert_eq!(mock.get("PlaybackStatus"), Some(ChangedProperty::PlaybackStatus(PlaybackStatus::Playing)));
+
+    stdout(&mpris(&["--player", "cli_controls", "shuffle", "toggle"]));
+    assert_eq!(stdout(&mpris(&["--player", "cli_controls", "shuffle"])), "On\n");
+    assert_eq!(stdout(&mpris(&["--player", "cli_controls", "metadata", "xesam:title"])), "Mock track\n");
+}
+
+#[test]
+fn test_cli_player_selection() {
+    let first = MockPlayer::builder().player_name("cli_select.instance1").start().unwrap();
+    let second = MockPlayer::builder().player_name("cli_select_other").start().unwrap();
+
+    // the first available player of the list is controlled
+    stdout(&mpris(&["--player", "cli_select_missing,cli_select_other,cli_select", "volume", "0.5"]));
+    assert_eq!(second.get("Volume"), Some(ChangedProperty::Volume(0.5)));
+    assert_eq!(first.get("Volume"), Some(ChangedProperty::Volume(1.0)));
+
+    // instances match the name of the player
+    stdout(&mpris(&["--all-players", "-p", "cli_select", "-p", "cli_select_other", "volume", "0.25"]));
+    assert_eq!(first.get("Volume"), Some(ChangedProperty::Volume(0.25)));
+    assert_eq!(second.get("Volume"), Some(ChangedProperty::Volume(0.25)));
+
+    let output = mpris(&["--player", "cli_select_missing", "status"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_format() {
+    let _mock = MockPlayer::builder().player_name("cli_format").start().unwrap();
+    let template = "{{player}}: {{status}} {{#if title}}{{title}}{{else}}-{{/if}} ({{default(artist, \"?\")}})";
+    assert_eq!(stdout(&mpris(&["--player", "cli_format", "--format", template, "status"])),
+               "cli_format: Stopped Mock track (?)\n");
+
+    let output = mpris(&["--player", "cli_format", "--format", "{{title", "status"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_follow_json() {
+    let mock = MockPlayer::builder().player_name("cli_follow").start().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mpris"))
+        .args(["--player", "cli_follow", "follow", "--json"])
+        .env("DBUS_SESSION_BUS_ADDRESS", session_bus_address().unwrap())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Could not run the mpris binary.");
+    let (sender, receiver) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let event: serde_json::Value = serde_json::from_str(&line.unwrap()).unwrap();
+            if sender.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    let next_event = || receiver.recv_timeout(Duration::from_secs(5)).expect("No event was printed.");
+
+    // the running player is reported with its state
+    let event = next_event();
+    assert_eq!(event["player"], "cli_follow");
+    assert_eq!(event["event"], "appeared");
+    assert_eq!(event["status"], "Stopped");
+    assert_eq!(event["metadata"]["mpris:trackid"], "/track/1");
+    assert_eq!(event["metadata"]["xesam:title"], "Mock track");
+
+    mock.emit(MprisSignal::PropertiesChanged {
+        interface: "org.mpris.MediaPlayer2.Player".to_string(),
+        changed_properties: vec![ChangedProperty::PlaybackStatus(PlaybackStatus::Playing)],
+        invalidated_properties: vec![],
+    });
+    let event = next_event();
+    assert_eq!(event["event"], "signal");
+    let signal = &event["signal"]["PropertiesChanged"];
+    assert_eq!(signal["interface"], "org.mpris.MediaPlayer2.Player");
+    assert_eq!(signal["changed_properties"][0]["PlaybackStatus"], "Playing");
+
+    mock.emit(MprisSignal::Seeked { position: 42 });
+    assert_eq!(next_event()["signal"]["Seeked"]["position"], 42);
+
+    drop(mock);
+    let event = next_event();
+    assert_eq!(event["player"], "cli_follow");
+    assert_eq!(event["event"], "vanished");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn test_cli_follow_closed_pipe() {
+    let mock = MockPlayer::builder().player_name("cli_follow_pipe").start().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mpris"))
+        .args(["--player", "cli_follow_pipe", "follow"])
+        .env("DBUS_SESSION_BUS_ADDRESS", session_bus_address().unwrap())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Could not run the mpris binary.");
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert!(line.starts_with("cli_follow_pipe "));
+
+    // the next event cannot be printed anymore, which ends follow quietly
+    drop(stdout);
+    mock.emit(MprisSignal::Seeked { position: 42 });
+    let mut status = None;
+    for _ in 0..50 {
+        status = child.try_wait().unwrap();
+        if status.is_some() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    let status = status.expect("mpris did not exit after stdout was closed.");
+    let output = child.wait_with_output().unwrap();
+    assert!(status.success());
+    assert!(output.stderr.is_empty(), "mpris failed: {}", String::from_utf8_lossy(&output.stderr));
+}
@@ -1,48 +1,114 @@
+extern crate dbus;
 extern crate mpris;
 
+use std::collections::HashMap;
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use dbus::{MessageType, Path};
+use dbus::arg::{RefArg, Variant};
+
 use mpris::errors;
 
 
-use mpris::client::MprisClient;
+use mpris::PlaybackStatus;
+use mpris::client::{ChangedProperty, MprisClient, MprisSignal};
+use mpris::decode::DecodeMode;
+use mpris::test_support::{MockPlayer, session_bus_address, session_connection};
+
+/// Starts a media player on a plain D-Bus connection, which sends `mpris:length` as `int32`
+/// instead of `int64`. It answers the first `calls` method calls with its metadata.
+fn start_nonconforming_player(player_name: &str, calls: usize) -> thread::JoinHandle<()> {
+    session_bus_address().unwrap();
+    let bus_name = format!("org.mpris.MediaPlayer2.{}", player_name);
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let player = thread::spawn(move || {
+        let conn = session_connection().unwrap();
+        conn.register_name(&bus_name, 0).unwrap();
+        conn.register_object_path("/org/mpris/MediaPlayer2").unwrap();
+        ready_tx.send(()).unwrap();
 
-fn setup_vlc() -> MprisClient {
-    MprisClient::new("vlc", 1000).expect(
-        "Could not connect to VLC's MPRIS Interface. Is VLC running?",
-    )
+        let mut answered = 0;
+        for _ in 0..50 {
+            if answered == calls {
+                return;
+            }
+            for msg in conn.incoming(100) {
+                if msg.msg_type() != MessageType::MethodCall {
+                    continue;
+                }
+                let mut metadata: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+                metadata.insert("mpris:trackid".to_string(), Variant(Box::new(Path::from("/track/1"))));
+                metadata.insert("mpris:length".to_string(), Variant(Box::new(1_000_000i32)));
+                let value: Box<dyn RefArg> = Box::new(metadata);
+                conn.send(msg.method_return().append1(Variant(value))).unwrap();
+                answered += 1;
+            }
+        }
+    });
+    ready_rx.recv().unwrap();
+    player
 }
 
-fn setup_cantata() -> MprisClient {
-    MprisClient::new("cantata", 1000).expect(
-        "Could not connect to Cantata's MPRIS Interface. Is Cantata running?",
-    )
+fn setup_mock() -> (MockPlayer, MprisClient) {
+    let mock = MockPlayer::start().expect("Could not start the mock player.");
+    let client = MprisClient::new(mock.player_name(), 1000).expect(
+        "Could not connect to the mock player's MPRIS Interface.",
+    );
+    (mock, client)
 }
 
 #[test]
 fn test_raise() {
-    let client = setup_vlc();
+    let (mock, client) = setup_mock();
     client.root.raise().unwrap();
+    assert_eq!(mock.calls(), vec!["Raise"]);
 }
 
 #[test]
 fn test_can_quit() {
-    let client = setup_vlc();
+    let (mock, client) = setup_mock();
     assert!(client.root.can_quit().unwrap());
+
+    mock.set(ChangedProperty::CanQuit(false));
+    assert!(!client.root.can_quit().unwrap());
+}
+
+#[test]
+fn test_identity() {
+    let (mock, client) = setup_mock();
+    assert_eq!(client.root.identity().unwrap(), "Mock player");
+
+    mock.set(ChangedProperty::Identity("VLC media player".to_string()));
+    assert_eq!(client.root.identity().unwrap(), "VLC media player");
+}
+
+#[test]
+fn test_desktop_entry_optional() {
+    let (mock, client) = setup_mock();
+    assert_eq!(client.root.desktop_entry().unwrap(), Some("mock".to_string()));
+
+    mock.unset("DesktopEntry");
+    assert_eq!(client.root.desktop_entry().unwrap(), None);
 }
 
 #[test]
 fn test_fullscreen() {
-    let client = setup_vlc();
+    let (_mock, client) = setup_mock();
     let is_fullscreen = client.root.fullscreen().unwrap().unwrap();
     client.root.set_fullscreen(!is_fullscreen).unwrap();
-    std::thread::sleep(std::time::Duration::from_millis(200));
     let is_fullscreen2 = client.root.fullscreen().unwrap().unwrap();
 
-    assert!(!is_fullscreen, is_fullscreen2);
+    assert_eq!(!is_fullscreen, is_fullscreen2);
 }
 
 #[test]
 fn test_fullscreen_optional() {
-    let client = setup_cantata();
+    let (mock, client) = setup_mock();
+    mock.unset("Fullscreen");
+    mock.unset("CanSetFullscreen");
+
     let result = client.root.fullscreen();
     let is_fullscreen = result.unwrap();
     assert_eq!(None, is_fullscreen);
@@ -56,7 +122,223 @@ fn test_fullscreen_optional() {
 
 }
 
+#[test]
+fn test_play_pause() {
+    let (mock, client) = setup_mock();
+    client.player.play_pause().unwrap();
+    assert_eq!(client.player.playback_status().unwrap(), PlaybackStatus::Playing);
+    client.player.play_pause().unwrap();
+    assert_eq!(client.player.playback_status().unwrap(), PlaybackStatus::Paused);
+    assert_eq!(mock.calls(), vec!["PlayPause", "PlayPause"]);
+}
+
+#[test]
+fn test_seek() {
+    let (mock, client) = setup_mock();
+    mock.set(ChangedProperty::Position(5_000_000));
+    client.player.seek(-1_000_000).unwrap();
+    assert_eq!(client.player.position().unwrap(), 4_000_000);
+    assert_eq!(mock.calls(), vec!["Seek(-1000000)"]);
+}
+
+#[test]
+fn test_playback_status() {
+    let (mock, client) = setup_mock();
+    assert_eq!(client.player.playback_status().unwrap(), PlaybackStatus::Stopped);
+
+    mock.set(ChangedProperty::PlaybackStatus(PlaybackStatus::Paused));
+    assert_eq!(client.player.playback_status().unwrap(), PlaybackStatus::Paused);
+}
+
+#[test]
+fn test_volume() {
+    let (_mock, client) = setup_mock();
+    let volume = client.player.volume().unwrap();
+    client.player.set_volume(volume / 2.0).unwrap();
+    let volume2 = client.player.volume().unwrap();
+    client.player.set_volume(volume).unwrap();
+
+    assert!((volume / 2.0 - volume2).abs() < 0.01);
+}
+
+#[test]
+fn test_tracks_metadata() {
+    let mock = MockPlayer::builder()
+        .player_name("mock_tracks")
+        .track_list(&["First", "Second"])
+        .start()
+        .unwrap();
+    let client = MprisClient::new(mock.player_name(), 1000).unwrap();
+    let tracks = client.tracklist.tracks().unwrap();
+    let metadata = client.tracklist.get_tracks_metadata(&tracks).unwrap();
+    assert_eq!(tracks.len(), metadata.len());
+    assert_eq!(metadata[1].title(), Some("Second".to_string()));
+}
+
+#[test]
+fn test_get_all() {
+    let mock = MockPlayer::builder()
+        .player_name("mock_get_all")
+        .track_list(&["First", "Second"])
+        .start()
+        .unwrap();
+    mock.unset("DesktopEntry");
+    let client = MprisClient::new(mock.player_name(), 1000).unwrap();
+
+    let root = client.root.get_all().unwrap();
+    assert_eq!(root.identity, Some("Mock player".to_string()));
+    assert_eq!(root.has_track_list, Some(true));
+    assert_eq!(root.desktop_entry, None);
+    assert!(root.other.is_empty());
+
+    let player = client.player.get_all().unwrap();
+    assert_eq!(player.playback_status, Some(PlaybackStatus::Stopped));
+    assert_eq!(player.volume, Some(1.0));
+    assert_eq!(player.metadata.unwrap().title(), Some("Mock track".to_string()));
+
+    let tracklist = client.tracklist.get_all().unwrap();
+    assert_eq!(tracklist.tracks.map(|tracks| tracks.len()), Some(2));
+}
+
+#[test]
+fn test_signals() {
+    let (mock, client) = setup_mock();
+    mock.emit(MprisSignal::Seeked { position: 1_000_000 });
+    mock.emit_after(MprisSignal::Seeked { position: 2_000_000 }, Duration::from_millis(100));
+
+    let signals: Vec<MprisSignal> = client.signals(500).take(3).collect();
+    assert_eq!(signals, vec![
+        MprisSignal::Seeked { position: 1_000_000 },
+        MprisSignal::Seeked { position: 2_000_000 },
+    ]);
+}
+
+#[test]
+fn test_client_across_threads() {
+    let (mock, client) = setup_mock();
+    let client = Arc::new(client);
+
+    let worker_client = client.clone();
+    let worker = thread::spawn(move || {
+        worker_client.player.play().unwrap();
+        worker_client.player.metadata().unwrap()
+    });
+    let metadata = worker.join().unwrap();
+
+    assert_eq!(metadata.title(), Some("Mock track".to_string()));
+    assert_eq!(client.player.playback_status().unwrap(), PlaybackStatus::Playing);
+    assert_eq!(mock.calls(), vec!["Play"]);
+}
+
+#[test]
+fn test_decode_mode() {
+    let player = start_nonconforming_player("nonconforming", 2);
+    let client = MprisClient::new("nonconforming", 1000).unwrap();
+
+    // the lenient default converts the length to `int64`
+    assert_eq!(client.decode_mode(), DecodeMode::Lenient);
+    assert_eq!(client.player.metadata().unwrap().length(), Some(1_000_000));
+
+    client.set_decode_mode(DecodeMode::Strict);
+    match client.player.metadata() {
+        Err(errors::Error(errors::ErrorKind::NonConformingMetadata(ref key, ref found, ref expected), _)) => {
+            assert_eq!((key as &str, found as &str, expected as &str), ("mpris:length", "i", "x"));
+        }
+        other => panic!("expected NonConformingMetadata, got {:?}", other.map(|_| ())),
+    }
+    player.join().unwrap();
+}
+
 #[test]
 fn test_list_players() {
-    MprisClient::list_players(1000).unwrap();
+    let _mock = MockPlayer::builder().player_name("mock_listed").start().unwrap();
+    let players = MprisClient::list_players(1000).unwrap();
+    assert!(players.contains(&"mock_listed".to_string()));
+}
+
+#[test]
+fn test_errors() {
+    let mock = MockPlayer::builder().player_name("mock_errors").start().unwrap();
+    let client = MprisClient::new(mock.player_name(), 1000).unwrap();
+
+    // the mock player has no track list
+    match client.tracklist.tracks() {
+        Err(errors::Error(errors::ErrorKind::UnknownInterface(ref player, ref interface, ref member), _)) => {
+            assert_eq!(player, "mock_errors");
+            assert_eq!(interface, "org.mpris.MediaPlayer2.TrackList");
+            assert_eq!(member, "Tracks");
+        }
+        other => panic!("wrong result: {:?}", other),
+    }
+    match client.tracklist.go_to(&"/track/1".parse().unwrap()) {
+        Err(errors::Error(errors::ErrorKind::UnknownInterface(_, _, ref member), _)) => assert_eq!(member, "GoTo"),
+        other => panic!("wrong result: {:?}", other),
+    }
+
+    // calls, property reads and property writes report the player as gone
+    drop(mock);
+    match client.player.next() {
+        Err(errors::Error(errors::ErrorKind::ServiceUnknown(ref player, ref interface, ref member), _)) => {
+            assert_eq!(player, "mock_errors");
+            assert_eq!(interface, "org.mpris.MediaPlayer2.Player");
+            assert_eq!(member, "Next");
+        }
+        other => panic!("wrong result: {:?}", other),
+    }
+    match client.player.volume() {
+        Err(errors::Error(errors::ErrorKind::ServiceUnknown(_, _, ref member), _)) => assert_eq!(member, "Volume"),
+        other => panic!("wrong result: {:?}", other),
+    }
+    match client.player.set_volume(0.5) {
+        Err(errors::Error(errors::ErrorKind::ServiceUnknown(_, _, ref member), _)) => assert_eq!(member, "Volume"),
+        other => panic!("wrong result: {:?}", other),
+    }
+    match MprisClient::new("mock_errors", 1000) {
+        Err(errors::Error(errors::ErrorKind::ServiceUnknown(ref player, ref interface, ref member), _)) => {
+            assert_eq!(player, "mock_errors");
+            assert_eq!(interface, "org.freedesktop.DBus");
+            assert_eq!(member, "GetNameOwner");
+        }
+        other => panic!("wrong result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_checked_mode() {
+    let mock = MockPlayer::builder().player_name("mock_checked").start().unwrap();
+    let client = MprisClient::new(mock.player_name(), 1000).unwrap();
+    assert!(!client.is_checked());
+
+    // without the checked mode, the call succeeds, but has no effect
+    mock.set(ChangedProperty::CanGoNext(false));
+    client.player.next().unwrap();
+    assert!(mock.calls().is_empty());
+
+    client.set_checked(true);
+    match client.player.next() {
+        Err(errors::Error(errors::ErrorKind::NotCapable(ref player, ref interface, ref member, ref capability), _)) => {
+            assert_eq!(player, "mock_checked");
+            assert_eq!(interface, "org.mpris.MediaPlayer2.Player");
+            assert_eq!(member, "Next");
+            assert_eq!(capability, "CanGoNext");
+        }
+        other => panic!("wrong result: {:?}", other),
+    }
+    mock.set(ChangedProperty::CanGoNext(true));
+    client.player.next().unwrap();
+
+    // an absent capability counts as `false`
+    mock.unset("CanSetFullscreen");
+    match client.root.set_fullscreen(true) {
+        Err(errors::Error(errors::ErrorKind::NotCapable(_, _, _, ref capability), _)) => {
+            assert_eq!(capability, "CanSetFullscreen")
+        }
+        other => panic!("wrong result: {:?}", other),
+    }
+
+    // `CanControl` is read only once, so the call is sent to the media player, which ignores it
+    client.player.set_volume(0.5).unwrap();
+    mock.set(ChangedProperty::CanControl(false));
+    client.player.stop().unwrap();
+    assert_eq!(mock.calls(), vec!["Next"]);
 }
@@ -0,0 +1,62 @@
+extern crate dbus;
+extern crate mpris;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use dbus::Path;
+use dbus::arg::RefArg;
+
+use mpris::{MetadataMap, PlaybackStatus};
+use mpris::client::{ChangedProperty, MprisClient, MprisSignal};
+use mpris::position::{ManualClock, PositionTracker};
+use mpris::test_support::MockPlayer;
+
+fn properties_changed(property: ChangedProperty) -> MprisSignal {
+    MprisSignal::PropertiesChanged {
+        interface: "org.mpris.MediaPlayer2.Player".to_string(),
+        changed_properties: vec![property],
+        invalidated_properties: vec![],
+    }
+}
+
+fn metadata(track_id: &str) -> MetadataMap {
+    let mut map: HashMap<String, Rc<dyn RefArg>> = HashMap::new();
+    map.insert("mpris:trackid".to_string(), Rc::new(Path::from(track_id.to_string())));
+    MetadataMap::from_map(map).unwrap()
+}
+
+#[test]
+fn test_position_tracking() {
+    let mock = MockPlayer::builder().player_name("position_tracking").start().unwrap();
+    mock.set(ChangedProperty::PlaybackStatus(PlaybackStatus::Playing));
+    mock.set(ChangedProperty::Position(5_000_000));
+    let client = MprisClient::new(mock.player_name(), 1000).unwrap();
+
+    let clock = ManualClock::new();
+    let mut tracker = PositionTracker::with_clock(&client, clock.clone()).unwrap();
+    assert_eq!(tracker.position(), 5_000_000);
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(tracker.position(), 7_000_000);
+
+    tracker.update(&properties_changed(ChangedProperty::Rate(2.0)));
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(tracker.position(), 9_000_000);
+
+    tracker.update(&properties_changed(ChangedProperty::PlaybackStatus(PlaybackStatus::Paused)));
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(tracker.position(), 9_000_000);
+
+    tracker.update(&MprisSignal::Seeked { position: 1_000_000 });
+    assert_eq!(tracker.position(), 1_000_000);
+
+    // a new track starts at the beginning
+    tracker.update(&properties_changed(ChangedProperty::PlaybackStatus(PlaybackStatus::Playing)));
+    tracker.update(&properties_changed(ChangedProperty::Metadata(metadata("/track/2"))));
+    clock.advance(Duration::from_millis(500));
+    assert_eq!(tracker.position(), 1_000_000);
+
+    tracker.update(&properties_changed(ChangedProperty::PlaybackStatus(PlaybackStatus::Stopped)));
+    assert_eq!(tracker.position(), 0);
+}
@@ -0,0 +1,79 @@
+extern crate dbus;
+extern crate mpris;
+extern crate serde_json;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use dbus::Path;
+use dbus::arg::{RefArg, Variant};
+
+use mpris::{LoopStatus, MetadataMap, PlaybackStatus, TrackId};
+use mpris::client::{ChangedProperty, MprisSignal};
+
+fn example_metadata() -> MetadataMap {
+    let mut vendor_map: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+    vendor_map.insert("rating".to_string(), Variant(Box::new(-3i32)));
+    let mut raw_map: HashMap<String, Rc<dyn RefArg>> = HashMap::new();
+    raw_map.insert("mpris:trackid".to_string(), Rc::new(Path::from("/track/1")));
+    raw_map.insert("mpris:length".to_string(), Rc::new(215_000_000i64));
+    raw_map.insert("xesam:artist".to_string(), Rc::new(vec!["first".to_string(), "second".to_string()]));
+    raw_map.insert("xesam:title".to_string(), Rc::new("example title".to_string()));
+    raw_map.insert("xesam:trackNumber".to_string(), Rc::new(7u32));
+    raw_map.insert("x-vendor:extra".to_string(), Rc::new(vendor_map));
+    MetadataMap::from_map(raw_map).unwrap()
+}
+
+#[test]
+fn test_serialize_metadata() {
+    let json = serde_json::to_value(example_metadata()).unwrap();
+    assert_eq!(json, serde_json::json!({
+        "mpris:length": 215_000_000,
+        "mpris:trackid": "/track/1",
+        "x-vendor:extra": { "rating": -3 },
+        "xesam:artist": ["first", "second"],
+        "xesam:title": "example title",
+        "xesam:trackNumber": 7,
+    }));
+
+    let metadata: MetadataMap = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(metadata.trackid(), &TrackId::from_str("/track/1").unwrap());
+    assert_eq!(metadata.length(), Some(215_000_000));
+    assert_eq!(metadata.artist(), Some(vec!["first".to_string(), "second".to_string()]));
+    assert_eq!(metadata.track_number(), Some(7));
+    assert_eq!(serde_json::to_value(&metadata).unwrap(), json);
+
+    assert!(serde_json::from_str::<MetadataMap>(r#"{"xesam:title": "no track id"}"#).is_err());
+    assert!(serde_json::from_str::<MetadataMap>(r#"{"mpris:trackid": "not a path"}"#).is_err());
+}
+
+#[test]
+fn test_serialize_signals() {
+    assert_eq!(serde_json::to_string(&PlaybackStatus::Paused).unwrap(), r#""Paused""#);
+    assert_eq!(serde_json::from_str::<LoopStatus>(r#""Playlist""#).unwrap(), LoopStatus::Playlist);
+    assert_eq!(serde_json::to_string(&TrackId::from_str("/track/2").unwrap()).unwrap(), r#""/track/2""#);
+
+    let signals = vec![
+        MprisSignal::Seeked { position: 42 },
+        MprisSignal::TrackMetadataChanged {
+            track_id: TrackId::from_str("/track/1").unwrap(),
+            metadata: example_metadata(),
+        },
+        MprisSignal::PropertiesChanged {
+            interface: "org.mpris.MediaPlayer2.Player".to_string(),
+            changed_properties: vec![
+                ChangedProperty::PlaybackStatus(PlaybackStatus::Playing),
+                ChangedProperty::LoopStatus(LoopStatus::Track),
+                ChangedProperty::Metadata(example_metadata()),
+            ],
+            invalidated_properties: vec!["Position".to_string()],
+        },
+    ];
+    for signal in signals {
+        let json = serde_json::to_string(&signal).unwrap();
+        let deserialized: MprisSignal = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, signal);
+        assert_eq!(serde_json::to_string(&deserialized).unwrap(), json);
+    }
+}
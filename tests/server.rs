@@ -0,0 +1,265 @@
+extern crate dbus;
+extern crate mpris;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use dbus::Message;
+
+use mpris::{LoopStatus, MetadataMap, PlaybackStatus, PlaylistOrdering};
+use mpris::client::{ChangedProperty, MprisClient, MprisSignal};
+use mpris::errors::*;
+use mpris::test_support::{session_bus_address, session_connection};
+use mpris::server::{MprisServer, PlayerHandler, PlaylistsHandler, Playlists, RootHandler, TrackList,
+                    TrackListHandler, VecPlaylistStore, VecTrackStore};
+
+
+struct TestPlayer {
+    playback_status: PlaybackStatus,
+    volume: f64,
+    position: i64,
+}
+
+impl RootHandler for TestPlayer {
+    fn identity(&self) -> String {
+        "Test player".to_string()
+    }
+}
+
+impl PlayerHandler for TestPlayer {
+    fn play(&mut self) -> Result<()> {
+        self.playback_status = PlaybackStatus::Playing;
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.playback_status = PlaybackStatus::Paused;
+        Ok(())
+    }
+
+    fn seek(&mut self, offset: i64) -> Result<()> {
+        self.position += offset;
+        Ok(())
+    }
+
+    fn playback_status(&self) -> PlaybackStatus {
+        self.playback_status
+    }
+
+    fn metadata(&self) -> MetadataMap {
+        metadata("/track/1", "Title")
+    }
+
+    fn set_volume(&mut self, volume: f64) -> Result<()> {
+        self.volume = volume;
+        Ok(())
+    }
+
+    fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    fn position(&self) -> i64 {
+        self.position
+    }
+
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+impl TrackListHandler for TestPlayer {
+    fn load_track(&mut self, uri: &str) -> Result<MetadataMap> {
+        MetadataMap::builder()
+            .trackid("/new")
+            .title(uri)
+            .artist(&["Third artist"])
+            .length(Duration::from_millis(1500))
+            .track_number(3)
+            .build()
+    }
+}
+
+impl PlaylistsHandler for TestPlayer {}
+
+fn metadata(track_id: &str, title: &str) -> MetadataMap {
+    MetadataMap::builder().trackid(track_id).title(title).build().unwrap()
+}
+
+fn setup_server(player_name: &'static str) -> MprisClient {
+    session_bus_address().unwrap();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let player = TestPlayer {
+            playback_status: PlaybackStatus::Stopped,
+            volume: 0.5,
+            position: 0,
+        };
+        let mut server = MprisServer::new(player_name, player)
+            .expect("Could not export the test player.");
+
+        let mut track_list = TrackList::new(VecTrackStore::new());
+        track_list.set_can_edit_tracks(true);
+        track_list.replace_tracks(vec![metadata("/a", "First"), metadata("/b", "Second")]);
+        server.enable_track_list(track_list);
+
+        let mut playlists = Playlists::new(VecPlaylistStore::new());
+        playlists.add_playlist("Rock", "");
+        playlists.add_playlist("Jazz", "");
+        server.enable_playlists(playlists);
+
+        server.emit_changes().unwrap();
+        ready_tx.send(()).unwrap();
+        server.run().unwrap();
+    });
+    ready_rx.recv().unwrap();
+
+    MprisClient::new(player_name, 1000).expect("Could not connect to the test player.")
+}
+
+#[test]
+fn test_server_properties() {
+    let client = setup_server("server_properties");
+    assert_eq!(client.root.identity().unwrap(), "Test player");
+    assert_eq!(client.root.fullscreen().unwrap(), None);
+    assert_eq!(client.player.playback_status().unwrap(), PlaybackStatus::Stopped);
+    assert_eq!(client.player.loop_status().unwrap(), None);
+    assert_eq!(client.player.metadata().unwrap().title(), Some("Title".to_string()));
+}
+
+#[test]
+fn test_server_methods() {
+    let client = setup_server("server_methods");
+    client.player.play().unwrap();
+    assert_eq!(client.player.playback_status().unwrap(), PlaybackStatus::Playing);
+
+    client.player.set_volume(0.8).unwrap();
+    assert_eq!(client.player.volume().unwrap(), 0.8);
+
+    // a rate of 0.0 pauses the playback, although it is below the minimum rate
+    client.player.set_rate(0.0).unwrap();
+    assert_eq!(client.player.playback_status().unwrap(), PlaybackStatus::Paused);
+    assert_eq!(client.player.rate().unwrap(), 1.0);
+
+    match client.player.set_loop_status(LoopStatus::Track) {
+        Err(ref err @ Error(ErrorKind::AccessedAbsentOptionalProperty(..), ..)) => {
+            // the cause names the player and the interface
+            assert_eq!(err.iter().nth(1).map(|cause| cause.to_string()),
+                       Some("server_methods has no property org.mpris.MediaPlayer2.Player.LoopStatus".to_string()));
+        }
+        Err(e) => panic!("wrong kind of error: {:?}", e),
+        Ok(..) => panic!("error expected"),
+    }
+
+    // calls of other objects are answered with an error instead of running into the timeout
+    let conn = session_connection().unwrap();
+    let msg = Message::new_method_call("org.mpris.MediaPlayer2.server_methods", "/",
+                                       "org.freedesktop.DBus.Introspectable", "Introspect").unwrap();
+    let err = conn.send_with_reply_and_block(msg, 5000).unwrap_err();
+    assert_eq!(err.name(), Some("org.freedesktop.DBus.Error.UnknownObject"));
+    let msg = Message::new_method_call("org.mpris.MediaPlayer2.server_methods", "/",
+                                       "org.freedesktop.DBus.Peer", "Ping").unwrap();
+    conn.send_with_reply_and_block(msg, 5000).unwrap();
+}
+
+#[test]
+fn test_server_signals() {
+    let client = setup_server("server_signals");
+    client.player.pause().unwrap();
+    client.player.set_volume(0.2).unwrap();
+    client.player.seek(1_000_000).unwrap();
+
+    // every request announces its own changes with a PropertiesChanged signal
+    let mut changed = Vec::new();
+    let mut seeked = Vec::new();
+    for signal in client.signals(500) {
+        match signal {
+            MprisSignal::PropertiesChanged { interface, changed_properties, invalidated_properties } => {
+                assert_eq!(interface, "org.mpris.MediaPlayer2.Player");
+                assert!(invalidated_properties.is_empty());
+                changed.extend(changed_properties);
+            }
+            MprisSignal::Seeked { position } => seeked.push(position),
+            signal => panic!("unexpected signal: {:?}", signal),
+        }
+    }
+    // the order of the properties within one signal is not defined
+    assert_eq!(changed.len(), 2);
+    assert!(changed.contains(&ChangedProperty::PlaybackStatus(PlaybackStatus::Paused)));
+    assert!(changed.contains(&ChangedProperty::Volume(0.2)));
+    assert_eq!(seeked, vec![1_000_000]);
+}
+
+#[test]
+fn test_server_track_list() {
+    let client = setup_server("server_track_list");
+    assert!(client.root.has_track_list().unwrap());
+
+    let tracks = client.tracklist.tracks().unwrap();
+    assert_eq!(tracks.len(), 2);
+    assert!(tracks.iter().all(|track_id| !track_id.as_ref().starts_with("/org/mpris")));
+    let titles: Vec<Option<String>> = client.tracklist.get_tracks_metadata(&tracks).unwrap()
+        .iter()
+        .map(|metadata| metadata.title())
+        .collect();
+    assert_eq!(titles, vec![Some("First".to_string()), Some("Second".to_string())]);
+
+    client.tracklist.add_track("file:///third.ogg", &tracks[0], false).unwrap();
+    client.tracklist.remove_track(&tracks[1]).unwrap();
+    let new_tracks = client.tracklist.tracks().unwrap();
+    assert_eq!(new_tracks.len(), 2);
+    assert_eq!(new_tracks[0], tracks[0]);
+    assert!(!tracks.contains(&new_tracks[1]));
+
+    let signals: Vec<MprisSignal> = client.signals(500)
+        .filter(|signal| !matches!(*signal, MprisSignal::PropertiesChanged { .. }))
+        .take(2)
+        .collect();
+    match signals[..] {
+        [MprisSignal::TrackAdded { ref metadata, ref after_track },
+         MprisSignal::TrackRemoved { ref track_id }] => {
+            assert_eq!(metadata.trackid(), &new_tracks[1]);
+            assert_eq!(metadata.title(), Some("file:///third.ogg".to_string()));
+            assert_eq!(metadata.artist(), Some(vec!["Third artist".to_string()]));
+            assert_eq!(metadata.length(), Some(1_500_000));
+            assert_eq!(metadata.track_number(), Some(3));
+            // the metadata is sent with the types of the specification
+            assert!(metadata.coercions().is_empty());
+            assert_eq!(after_track, &tracks[0]);
+            assert_eq!(track_id, &tracks[1]);
+        }
+        _ => panic!("unexpected signals: {:?}", signals),
+    }
+}
+
+#[test]
+fn test_server_playlists() {
+    let client = setup_server("server_playlists");
+    assert_eq!(client.playlists.playlist_count().unwrap(), 2);
+    assert!(!client.playlists.active_playlist().unwrap().valid);
+
+    let playlists = client.playlists.get_playlists(0, 10, PlaylistOrdering::Alphabetical, false).unwrap();
+    let names: Vec<&str> = playlists.iter().map(|playlist| &playlist.name as &str).collect();
+    assert_eq!(names, vec!["Jazz", "Rock"]);
+
+    client.playlists.activate_playlist(&playlists[1].id).unwrap();
+    let active_playlist = client.playlists.active_playlist().unwrap().into_option();
+    assert_eq!(active_playlist, Some(playlists[1].clone()));
+
+    // unknown playlists are ignored
+    client.playlists.activate_playlist("/playlist/unknown").unwrap();
+    assert_eq!(client.playlists.active_playlist().unwrap().into_option(), Some(playlists[1].clone()));
+}
@@ -0,0 +1,42 @@
+extern crate mpris;
+
+use mpris::PlaybackStatus;
+use mpris::client::{ChangedProperty, MprisClient, MprisSignal};
+use mpris::state::PlayerState;
+use mpris::test_support::MockPlayer;
+
+#[test]
+fn test_state_from_get_all() {
+    let mock = MockPlayer::builder().player_name("state_get_all").start().unwrap();
+    let client = MprisClient::new(mock.player_name(), 1000).unwrap();
+
+    let state = PlayerState::new(&client).unwrap();
+    assert_eq!(state.root.identity, Some("Mock player".to_string()));
+    assert_eq!(state.root.has_track_list, Some(false));
+    assert_eq!(state.player.playback_status, Some(PlaybackStatus::Stopped));
+    assert_eq!(state.player.metadata.unwrap().title(), Some("Mock track".to_string()));
+    assert_eq!(state.player.can_control, Some(true));
+}
+
+#[test]
+fn test_state_updates() {
+    let mock = MockPlayer::builder().player_name("state_updates").start().unwrap();
+    let client = MprisClient::new(mock.player_name(), 1000).unwrap();
+    let mut state = PlayerState::new(&client).unwrap();
+
+    // Changed properties are stored as they are announced.
+    mock.set(ChangedProperty::Volume(0.5));
+    let signal = client.signals(1000).next().unwrap();
+    assert!(state.update(&client, &signal).unwrap());
+    assert_eq!(state.player.volume, Some(0.5));
+
+    // Invalidated properties are read again.
+    mock.unset("DesktopEntry");
+    let signal = client.signals(1000).next().unwrap();
+    assert!(state.update(&client, &signal).unwrap());
+    assert_eq!(state.root.desktop_entry, None);
+
+    assert!(state.update(&client, &MprisSignal::Seeked { position: 1_000_000 }).unwrap());
+    assert_eq!(state.player.position, Some(1_000_000));
+    assert!(!state.update(&client, &MprisSignal::TrackRemoved { track_id: "/track/1".parse().unwrap() }).unwrap());
+}
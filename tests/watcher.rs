@@ -0,0 +1,126 @@
+extern crate dbus;
+extern crate mpris;
+
+use std::time::Duration;
+
+use dbus::NameFlag;
+
+use mpris::client::MprisSignal;
+use mpris::test_support::{MockPlayer, session_bus_address, session_connection};
+use mpris::watcher::{PlayerEvent, PlayerSignal, PlayerWatcher, SignalWatcher, WatcherEvent};
+
+/// Returns the next event of the player `bus_name`.
+fn next_event(watcher: &mut PlayerWatcher, bus_name: &str) -> Option<PlayerEvent> {
+    watcher.events(1000).find(|event| event.bus_name() == bus_name)
+}
+
+#[test]
+fn test_player_appeared_and_vanished() {
+    session_bus_address().unwrap();
+    let mut watcher = PlayerWatcher::new(1000).unwrap();
+
+    let mock = MockPlayer::builder().player_name("watched").start().unwrap();
+    let unique_bus_name = match next_event(&mut watcher, &mock.bus_name()) {
+        Some(PlayerEvent::PlayerAppeared { ref unique_bus_name, .. }) if unique_bus_name.starts_with(':') => {
+            unique_bus_name.clone()
+        }
+        event => panic!("unexpected event: {:?}", event),
+    };
+
+    drop(mock);
+    assert_eq!(next_event(&mut watcher, "org.mpris.MediaPlayer2.watched"), Some(PlayerEvent::PlayerVanished {
+        bus_name: "org.mpris.MediaPlayer2.watched".to_string(),
+        unique_bus_name,
+    }));
+}
+
+#[test]
+fn test_existing_players() {
+    let mock = MockPlayer::builder().player_name("existing").start().unwrap();
+    let mut watcher = PlayerWatcher::new(1000).unwrap();
+
+    let event = next_event(&mut watcher, &mock.bus_name()).unwrap();
+    assert_eq!(event.player_name(), "existing");
+    match event {
+        PlayerEvent::PlayerAppeared { .. } => {}
+        event => panic!("unexpected event: {:?}", event),
+    }
+}
+
+#[test]
+fn test_player_owner_changed() {
+    session_bus_address().unwrap();
+    let mut watcher = PlayerWatcher::new(1000).unwrap();
+    let bus_name = "org.mpris.MediaPlayer2.replaced";
+
+    let old_conn = session_connection().unwrap();
+    old_conn.register_name(bus_name, NameFlag::AllowReplacement.value()).unwrap();
+    let new_conn = session_connection().unwrap();
+    new_conn.register_name(bus_name, NameFlag::ReplaceExisting.value()).unwrap();
+
+    assert_eq!(next_event(&mut watcher, bus_name), Some(PlayerEvent::PlayerAppeared {
+        bus_name: bus_name.to_string(),
+        unique_bus_name: old_conn.unique_name(),
+    }));
+    assert_eq!(next_event(&mut watcher, bus_name), Some(PlayerEvent::PlayerOwnerChanged {
+        bus_name: bus_name.to_string(),
+        old_unique_bus_name: old_conn.unique_name(),
+        new_unique_bus_name: new_conn.unique_name(),
+    }));
+}
+
+#[test]
+fn test_signals_of_all_players() {
+    let first = MockPlayer::builder().player_name("signals_first").start().unwrap();
+    let mut watcher = SignalWatcher::new(1000).unwrap();
+    assert!(watcher.bus_names().contains(&"org.mpris.MediaPlayer2.signals_first"));
+
+    // The second player appears after the watcher was created.
+    let second = MockPlayer::builder().player_name("signals_second").start().unwrap();
+    first.emit_after(MprisSignal::Seeked { position: 1_000_000 }, Duration::from_millis(100));
+    second.emit_after(MprisSignal::Seeked { position: 2_000_000 }, Duration::from_millis(200));
+
+    let mut signals: Vec<PlayerSignal> = watcher.signals(1000)
+        .filter(|signal| signal.player_name().starts_with("signals_"))
+        .take(2)
+        .collect();
+    signals.sort_by(|a, b| a.bus_name.cmp(&b.bus_name));
+    assert_eq!(signals, vec![
+        PlayerSignal {
+            bus_name: first.bus_name(),
+            signal: MprisSignal::Seeked { position: 1_000_000 },
+        },
+        PlayerSignal {
+            bus_name: second.bus_name(),
+            signal: MprisSignal::Seeked { position: 2_000_000 },
+        },
+    ]);
+}
+
+#[test]
+fn test_watcher_events() {
+    let mock = MockPlayer::builder().player_name("events").start().unwrap();
+    let mut watcher = SignalWatcher::new(1000).unwrap();
+    let bus_name = mock.bus_name();
+    let mut events = || {
+        watcher.events(1000).find(|event| match *event {
+            WatcherEvent::Player(ref event) => event.bus_name() == bus_name,
+            WatcherEvent::Signal(ref signal) => signal.bus_name == bus_name,
+        })
+    };
+
+    match events() {
+        Some(WatcherEvent::Player(PlayerEvent::PlayerAppeared { .. })) => {}
+        event => panic!("unexpected event: {:?}", event),
+    }
+    mock.emit(MprisSignal::Seeked { position: 42 });
+    assert_eq!(events(), Some(WatcherEvent::Signal(PlayerSignal {
+        bus_name: mock.bus_name(),
+        signal: MprisSignal::Seeked { position: 42 },
+    })));
+    drop(mock);
+    match events() {
+        Some(WatcherEvent::Player(PlayerEvent::PlayerVanished { .. })) => {}
+        event => panic!("unexpected event: {:?}", event),
+    }
+}